@@ -1,25 +1,158 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
 use colored::*;
+use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::automock;
+use regex::{Captures, Regex};
+use serde::Serialize;
 
+use crate::config::ColorTheme;
+use crate::console_output::ConsoleOutput;
+use crate::git::LogEntry;
 use crate::note::Note;
-use crate::search_match::SearchMatch;
+use crate::repository::RepositoryDir;
+use crate::search_match::{ContextLine, HistoryMatch, SearchMatch, TermScore};
+
+lazy_static! {
+    /// Removed and added words of 'git diff --word-diff=plain'
+    static ref WORD_CHANGE: Regex = Regex::new(r"\[-.*?-\]|\{\+.*?\+\}").unwrap();
+}
 
+#[derive(Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Element of command output, with the name of its notebook if all notebooks are used
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NotebookItem<T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notebook: Option<String>,
+    #[serde(flatten)]
+    pub item: T,
+}
+
+/// Change made to a note by a command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum NoteChange {
+    Created {
+        #[serde(serialize_with = "crate::json_format::lossy_path")]
+        path: PathBuf,
+    },
+    Edited {
+        #[serde(serialize_with = "crate::json_format::lossy_path")]
+        path: PathBuf,
+    },
+    Deleted {
+        #[serde(serialize_with = "crate::json_format::lossy_path")]
+        path: PathBuf,
+    },
+    Moved {
+        #[serde(serialize_with = "crate::json_format::lossy_path")]
+        path: PathBuf,
+        destination: String,
+    },
+    Tagged {
+        #[serde(serialize_with = "crate::json_format::lossy_path")]
+        path: PathBuf,
+        tags: Vec<String>,
+    },
+    Restored {
+        #[serde(serialize_with = "crate::json_format::lossy_path")]
+        path: PathBuf,
+        revision: String,
+    },
+}
+
+/// Outputs of commands are formatted as a whole, so that they can be displayed as text or as
+/// JSON documents.
 #[cfg_attr(test, automock)]
 pub trait CliFormat {
+    fn note_tree(&self, directories: &[NotebookItem<RepositoryDir>]) -> String;
+    /// Scope is the searched directory, relative to repositories. It is empty if whole repositories were searched.
+    fn search_results(&self, needle: &str, scope: &Path, matches: &[NotebookItem<SearchMatch>], explain: bool) -> String;
+    fn tag_list(&self, tags: &BTreeMap<String, usize>) -> String;
+    /// Versions of notes found with search --history, scope is the same as in search_results()
+    fn history_results(&self, needle: &str, scope: &Path, matches: &[HistoryMatch]) -> String;
+    /// Commits that changed a note, path is current path of note relative to repository
+    fn note_history(&self, note: &Note, path: &Path, commits: &[LogEntry]) -> String;
+    /// Unified diff, as produced by git diff. If word is true, lines contain changed words
+    /// marked with [-removed-] and {+added+}.
+    fn diff(&self, diff: &str, word: bool) -> String;
+    /// Full content of a note
+    fn note_content(&self, note: &Note) -> String;
+    fn note_changes(&self, changes: &[NoteChange]) -> String;
+    /// Outcome of a command that does not produce data, e.g. a cancellation
+    fn message(&self, message: &str) -> String;
+    fn config_values(&self, values: &[(String, String)]) -> String;
+    fn config_value(&self, key: &str, value: &str) -> String;
+    /// Configuration value saved in file at path
+    fn config_saved(&self, key: &str, value: &str, path: &Path) -> String;
+    /// Output of Git, editor and banners, which is not part of command result
+    fn command_output(&self, output: ConsoleOutput) -> ConsoleOutput;
     fn search_match(&self, search_m: &SearchMatch) -> String;
     fn note_list_item(&self, note: &Note) -> String;
-    fn match_score(&self, score: usize) -> String;
+    fn match_score(&self, score: f64) -> String;
+    fn score_explanation(&self, explanation: &[TermScore]) -> String;
     fn note_id(&self, id: usize) -> String;
     fn note_title(&self, title: &str) -> String;
     fn note_directory(&self, name: &str) -> String;
+    fn tag_list_item(&self, tag: &str, count: usize) -> String;
+    fn notebook_name(&self, name: &str) -> String;
 }
 
-pub struct CliFormatImpl;
+pub struct CliFormatImpl {
+    id_color: Color,
+    title_color: Color,
+    match_color: Color,
+}
 
 impl CliFormatImpl {
     pub fn new() -> Self {
-        CliFormatImpl {}
+        CliFormatImpl::with_theme(&ColorTheme::Default)
+    }
+
+    /// Theme 'none' is handled by disabling colors globally
+    pub fn with_theme(theme: &ColorTheme) -> Self {
+        match theme {
+            ColorTheme::Light => CliFormatImpl {
+                id_color: Color::Blue,
+                title_color: Color::Magenta,
+                match_color: Color::Red,
+            },
+            _ => CliFormatImpl {
+                id_color: Color::Green,
+                title_color: Color::Cyan,
+                match_color: Color::Yellow,
+            },
+        }
+    }
+
+    fn notebook_prefix(&self, notebook: &Option<String>) -> String {
+        match notebook {
+            Some(name) => format!("{} ", self.notebook_name(name)),
+            None => "".to_string(),
+        }
+    }
+
+    /// Colorize specified byte ranges of text. Invalid ranges are ignored.
+    fn highlight(&self, text: &str, matches: &[Range<usize>]) -> String {
+        let mut result = String::new();
+        let mut position = 0;
+        for range in matches {
+            if let (Some(before), Some(matched)) = (text.get(position..range.start), text.get(range.clone())) {
+                result.push_str(before);
+                result.push_str(&matched.color(self.match_color).to_string());
+                position = range.end;
+            }
+        }
+        result.push_str(&text[position..]);
+        result
     }
 }
 
@@ -30,40 +163,198 @@ impl Default for CliFormatImpl {
 }
 
 impl CliFormat for CliFormatImpl {
+    fn note_tree(&self, directories: &[NotebookItem<RepositoryDir>]) -> String {
+        let mut out = String::new();
+        for NotebookItem { notebook, item: directory } in directories {
+            let prefix = self.notebook_prefix(notebook);
+            let pad = "  ".repeat(directory.level);
+            out.push_str(&format!("{}{}{}\n", prefix, pad, self.note_directory(&directory.name)));
+            directory
+                .notes
+                .iter()
+                .for_each(|n| out.push_str(&format!("{}{}{}\n", prefix, pad, self.note_list_item(n))));
+            out.push('\n');
+        }
+        out
+    }
+
+    fn search_results(&self, needle: &str, scope: &Path, matches: &[NotebookItem<SearchMatch>], explain: bool) -> String {
+        let mut out = String::new();
+        for NotebookItem { notebook, item: search_m } in matches {
+            out.push_str(&format!("{}{}\n", self.notebook_prefix(notebook), self.search_match(search_m)));
+            if explain && !search_m.explanation.is_empty() {
+                out.push_str(&format!("{}\n", self.score_explanation(&search_m.explanation)));
+            }
+            out.push('\n');
+        }
+
+        match scope.to_str() {
+            Some("") | None => out.push_str(&format!("{} results found for '{}'\n", matches.len(), needle)),
+            Some(scope) => out.push_str(&format!("{} results found for '{}' in {}\n", matches.len(), needle, scope)),
+        }
+        out
+    }
+
+    fn tag_list(&self, tags: &BTreeMap<String, usize>) -> String {
+        let mut out = String::new();
+        tags.iter()
+            .for_each(|(tag, count)| out.push_str(&format!("{}\n", self.tag_list_item(tag, *count))));
+        out.push_str(&format!("\n{} tags found\n", tags.len()));
+        out
+    }
+
+    fn history_results(&self, needle: &str, scope: &Path, matches: &[HistoryMatch]) -> String {
+        let mut out = String::new();
+        for history_m in matches {
+            let path = history_m.path.to_str().unwrap_or("");
+            out.push_str(&format!(
+                "{} {} {}\n",
+                history_m.commit.color(self.id_color),
+                history_m.date,
+                path.color(self.title_color)
+            ));
+            for line in &history_m.lines {
+                let sign = match line.added {
+                    true => "+".green(),
+                    false => "-".red(),
+                };
+                out.push_str(&format!("{} {}\n", sign, self.highlight(&line.content, &line.matches)));
+            }
+            out.push('\n');
+        }
+
+        match scope.to_str() {
+            Some("") | None => out.push_str(&format!("{} versions found for '{}'\n", matches.len(), needle)),
+            Some(scope) => out.push_str(&format!("{} versions found for '{}' in {}\n", matches.len(), needle, scope)),
+        }
+        if !matches.is_empty() {
+            out.push_str(
+                &"Restore a version with: notes search --history <needle> --restore <commit>\n"
+                    .dimmed()
+                    .to_string(),
+            );
+        }
+        out
+    }
+
+    fn note_history(&self, note: &Note, path: &Path, commits: &[LogEntry]) -> String {
+        let path_str = path.to_str().unwrap_or("");
+        let mut out = format!("{} {} {}\n\n", self.note_id(note.id), self.note_title(&note.title), path_str.dimmed());
+        for entry in commits {
+            out.push_str(&format!(
+                "{} {} {}: {} {} {}",
+                entry.commit.color(self.id_color),
+                entry.date,
+                entry.author,
+                entry.message,
+                format!("+{}", entry.added).green(),
+                format!("-{}", entry.removed).red()
+            ));
+            // Note was moved since this commit
+            if entry.path != path {
+                out.push_str(&format!(" {}", format!("({})", entry.path.to_str().unwrap_or("")).dimmed()));
+            }
+            out.push('\n');
+        }
+        if !commits.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("{} commits found\n", commits.len()));
+        out
+    }
+
+    fn diff(&self, diff: &str, word: bool) -> String {
+        if diff.is_empty() {
+            return "No changes\n".to_string();
+        }
+        let mut out = String::new();
+        // Headers of a file go from 'diff' line to its first hunk
+        let mut in_header = false;
+        for line in diff.lines() {
+            if line.starts_with("diff ") {
+                in_header = true;
+            } else if line.starts_with("@@") {
+                in_header = false;
+            }
+            let colored_line = match line.chars().next() {
+                _ if in_header => line.bold().to_string(),
+                Some('@') => line.cyan().to_string(),
+                _ if word => WORD_CHANGE
+                    .replace_all(line, |c: &Captures| match c[0].starts_with('[') {
+                        true => c[0].red().to_string(),
+                        false => c[0].green().to_string(),
+                    })
+                    .to_string(),
+                Some('+') => line.green().to_string(),
+                Some('-') => line.red().to_string(),
+                _ => line.to_string(),
+            };
+            out.push_str(&format!("{}\n", colored_line));
+        }
+        out
+    }
+
+    fn note_content(&self, note: &Note) -> String {
+        format!("{}\n", note.content())
+    }
+
+    fn note_changes(&self, changes: &[NoteChange]) -> String {
+        let mut out = String::from("\n");
+        for change in changes {
+            let line = match change {
+                NoteChange::Created { path } => format!("Note '{}' created", path.display()),
+                NoteChange::Edited { path } => format!("Note '{}' edited", path.display()),
+                NoteChange::Deleted { path } => format!("Note '{}' deleted", path.display()),
+                NoteChange::Moved { path, destination } => format!("Note '{}' moved to '{}'", path.display(), destination),
+                NoteChange::Tagged { path, tags } => format!("Note '{}' tags: {}", path.display(), tags.join(", ")),
+                NoteChange::Restored { path, revision } => format!("Note '{}' restored to {}", path.display(), revision),
+            };
+            out.push_str(&format!("{}\n", line));
+        }
+        out
+    }
+
+    fn message(&self, message: &str) -> String {
+        format!("{}\n", message)
+    }
+
+    fn config_values(&self, values: &[(String, String)]) -> String {
+        values.iter().map(|(key, value)| format!("{} = {}\n", key, value)).collect()
+    }
+
+    fn config_value(&self, _key: &str, value: &str) -> String {
+        format!("{}\n", value)
+    }
+
+    fn config_saved(&self, key: &str, value: &str, path: &Path) -> String {
+        format!("\n{} = {} saved in {}\n", key, value, path.display())
+    }
+
+    fn command_output(&self, output: ConsoleOutput) -> ConsoleOutput {
+        output
+    }
+
     fn search_match(&self, search_m: &SearchMatch) -> String {
         let id = self.note_id(search_m.id);
         let title = self.note_title(&search_m.title);
         let score = self.match_score(search_m.score);
         let header = format!("{} {} {} \n", id, title, score);
 
-        let total_match = search_m.matched_lines.len();
-        let mut body: Vec<String> = search_m
-            .matched_lines
-            .iter()
-            .enumerate()
-            .map(|(mnbr, raw_line)| {
-                let match_highlighted = raw_line.matched.yellow().to_string();
-                let line_nbr = format!("{}.", raw_line.display_number).dimmed();
-                let previous_nbr = format!("{}.", raw_line.display_number - 1).dimmed();
-                let next_nbr = format!("{}.", raw_line.display_number + 1).dimmed();
-
-                let previous = &raw_line.previous.as_ref().map(|l| format!("{} {}", previous_nbr, l.dimmed()));
-                let next = &raw_line.next.as_ref().map(|l| format!("{} {}", next_nbr, l.dimmed()));
-
-                let line = format!("{:2} {}", line_nbr, raw_line.content.replace(&raw_line.matched, &match_highlighted));
-
-                let is_last = mnbr == total_match - 1;
-                match (previous, next, is_last) {
-                    (Some(p), Some(n), false) => format!("{}\n{}\n{}\n", p, line, n),
-                    (Some(p), Some(n), true) => format!("{}\n{}\n{}", p, line, n),
-                    (Some(p), None, false) => format!("{}\n{}\n", p, line),
-                    (Some(p), None, true) => format!("{}\n{}", p, line),
-                    (None, Some(n), false) => format!("{}\n{}\n", line, n),
-                    (None, Some(n), true) => format!("{}\n{}", line, n),
-                    (None, None, _) => line,
-                }
-            })
-            .collect();
+        let context_line = |l: &ContextLine| format!("{} {}", format!("{}.", l.display_number).dimmed(), l.content.dimmed());
+        let mut body: Vec<String> = vec![];
+        // Number of the last displayed line, a separator is displayed before lines that do not follow it
+        let mut last_displayed: Option<usize> = None;
+        for raw_line in &search_m.matched_lines {
+            let first = raw_line.before.first().map_or(raw_line.display_number, |l| l.display_number);
+            if matches!(last_displayed, Some(last) if first > last + 1) {
+                body.push("--".dimmed().to_string());
+            }
+            body.extend(raw_line.before.iter().map(context_line));
+            let line_nbr = format!("{}.", raw_line.display_number).dimmed();
+            body.push(format!("{:2} {}", line_nbr, self.highlight(&raw_line.content, &raw_line.matches)));
+            body.extend(raw_line.after.iter().map(context_line));
+            last_displayed = Some(raw_line.after.last().map_or(raw_line.display_number, |l| l.display_number));
+        }
 
         // If no match was provided, this is because note is empty, otherwise we have the first lines of note
         if body.is_empty() {
@@ -77,39 +368,71 @@ impl CliFormat for CliFormatImpl {
         format!(" {} - {}", self.note_id(note.id), self.note_title(&note.title))
     }
 
-    fn match_score(&self, score: usize) -> String {
-        format!("(Score: {})", score.to_string()).dimmed().to_string()
+    fn match_score(&self, score: f64) -> String {
+        format!("(Score: {:.2})", score).dimmed().to_string()
+    }
+
+    fn score_explanation(&self, explanation: &[TermScore]) -> String {
+        let lines: Vec<String> = explanation
+            .iter()
+            .map(|t| {
+                let details = format!(
+                    "(idf {:.2}, title {}, headings {}, path {}, body {})",
+                    t.idf, t.title, t.headings, t.path, t.body
+                );
+                format!("  {} {:.2} {}", t.term.color(self.match_color), t.score, details.dimmed())
+            })
+            .collect();
+        lines.join("\n")
     }
 
     fn note_id(&self, id: usize) -> String {
-        format!("@{}", id).green().to_string()
+        format!("@{}", id).color(self.id_color).to_string()
     }
 
     fn note_title(&self, title: &str) -> String {
-        format!("{}", title.cyan())
+        format!("{}", title.color(self.title_color))
     }
 
     fn note_directory(&self, name: &str) -> String {
         format!(" 🗁  {}", name)
     }
+
+    fn tag_list_item(&self, tag: &str, count: usize) -> String {
+        format!(" {} {}", format!("#{}", tag).color(self.id_color), format!("({})", count).dimmed())
+    }
+
+    fn notebook_name(&self, name: &str) -> String {
+        format!("[{}]", name).bold().to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::search_match::MatchedLine;
+    use crate::search_match::{HistoryLine, MatchedLine};
 
     fn init() {
         // We disable colors for test
         control::set_override(false);
     }
 
+    fn context(lines: &[(usize, &str)]) -> Vec<ContextLine> {
+        lines
+            .iter()
+            .map(|(display_number, content)| ContextLine {
+                display_number: *display_number,
+                content: content.to_string(),
+            })
+            .collect()
+    }
+
     #[test]
-    pub fn search_match_no_previous_no_next() {
+    pub fn search_match_without_context() {
         init();
         let search_m = SearchMatch {
             id: 0,
-            score: 4,
+            score: 4.0,
             path: "/tmp/note-1.txt".into(),
             title: "# What a note !".to_string(),
             matched_lines: vec![
@@ -117,34 +440,35 @@ mod tests {
                     display_number: 3,
                     line_number: 2,
                     content: "A very interesting one".to_string(),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: None,
+                    matches: vec![],
+                    before: vec![],
+                    after: vec![],
                 },
                 MatchedLine {
                     display_number: 4,
                     line_number: 3,
                     content: "With very interesting things inside".to_string(),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: None,
+                    matches: vec![],
+                    before: vec![],
+                    after: vec![],
                 },
             ],
+            explanation: vec![],
         };
 
         let fmt = CliFormatImpl::default();
         let actual = fmt.search_match(&search_m);
-        let expected = "@0 # What a note ! (Score: 4) \n3. A very interesting one\n4. With very interesting things inside".to_string();
+        let expected = "@0 # What a note ! (Score: 4.00) \n3. A very interesting one\n4. With very interesting things inside".to_string();
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    pub fn search_match_previous_no_next() {
+    pub fn search_match_with_context() {
         init();
         let search_m = SearchMatch {
             id: 0,
-            score: 4,
+            score: 4.0,
             path: "/tmp/note-1.txt".into(),
             title: "# What a note !".to_string(),
             matched_lines: vec![
@@ -152,36 +476,37 @@ mod tests {
                     display_number: 3,
                     line_number: 2,
                     content: "A very interesting one".to_string(),
-                    matched: "".to_string(),
-                    previous: Some("Previous line 1".to_string()),
-                    next: None,
+                    matches: vec![],
+                    before: context(&[(2, "Previous line")]),
+                    after: context(&[(4, "Next line 1")]),
                 },
                 MatchedLine {
-                    display_number: 4,
-                    line_number: 3,
+                    display_number: 5,
+                    line_number: 4,
                     content: "With very interesting things inside".to_string(),
-                    matched: "".to_string(),
-                    previous: Some("Previous line 2".to_string()),
-                    next: None,
+                    matches: vec![],
+                    before: vec![],
+                    after: context(&[(6, "Next line 2")]),
                 },
             ],
+            explanation: vec![],
         };
 
         let fmt = CliFormatImpl::default();
         let actual = fmt.search_match(&search_m);
-        let expected =
-            "@0 # What a note ! (Score: 4) \n2. Previous line 1\n3. A very interesting one\n\n3. Previous line 2\n4. With very interesting things inside"
-                .to_string();
+        let expected = "@0 # What a note ! (Score: 4.00) \n2. Previous line\n3. A very interesting one\n4. Next line 1\n\
+                        5. With very interesting things inside\n6. Next line 2"
+            .to_string();
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    pub fn search_match_no_previous_next() {
+    pub fn search_match_should_separate_context() {
         init();
         let search_m = SearchMatch {
             id: 0,
-            score: 4,
+            score: 4.0,
             path: "/tmp/note-1.txt".into(),
             title: "# What a note !".to_string(),
             matched_lines: vec![
@@ -189,62 +514,67 @@ mod tests {
                     display_number: 3,
                     line_number: 2,
                     content: "A very interesting one".to_string(),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: Some("Next line 1".to_string()),
+                    matches: vec![],
+                    before: vec![],
+                    after: context(&[(4, "Next line 1")]),
                 },
                 MatchedLine {
-                    display_number: 4,
-                    line_number: 3,
+                    display_number: 7,
+                    line_number: 6,
                     content: "With very interesting things inside".to_string(),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: Some("Next line 2".to_string()),
+                    matches: vec![],
+                    before: context(&[(6, "Previous line 2")]),
+                    after: vec![],
+                },
+                MatchedLine {
+                    display_number: 9,
+                    line_number: 8,
+                    content: "The end".to_string(),
+                    matches: vec![],
+                    before: vec![],
+                    after: vec![],
                 },
             ],
+            explanation: vec![],
         };
 
         let fmt = CliFormatImpl::default();
         let actual = fmt.search_match(&search_m);
-        let expected =
-            "@0 # What a note ! (Score: 4) \n3. A very interesting one\n4. Next line 1\n\n4. With very interesting things inside\n5. Next line 2".to_string();
+        let expected = "@0 # What a note ! (Score: 4.00) \n3. A very interesting one\n4. Next line 1\n--\n\
+                        6. Previous line 2\n7. With very interesting things inside\n--\n9. The end"
+            .to_string();
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    pub fn search_match_previous_next() {
+    fn score_explanation() {
         init();
-        let search_m = SearchMatch {
-            id: 0,
-            score: 4,
-            path: "/tmp/note-1.txt".into(),
-            title: "# What a note !".to_string(),
-            matched_lines: vec![
-                MatchedLine {
-                    display_number: 3,
-                    line_number: 2,
-                    content: "A very interesting one".to_string(),
-                    matched: "".to_string(),
-                    previous: Some("Previous line 1".to_string()),
-                    next: Some("Next line 1".to_string()),
-                },
-                MatchedLine {
-                    display_number: 4,
-                    line_number: 3,
-                    content: "With very interesting things inside".to_string(),
-                    matched: "".to_string(),
-                    previous: Some("Previous line 2".to_string()),
-                    next: Some("Next line 2".to_string()),
-                },
-            ],
-        };
-
+        let explanation = vec![
+            TermScore {
+                term: "ssh".to_string(),
+                title: 1,
+                headings: 0,
+                path: 2,
+                body: 3,
+                idf: 0.4701,
+                score: 0.9812,
+            },
+            TermScore {
+                term: "rsync".to_string(),
+                title: 0,
+                headings: 1,
+                path: 0,
+                body: 0,
+                idf: 1.2,
+                score: 1.5,
+            },
+        ];
         let fmt = CliFormatImpl::default();
-        let actual = fmt.search_match(&search_m);
-        let expected = "@0 # What a note ! (Score: 4) \n2. Previous line 1\n3. A very interesting one\n4. Next line 1\n\n3. Previous line 2\n4. With very interesting things inside\n5. Next line 2".to_string();
-
-        assert_eq!(actual, expected);
+        assert_eq!(
+            fmt.score_explanation(&explanation),
+            "  ssh 0.98 (idf 0.47, title 1, headings 0, path 2, body 3)\n  rsync 1.50 (idf 1.20, title 0, headings 1, path 0, body 0)"
+        );
     }
 
     #[test]
@@ -267,4 +597,197 @@ mod tests {
         let fmt = CliFormatImpl::default();
         assert_eq!(fmt.note_directory("# abcd"), " 🗁  # abcd");
     }
+
+    #[test]
+    fn tag_list_item() {
+        init();
+        let fmt = CliFormatImpl::default();
+        assert_eq!(fmt.tag_list_item("rsync", 3), " #rsync (3)");
+    }
+
+    #[test]
+    pub fn highlight() {
+        init();
+        let fmt = CliFormatImpl::default();
+        assert_eq!(fmt.highlight("ssh or rsync over ssh", &[0..3, 7..12, 18..21]), "ssh or rsync over ssh");
+        assert_eq!(fmt.highlight("état", &[1..2, 2..10]), "état");
+    }
+
+    #[test]
+    pub fn notebook_name() {
+        init();
+        let formatter = CliFormatImpl::new();
+        assert_eq!(formatter.notebook_name("work"), "[work]");
+    }
+
+    #[test]
+    pub fn note_tree() {
+        init();
+        let note = Note::from(2, "/repo/tools/rsync.md".into(), "# Rsync".to_string()).unwrap();
+        let directories = vec![NotebookItem {
+            notebook: Some("work".to_string()),
+            item: RepositoryDir {
+                name: "tools".to_string(),
+                path: "/repo/tools".into(),
+                notes: vec![note],
+                level: 1,
+            },
+        }];
+        let fmt = CliFormatImpl::default();
+        assert_eq!(fmt.note_tree(&directories), "[work]    🗁  tools\n[work]    @2 - # Rsync\n\n");
+    }
+
+    #[test]
+    pub fn search_results() {
+        init();
+        let search_m = SearchMatch {
+            id: 2,
+            score: 1.0,
+            path: "/repo/tools/rsync.md".into(),
+            title: "# Rsync".to_string(),
+            matched_lines: vec![],
+            explanation: vec![TermScore {
+                term: "rsync".to_string(),
+                title: 1,
+                headings: 0,
+                path: 1,
+                body: 0,
+                idf: 0.5,
+                score: 1.0,
+            }],
+        };
+        let matches = vec![NotebookItem {
+            notebook: None,
+            item: search_m,
+        }];
+        let fmt = CliFormatImpl::default();
+        assert_eq!(
+            fmt.search_results("rsync", Path::new("tools"), &matches, true),
+            "@2 # Rsync (Score: 1.00) \n... This note is empty ...\n  rsync 1.00 (idf 0.50, title 1, headings 0, path 1, body 0)\n\n1 results found for 'rsync' in tools\n"
+        );
+        assert_eq!(
+            fmt.search_results("rsync", Path::new(""), &matches, false),
+            "@2 # Rsync (Score: 1.00) \n... This note is empty ...\n\n1 results found for 'rsync'\n"
+        );
+    }
+
+    #[test]
+    pub fn tag_list() {
+        init();
+        let mut tags = BTreeMap::new();
+        tags.insert("rsync".to_string(), 2);
+        let fmt = CliFormatImpl::default();
+        assert_eq!(fmt.tag_list(&tags), " #rsync (2)\n\n1 tags found\n");
+    }
+
+    #[test]
+    pub fn history_results() {
+        init();
+        let matches = vec![HistoryMatch {
+            commit: "00c1325".to_string(),
+            date: "2020-10-18".to_string(),
+            path: "tools/ssh.md".into(),
+            revision: "00c1325^".to_string(),
+            lines: vec![
+                HistoryLine {
+                    added: false,
+                    content: "Use rsync".to_string(),
+                    matches: vec![Range { start: 4, end: 9 }],
+                },
+                HistoryLine {
+                    added: true,
+                    content: "Use scp".to_string(),
+                    matches: vec![],
+                },
+            ],
+        }];
+        let fmt = CliFormatImpl::default();
+        assert_eq!(
+            fmt.history_results("rsync", Path::new(""), &matches),
+            "00c1325 2020-10-18 tools/ssh.md\n- Use rsync\n+ Use scp\n\n1 versions found for 'rsync'\n\
+             Restore a version with: notes search --history <needle> --restore <commit>\n"
+        );
+        assert_eq!(fmt.history_results("rsync", Path::new("tools"), &[]), "0 versions found for 'rsync' in tools\n");
+    }
+
+    #[test]
+    pub fn diff() {
+        init();
+        let diff = "diff --git a/a.md b/a.md\n--- a/a.md\n+++ b/a.md\n@@ -1 +1 @@\n--- old\n+new\n";
+        assert_eq!(CliFormatImpl::default().diff(diff, false), diff);
+        assert_eq!(CliFormatImpl::default().diff("", false), "No changes\n");
+    }
+
+    #[test]
+    pub fn word_diff() {
+        init();
+        // Changed words keep their markers, so that they are visible without colors
+        let diff = "diff --git a/a.md b/a.md\n@@ -1 +1 @@\n- list [-old-]{+new+} item\n";
+        assert_eq!(CliFormatImpl::default().diff(diff, true), diff);
+    }
+
+    #[test]
+    pub fn note_changes() {
+        let changes = vec![
+            NoteChange::Moved {
+                path: "/repo/a.md".into(),
+                destination: "tools/".to_string(),
+            },
+            NoteChange::Tagged {
+                path: "/repo/b.md".into(),
+                tags: vec!["ssh".to_string(), "network".to_string()],
+            },
+            NoteChange::Restored {
+                path: "c.md".into(),
+                revision: "00c1325".to_string(),
+            },
+        ];
+        assert_eq!(
+            CliFormatImpl::default().note_changes(&changes),
+            "\nNote '/repo/a.md' moved to 'tools/'\nNote '/repo/b.md' tags: ssh, network\nNote 'c.md' restored to 00c1325\n"
+        );
+    }
+
+    #[test]
+    pub fn config_values() {
+        let fmt = CliFormatImpl::default();
+        let values = vec![("editor".to_string(), "vim".to_string()), ("auto_push".to_string(), "false".to_string())];
+        assert_eq!(fmt.config_values(&values), "editor = vim\nauto_push = false\n");
+        assert_eq!(fmt.config_value("editor", "vim"), "vim\n");
+        assert_eq!(
+            fmt.config_saved("editor", "vim", Path::new("/repo/.notes/config.toml")),
+            "\neditor = vim saved in /repo/.notes/config.toml\n"
+        );
+    }
+
+    #[test]
+    pub fn note_history() {
+        init();
+        let note = Note::from(3, "/repo/tools/rsync.md".into(), "# Rsync".to_string()).unwrap();
+        let entry = |commit: &str, message: &str, path: &str| LogEntry {
+            commit: commit.to_string(),
+            date: "2020-10-18".to_string(),
+            author: "Jane Doe".to_string(),
+            message: message.to_string(),
+            path: path.into(),
+            added: 3,
+            removed: 1,
+        };
+        let commits = vec![
+            entry("00c1325", "Update note rsync.md", "tools/rsync.md"),
+            entry("58d921d", "Create note", "rsync.md"),
+        ];
+        let fmt = CliFormatImpl::default();
+        assert_eq!(
+            fmt.note_history(&note, Path::new("tools/rsync.md"), &commits),
+            "@3 # Rsync tools/rsync.md\n\n\
+             00c1325 2020-10-18 Jane Doe: Update note rsync.md +3 -1\n\
+             58d921d 2020-10-18 Jane Doe: Create note +3 -1 (rsync.md)\n\n\
+             2 commits found\n"
+        );
+        assert_eq!(
+            fmt.note_history(&note, Path::new("tools/rsync.md"), &[]),
+            "@3 # Rsync tools/rsync.md\n\n0 commits found\n"
+        );
+    }
 }
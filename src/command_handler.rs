@@ -1,45 +1,242 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
 use regex::RegexBuilder;
 
 use crate::banners::Banners;
-use crate::cli_format::CliFormat;
+use crate::cli_format::{CliFormat, NoteChange, NotebookItem};
+use crate::config::Config;
+use crate::config_file::{ConfigFile, CONFIG_KEYS, USER_CONFIG_KEYS};
 use crate::console_output::ConsoleOutput;
 use crate::default_error::DefaultError;
+use crate::git::check_extended_regexp;
 use crate::note::Note;
-use crate::repository::Repository;
-use crate::search_match::SearchMatch;
+use crate::note_selector::NoteSelector;
+use crate::prompt::Prompt;
+use crate::ranking::Ranking;
+use crate::repository::{Repository, RepositoryDir};
+use crate::search_match::{HistoryMatch, SearchMatch};
+use crate::search_query::{Query, SearchOptions};
 use crate::usage::usage;
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
-    New { path: String },
-    List,
-    Search { needle: String },
-    Edit { id: usize },
-    Delete { id: usize },
+    New {
+        path: String,
+    },
+    /// Directory is relative to repository root
+    List {
+        tag: Option<String>,
+        all_notebooks: bool,
+        directory: Option<String>,
+    },
+    Search {
+        needle: String,
+        tag: Option<String>,
+        all_notebooks: bool,
+        options: SearchOptions,
+        explain: bool,
+        directory: Option<String>,
+    },
+    /// Search versions of notes where needle was added or removed. If restore is set, versions of
+    /// matching commit are restored instead of being displayed.
+    SearchHistory {
+        needle: String,
+        directory: Option<String>,
+        restore: Option<String>,
+    },
+    Edit {
+        id: usize,
+    },
+    History {
+        id: usize,
+    },
+    /// Show changes of a note, or of whole repository if id is None. Range is a revision or 'A..B',
+    /// uncommitted changes are shown if it is None.
+    Diff {
+        id: Option<usize>,
+        range: Option<String>,
+        word: bool,
+    },
+    /// Restore a note to an earlier version. If confirmed is false, user confirms after a preview.
+    Restore {
+        id: usize,
+        revision: Revision,
+        confirmed: bool,
+    },
+    /// If action is None, user chooses it in picker
+    Pick {
+        action: Option<PickAction>,
+    },
+    /// If several notes are selected and confirmed is false, user confirms deletion
+    Delete {
+        selectors: Vec<NoteSelector>,
+        confirmed: bool,
+    },
+    DeleteDirectory {
+        path: String,
+        confirmed: bool,
+    },
+    Move {
+        selectors: Vec<NoteSelector>,
+        destination: String,
+    },
+    Tags,
+    Tag {
+        action: TagAction,
+        selectors: Vec<NoteSelector>,
+        tag: String,
+    },
+    Config {
+        action: ConfigAction,
+    },
     Push,
     Pull,
+    Sync,
     Help,
 }
 
+/// Version of a note to restore
+#[derive(Debug, PartialEq)]
+pub enum Revision {
+    Commit(String),
+    /// Number of versions to go back in history of note, 1 is the version before the last commit
+    Steps(usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigAction {
+    List,
+    Get {
+        key: String,
+    },
+    /// If global is true, user configuration is modified, otherwise repository configuration
+    Set {
+        key: String,
+        value: String,
+        global: bool,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TagAction {
+    Add,
+    Remove,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PickAction {
+    Edit,
+    Show,
+    Delete,
+    Move,
+}
+
+impl PickAction {
+    pub const ALL: [PickAction; 4] = [PickAction::Edit, PickAction::Show, PickAction::Delete, PickAction::Move];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PickAction::Edit => "edit",
+            PickAction::Show => "show",
+            PickAction::Delete => "delete",
+            PickAction::Move => "move",
+        }
+    }
+}
+
+impl Command {
+    pub fn uses_all_notebooks(&self) -> bool {
+        match self {
+            Command::List { all_notebooks, .. } => *all_notebooks,
+            Command::Search { all_notebooks, .. } => *all_notebooks,
+            _ => false,
+        }
+    }
+
+    /// Push and pull only display Git output, help is only for humans
+    pub fn has_json_output(&self) -> bool {
+        !matches!(self, Command::Push | Command::Pull | Command::Help)
+    }
+}
+
+/// Named repository, declared in configuration
+pub struct Notebook<'a> {
+    pub name: String,
+    pub repository: &'a dyn Repository,
+}
+
 pub struct CommandHandler<'a> {
+    config: &'a Config,
     repository: &'a dyn Repository,
     formatter: &'a dyn CliFormat,
+    prompt_formatter: &'a dyn CliFormat,
+    prompt: &'a dyn Prompt,
+    notebooks: Vec<Notebook<'a>>,
+    current_directory: Option<PathBuf>,
 }
 
 impl<'a> CommandHandler<'a> {
-    pub fn new(repository: &'a dyn Repository, formatter: &'a dyn CliFormat) -> CommandHandler<'a> {
-        CommandHandler { repository, formatter }
+    pub fn new(config: &'a Config, repository: &'a dyn Repository, formatter: &'a dyn CliFormat, prompt: &'a dyn Prompt) -> CommandHandler<'a> {
+        CommandHandler {
+            config,
+            repository,
+            formatter,
+            prompt_formatter: formatter,
+            prompt,
+            notebooks: vec![],
+            current_directory: None,
+        }
+    }
+
+    /// Notebooks are only needed by commands that use all notebooks
+    pub fn with_notebooks(mut self, notebooks: Vec<Notebook<'a>>) -> CommandHandler<'a> {
+        self.notebooks = notebooks;
+        self
+    }
+
+    /// Questions and errors are read by user, they can use another format than command output, e.g.
+    /// text when output is JSON
+    pub fn with_prompt_format(mut self, formatter: &'a dyn CliFormat) -> CommandHandler<'a> {
+        self.prompt_formatter = formatter;
+        self
+    }
+
+    /// If current directory is inside repository, notes are only listed and searched in it by default
+    pub fn with_current_directory(mut self, directory: Option<PathBuf>) -> CommandHandler<'a> {
+        self.current_directory = directory;
+        self
     }
 
     pub fn apply_command(&self, command: Command) -> Result<ConsoleOutput, DefaultError> {
         match command {
             Command::New { path } => self.new_note(path),
-            Command::List => self.list_notes(),
-            Command::Search { needle } => self.search(needle),
+            Command::List { tag, all_notebooks, directory } => self.list_notes(tag, all_notebooks, directory),
+            Command::Search {
+                needle,
+                tag,
+                all_notebooks,
+                options,
+                explain,
+                directory,
+            } => self.search(needle, options, tag, all_notebooks, explain, directory),
+            Command::SearchHistory { needle, directory, restore } => self.search_history(needle, directory, restore),
             Command::Edit { id } => self.edit_note(id),
-            Command::Delete { id } => self.delete_note(id),
+            Command::History { id } => self.note_history(id),
+            Command::Diff { id, range, word } => self.diff(id, range, word),
+            Command::Restore { id, revision, confirmed } => self.restore_note(id, revision, confirmed),
+            Command::Pick { action } => self.pick_note(action),
+            Command::Delete { selectors, confirmed } => self.delete_notes(selectors, confirmed),
+            Command::DeleteDirectory { path, confirmed } => self.delete_directory(path, confirmed),
+            Command::Move { selectors, destination } => self.move_notes(selectors, destination),
+            Command::Tags => self.list_tags(),
+            Command::Tag { action, selectors, tag } => self.tag_notes(action, selectors, tag),
+            Command::Config { action } => self.config(action),
             Command::Push => self.push_repo(),
             Command::Pull => self.pull_repo(),
+            Command::Sync => self.sync_repo(),
             Command::Help => self.help(),
         }
     }
@@ -47,76 +244,486 @@ impl<'a> CommandHandler<'a> {
     fn new_note(&self, path: String) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
         let mut final_path = path.clone();
-        if !final_path.ends_with(".md") {
-            final_path = format!("{}.md", path)
+        let has_extension = self.config.extensions.iter().any(|e| final_path.ends_with(&format!(".{}", e)));
+        if !has_extension {
+            final_path = format!("{}.{}", path, self.config.extensions[0])
         }
-        let id = self.repository.load_notes().len();
-        let note = self.repository.new_note(id, &final_path)?;
-        out.append(self.repository.edit_note(&note)?);
+        let note = self.repository.new_note(&final_path)?;
+        out.append(self.formatter.command_output(self.repository.edit_note(&note)?));
 
-        out.append_stdout(&format!("\nNote '{}' created\n", &note.path.to_str().unwrap()));
+        out.append_stdout(&self.formatter.note_changes(&[NoteChange::Created { path: note.path }]));
         Ok(out)
     }
 
-    fn search(&self, needle: String) -> Result<ConsoleOutput, DefaultError> {
-        let mut out = ConsoleOutput::empty();
-        let notes: Vec<Note> = self.repository.load_notes();
-        let needle_regex = RegexBuilder::new(&format!("({})", needle)).case_insensitive(true).build().unwrap();
+    /// Return repositories to use with their notebook name, or current repository without name
+    fn repositories(&self, all_notebooks: bool) -> Vec<(Option<&str>, &dyn Repository)> {
+        if all_notebooks {
+            self.notebooks.iter().map(|n| (Some(n.name.as_str()), n.repository)).collect()
+        } else {
+            vec![(None, self.repository)]
+        }
+    }
+
+    /// Directory where notes are listed or searched, relative to repository root. An empty path
+    /// designates the whole repository, which is used if current directory is outside of repository.
+    fn scope(&self, directory: Option<String>, all_notebooks: bool) -> PathBuf {
+        let current_directory = match (directory, &self.current_directory) {
+            (Some(directory), _) => return PathBuf::from(directory.trim_start_matches('/')),
+            (None, Some(current_directory)) if !all_notebooks => current_directory,
+            _ => return PathBuf::new(),
+        };
+
+        let storage_directory = &self.config.storage_directory;
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let relative = match canonical(current_directory).strip_prefix(canonical(storage_directory)) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => return PathBuf::new(),
+        };
+        // Notes are not searched in data directories and ignored directories
+        let ignored = relative.components().any(|c| match c {
+            Component::Normal(name) => {
+                let name = name.to_str().unwrap_or("");
+                name.starts_with('.') || self.config.ignored_directories.iter().any(|d| d == name)
+            }
+            _ => true,
+        });
+        match ignored {
+            true => PathBuf::new(),
+            false => relative,
+        }
+    }
+
+    fn search(
+        &self,
+        needle: String,
+        options: SearchOptions,
+        tag: Option<String>,
+        all_notebooks: bool,
+        explain: bool,
+        directory: Option<String>,
+    ) -> Result<ConsoleOutput, DefaultError> {
+        let scope = self.scope(directory, all_notebooks);
+        // In fuzzy mode, needle is a list of words
+        let query = match options.fuzzy {
+            true => None,
+            false => Some(Query::parse(&needle, &options)?),
+        };
+        // Typos cannot be found with search index, all notes are needed
+        let words: Vec<String> = query.as_ref().map_or(vec![], |q| q.required_words());
+        let fuzzy_words: Vec<String> = needle.split_whitespace().map(String::from).collect();
+
+        let mut matches: Vec<NotebookItem<SearchMatch>> = vec![];
+        for (notebook, repository) in self.repositories(all_notebooks) {
+            let mut index = repository.search_index();
+            let notes: Vec<Note> = repository
+                .load_notes_containing(&words, &scope, &mut index)?
+                .into_iter()
+                .filter(|n| has_tag(n, &tag))
+                .collect();
+            match &query {
+                Some(query) => {
+                    let candidates: Vec<(Note, PathBuf)> = notes
+                        .into_iter()
+                        .map(|note| {
+                            let path = repository.note_path(&note);
+                            (note, path)
+                        })
+                        .collect();
+                    let ranking = Ranking::new(query, &index, &candidates);
+                    candidates
+                        .iter()
+                        .filter_map(|(note, path)| note.query_match(query, path, &ranking, &options.context))
+                        .for_each(|search_m| matches.push(notebook_item(notebook, search_m)));
+                }
+                None => notes
+                    .iter()
+                    .map(|note| note.fuzzy_match(&fuzzy_words, &options.context))
+                    .filter(|search_m| search_m.score > 0.0)
+                    .for_each(|search_m| matches.push(notebook_item(notebook, search_m))),
+            }
+        }
+        matches.sort_by(|a, b| b.item.score.partial_cmp(&a.item.score).unwrap_or(Ordering::Equal));
+
+        Ok(ConsoleOutput::from_stdout(&self.formatter.search_results(&needle, &scope, &matches, explain)))
+    }
+
+    fn search_history(&self, needle: String, directory: Option<String>, restore: Option<String>) -> Result<ConsoleOutput, DefaultError> {
+        // Pattern is used by git to find commits, then here to find lines
+        check_extended_regexp(&needle)?;
+        let regex = RegexBuilder::new(&needle)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| DefaultError::new(format!("Invalid pattern '{}': {}", needle, e)))?;
+        let scope = self.scope(directory, false);
+
+        let mut matches: Vec<HistoryMatch> = self.repository.search_history(&needle)?;
+        matches.retain(|m| m.path.starts_with(&scope));
+        for history_m in matches.iter_mut() {
+            history_m.lines.retain(|l| regex.is_match(&l.content));
+            history_m
+                .lines
+                .iter_mut()
+                .for_each(|l| l.matches = regex.find_iter(&l.content).map(|m| m.start()..m.end()).collect());
+            // If needle was removed, the version containing it is the previous one
+            if history_m.lines.iter().any(|l| !l.added) {
+                history_m.revision = format!("{}^", history_m.commit);
+            }
+        }
+        matches.retain(|m| !m.lines.is_empty());
 
-        let mut matches: Vec<SearchMatch> = notes
+        let commit = match restore {
+            Some(commit) => commit,
+            None => return Ok(ConsoleOutput::from_stdout(&self.formatter.history_results(&needle, &scope, &matches))),
+        };
+        let versions: Vec<&HistoryMatch> = matches
             .iter()
-            .map(|note| note.search_match(&needle_regex))
-            .filter(|search_m| search_m.score.gt(&0))
+            .filter(|m| m.commit.starts_with(&commit) || commit.starts_with(&m.commit))
             .collect();
-        matches.sort_by(|a, b| b.score.cmp(&a.score));
+        if versions.is_empty() {
+            return Err(DefaultError::new(format!("Commit {} not found in history of '{}'.", commit, needle)));
+        }
+        // A short prefix can designate several commits, only one version of each note is restored
+        let mut candidates: Vec<&str> = vec![];
+        for version in versions.iter() {
+            if !candidates.contains(&version.commit.as_str()) {
+                candidates.push(&version.commit);
+            }
+        }
+        if candidates.len() > 1 {
+            return Err(DefaultError::new(format!(
+                "Commit {} is ambiguous, it can designate: {}",
+                commit,
+                candidates.join(", ")
+            )));
+        }
 
-        matches
-            .iter()
-            .for_each(|search_m| out.append_stdout(&format!("{}\n\n", self.formatter.search_match(search_m))));
+        let mut out = ConsoleOutput::empty();
+        let mut changes: Vec<NoteChange> = vec![];
+        for version in versions {
+            out.append(self.formatter.command_output(self.repository.restore_file(&version.path, &version.revision)?));
+            changes.push(NoteChange::Restored {
+                path: version.path.clone(),
+                revision: version.revision.clone(),
+            });
+        }
+        out.append_stdout(&self.formatter.note_changes(&changes));
+        Ok(out)
+    }
+
+    fn list_notes(&self, tag: Option<String>, all_notebooks: bool, directory: Option<String>) -> Result<ConsoleOutput, DefaultError> {
+        let scope = self.scope(directory, all_notebooks);
+        let mut directories: Vec<NotebookItem<RepositoryDir>> = vec![];
+        for (notebook, repository) in self.repositories(all_notebooks) {
+            for mut entry in repository.load_directory_tree(&scope)? {
+                entry.notes.retain(|n| has_tag(n, &tag));
+                // When filtering by tag, we do not display directories without matching notes
+                if tag.is_some() && entry.notes.is_empty() {
+                    continue;
+                }
+                directories.push(notebook_item(notebook, entry));
+            }
+        }
+        Ok(ConsoleOutput::from_stdout(&self.formatter.note_tree(&directories)))
+    }
 
-        out.append_stdout(&format!("{} results found for '{}'\n", matches.len(), needle));
+    fn list_tags(&self) -> Result<ConsoleOutput, DefaultError> {
+        let mut tags: BTreeMap<String, usize> = BTreeMap::new();
+        self.repository
+            .load_notes()?
+            .iter()
+            .flat_map(|n| n.tags())
+            .for_each(|tag| *tags.entry(tag).or_insert(0) += 1);
 
-        Ok(out)
+        Ok(ConsoleOutput::from_stdout(&self.formatter.tag_list(&tags)))
     }
 
-    fn list_notes(&self) -> Result<ConsoleOutput, DefaultError> {
+    fn tag_notes(&self, action: TagAction, selectors: Vec<NoteSelector>, tag: String) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
-        let entries = self.repository.load_repository_tree();
-
-        for entry in entries {
-            let pad: Vec<&str> = vec![0; entry.level].iter().map(|_| "  ").collect();
-            out.append_stdout(&format!("{}{}\n", pad.join(""), self.formatter.note_directory(&entry.name)));
-            entry
-                .notes
-                .iter()
-                .for_each(|n| out.append_stdout(&format!("{}{}\n", pad.join(""), self.formatter.note_list_item(n))));
-            out.append_stdout("\n");
+        let notes = self.repository.find_notes(&selectors)?;
+
+        // All notes are updated in memory first, so that nothing is written if one of them fails
+        let mut updated_notes: Vec<Note> = vec![];
+        let mut changes: Vec<NoteChange> = vec![];
+        for note in notes.iter() {
+            let updated = match action {
+                TagAction::Add => note.with_tag(&tag)?,
+                TagAction::Remove => note.without_tag(&tag)?,
+            };
+            changes.push(NoteChange::Tagged {
+                path: note.path.clone(),
+                tags: updated.tags(),
+            });
+            updated_notes.push(updated);
         }
+
+        let changed: Vec<Note> = updated_notes
+            .into_iter()
+            .zip(notes.iter())
+            .filter(|(updated, note)| updated.content() != note.content())
+            .map(|(updated, _)| updated)
+            .collect();
+
+        let target = match changed.as_slice() {
+            [] => {
+                out.append_stdout(&self.formatter.note_changes(&changes));
+                return Ok(out);
+            }
+            [note] => format!("note {}", note.path.file_name().unwrap().to_str().unwrap()),
+            _ => format!("{} notes", changed.len()),
+        };
+        let message = match action {
+            TagAction::Add => format!("Add tag {} to {}", tag, target),
+            TagAction::Remove => format!("Remove tag {} from {}", tag, target),
+        };
+        out.append(self.formatter.command_output(self.repository.update_notes(&changed, &message)?));
+        out.append_stdout(&self.formatter.note_changes(&changes));
         Ok(out)
     }
 
     fn edit_note(&self, id: usize) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
-        match self.repository.find_note_by_id(id) {
+        match self.repository.find_note_by_id(id)? {
             Some(n) => {
-                out.append(self.repository.edit_note(&n)?);
-                out.append_stdout(&format!("\nNote '{}' edited\n", n.path.to_str().unwrap()));
+                out.append(self.formatter.command_output(self.repository.edit_note(&n)?));
+                out.append_stdout(&self.formatter.note_changes(&[NoteChange::Edited { path: n.path }]));
                 Ok(out)
             }
             None => Err(DefaultError::new(format!("Note with id {} not found.", id))),
         }
     }
 
-    fn delete_note(&self, id: usize) -> Result<ConsoleOutput, DefaultError> {
+    fn note_history(&self, id: usize) -> Result<ConsoleOutput, DefaultError> {
+        let note = match self.repository.find_note_by_id(id)? {
+            Some(n) => n,
+            None => return Err(DefaultError::new(format!("Note with id {} not found.", id))),
+        };
+        let commits = self.repository.note_history(&note)?;
+        let path = self.repository.note_path(&note);
+        Ok(ConsoleOutput::from_stdout(&self.formatter.note_history(&note, &path, &commits)))
+    }
+
+    fn diff(&self, id: Option<usize>, range: Option<String>, word: bool) -> Result<ConsoleOutput, DefaultError> {
+        let paths = match id {
+            Some(id) => {
+                let note = match self.repository.find_note_by_id(id)? {
+                    Some(n) => n,
+                    None => return Err(DefaultError::new(format!("Note with id {} not found.", id))),
+                };
+                let mut paths = vec![self.repository.note_path(&note)];
+                // Previous paths are needed to show changes made before note was moved
+                if range.is_some() {
+                    for entry in self.repository.note_history(&note)? {
+                        if !paths.contains(&entry.path) {
+                            paths.push(entry.path);
+                        }
+                    }
+                }
+                paths
+            }
+            None => vec![],
+        };
+        let diff = self.repository.diff(range.as_deref().unwrap_or("HEAD"), &paths, word)?;
+        Ok(ConsoleOutput::from_stdout(&self.formatter.diff(&diff, word)))
+    }
+
+    fn restore_note(&self, id: usize, revision: Revision, confirmed: bool) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
-        match self.repository.find_note_by_id(id) {
-            Some(n) => {
-                out.append(self.repository.delete_note(&n)?);
-                out.append_stdout(&format!("\nNote '{}' deleted\n", n.path.to_str().unwrap()));
-                Ok(out)
+        let note = match self.repository.find_note_by_id(id)? {
+            Some(n) => n,
+            None => return Err(DefaultError::new(format!("Note with id {} not found.", id))),
+        };
+        let path = self.repository.note_path(&note);
+        let history = self.repository.note_history(&note)?;
+        // Note may have been moved since this revision
+        let (revision, revision_path) = match revision {
+            Revision::Steps(steps) => match history.get(steps) {
+                Some(entry) => (entry.commit.clone(), entry.path.clone()),
+                None => {
+                    return Err(DefaultError::new(format!(
+                        "Cannot go back {} steps, note '{}' has {} previous versions.",
+                        steps,
+                        note.title,
+                        history.len().saturating_sub(1)
+                    )))
+                }
+            },
+            Revision::Commit(commit) => match history.iter().find(|e| e.commit.starts_with(&commit) || commit.starts_with(&e.commit)) {
+                Some(entry) => (entry.commit.clone(), entry.path.clone()),
+                None => (commit, path.clone()),
+            },
+        };
+
+        let diff = self.repository.restore_preview(&note, &revision, &revision_path)?;
+        if !diff.lines().any(|l| l.starts_with("@@")) {
+            let message = format!("Nothing to restore, note '{}' is the same in {}", note.title, revision);
+            out.append_stdout(&self.formatter.message(&message));
+            return Ok(out);
+        }
+        if !confirmed {
+            let question = format!("{}\nRestore note '{}' to {} ?", self.prompt_formatter.diff(&diff, false), note.title, revision);
+            if !self.prompt.confirm(&question)? {
+                out.append_stdout(&self.formatter.message("Restore cancelled"));
+                return Ok(out);
+            }
+        }
+
+        out.append(self.formatter.command_output(self.repository.restore_note(&note, &revision, &revision_path)?));
+        out.append_stdout(&self.formatter.note_changes(&[NoteChange::Restored { path, revision }]));
+        Ok(out)
+    }
+
+    fn pick_note(&self, action: Option<PickAction>) -> Result<ConsoleOutput, DefaultError> {
+        let candidates: Vec<(Note, PathBuf)> = self
+            .repository
+            .load_notes()?
+            .into_iter()
+            .map(|n| {
+                let path = self.repository.note_path(&n);
+                (n, path)
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Ok(ConsoleOutput::from_stdout(&self.formatter.message("No notes found")));
+        }
+
+        let actions = match action {
+            Some(action) => vec![action],
+            None => PickAction::ALL.to_vec(),
+        };
+        let (note, action) = match self.prompt.pick_note(&candidates, &actions)? {
+            Some((index, action)) => (&candidates[index].0, action),
+            None => return Ok(ConsoleOutput::from_stdout(&self.formatter.message("Nothing selected"))),
+        };
+
+        let selectors = vec![NoteSelector::Id(note.id)];
+        match action {
+            PickAction::Edit => self.edit_note(note.id),
+            PickAction::Show => Ok(ConsoleOutput::from_stdout(&self.formatter.note_content(note))),
+            PickAction::Delete => {
+                if !self.prompt.confirm(&format!("Delete note '{}' ?", note.title))? {
+                    return Ok(ConsoleOutput::from_stdout(&self.formatter.message("Deletion cancelled")));
+                }
+                self.delete_notes(selectors, true)
+            }
+            PickAction::Move => {
+                let destination = self.prompt.input(&format!("Move '{}' to", note.title))?;
+                if destination.is_empty() {
+                    return Ok(ConsoleOutput::from_stdout(&self.formatter.message("Move cancelled")));
+                }
+                self.move_notes(selectors, destination)
+            }
+        }
+    }
+
+    fn delete_notes(&self, selectors: Vec<NoteSelector>, confirmed: bool) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = ConsoleOutput::empty();
+        let notes = self.repository.find_notes(&selectors)?;
+
+        // Ranges and globs can select more notes than expected
+        if notes.len() > 1 && !confirmed {
+            let note_list: Vec<String> = notes.iter().map(|n| self.prompt_formatter.note_list_item(n)).collect();
+            let question = format!("{}\n\nDelete these {} notes ?", note_list.join("\n"), notes.len());
+            if !self.prompt.confirm(&question)? {
+                out.append_stdout(&self.formatter.message("Deletion cancelled"));
+                return Ok(out);
+            }
+        }
+
+        out.append(self.formatter.command_output(self.repository.delete_notes(&notes)?));
+        let changes: Vec<NoteChange> = notes.into_iter().map(|n| NoteChange::Deleted { path: n.path }).collect();
+        out.append_stdout(&self.formatter.note_changes(&changes));
+        Ok(out)
+    }
+
+    fn delete_directory(&self, path: String, confirmed: bool) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = ConsoleOutput::empty();
+        let name = path.trim_end_matches('/');
+        let tree = self.repository.load_repository_tree()?;
+
+        // Repository root is the only directory with level 0, it cannot be deleted
+        let directory = match tree.iter().find(|d| d.level > 0 && d.name == name) {
+            Some(d) => d,
+            None => return Err(DefaultError::new(format!("Directory {} not found.", name))),
+        };
+        let notes: Vec<&Note> = tree
+            .iter()
+            .filter(|d| d.path.starts_with(&directory.path))
+            .flat_map(|d| d.notes.iter())
+            .collect();
+
+        if !confirmed {
+            let mut file_list: Vec<String> = notes.iter().map(|n| self.prompt_formatter.note_list_item(n)).collect();
+            // Attachments and other files are deleted too
+            let other_files = self.repository.other_files(directory);
+            file_list.extend(other_files.iter().map(|p| p.to_str().unwrap().to_string()));
+            let question = match other_files.len() {
+                0 => format!("{}\n\nDelete directory '{}' and its {} notes ?", file_list.join("\n"), name, notes.len()),
+                count => format!(
+                    "{}\n\nDelete directory '{}', its {} notes and {} other files ?",
+                    file_list.join("\n"),
+                    name,
+                    notes.len(),
+                    count
+                ),
+            };
+            if !self.prompt.confirm(&question)? {
+                out.append_stdout(&self.formatter.message("Deletion cancelled"));
+                return Ok(out);
             }
-            None => Err(DefaultError::new(format!("Note with id {} not found.", id))),
         }
+
+        out.append(self.formatter.command_output(self.repository.delete_directory(directory)?));
+        let message = format!("Directory '{}' deleted, {} notes removed", name, notes.len());
+        out.append_stdout(&self.formatter.message(&message));
+        Ok(out)
+    }
+
+    fn move_notes(&self, selectors: Vec<NoteSelector>, destination: String) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = ConsoleOutput::empty();
+        let notes = self.repository.find_notes(&selectors)?;
+        out.append(self.formatter.command_output(self.repository.move_notes(&notes, &destination)?));
+        let changes: Vec<NoteChange> = notes
+            .into_iter()
+            .map(|n| NoteChange::Moved {
+                path: n.path,
+                destination: destination.clone(),
+            })
+            .collect();
+        out.append_stdout(&self.formatter.note_changes(&changes));
+        Ok(out)
+    }
+
+    fn config(&self, action: ConfigAction) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = ConsoleOutput::empty();
+        match action {
+            ConfigAction::List => {
+                let values = CONFIG_KEYS
+                    .iter()
+                    .map(|key| Ok((key.to_string(), self.config.get(key)?)))
+                    .collect::<Result<Vec<(String, String)>, DefaultError>>()?;
+                out.append_stdout(&self.formatter.config_values(&values));
+            }
+            ConfigAction::Get { key } => out.append_stdout(&self.formatter.config_value(&key, &self.config.get(&key)?)),
+            ConfigAction::Set { key, value, global } => {
+                if !global && (USER_CONFIG_KEYS.contains(&key.as_str()) || key.starts_with("notebooks.")) {
+                    return Err(DefaultError::new(format!("{} can only be set in user configuration, use --global", key)));
+                }
+                let path = match (global, &self.config.user_config_path) {
+                    (true, Some(p)) => p.clone(),
+                    (true, None) => return Err(DefaultError::new("Cannot find user configuration directory".to_string())),
+                    (false, _) => self.config.repository_config_path(),
+                };
+                let mut file = ConfigFile::load(&path)?;
+                file.set(&key, &value)?;
+                if global {
+                    file.save(&path)?;
+                } else {
+                    out.append(self.formatter.command_output(self.repository.save_config(&file)?));
+                }
+                out.append_stdout(&self.formatter.config_saved(&key, &value, &path));
+            }
+        };
+        Ok(out)
     }
 
     fn push_repo(&self) -> Result<ConsoleOutput, DefaultError> {
@@ -133,6 +740,32 @@ impl<'a> CommandHandler<'a> {
         Ok(out)
     }
 
+    fn sync_repo(&self) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = self.formatter.command_output(ConsoleOutput::from_stdout(&format!("{}\n", Banners::big())));
+        let result = self.repository.sync_repo()?;
+        out.append(self.formatter.command_output(result.output));
+        if result.conflicts.is_empty() {
+            out.append_stdout(&self.formatter.message("Notes synchronized"));
+            return Ok(out);
+        }
+
+        let notes = self.repository.load_notes()?;
+        let conflicts: Vec<String> = result
+            .conflicts
+            .iter()
+            .map(|path| match notes.iter().find(|n| &self.repository.note_path(n) == path) {
+                Some(note) => format!("{} ({})", self.prompt_formatter.note_list_item(note), path.display()),
+                None => format!(" {}", path.display()),
+            })
+            .collect();
+        Err(DefaultError::new(format!(
+            "Conflicts found while rebasing on remote changes, nothing was pushed:\n{}\n\n\
+             Resolve conflicts in these files, then run 'git add <file>' and 'git rebase --continue' \
+             in your repository, or 'git rebase --abort' to cancel. Run 'notes sync' again to push.",
+            conflicts.join("\n")
+        )))
+    }
+
     fn help(&self) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
         out.append_stdout(&format!("{}\n", Banners::big()));
@@ -141,22 +774,61 @@ impl<'a> CommandHandler<'a> {
     }
 }
 
+fn notebook_item<T>(notebook: Option<&str>, item: T) -> NotebookItem<T> {
+    NotebookItem {
+        notebook: notebook.map(String::from),
+        item,
+    }
+}
+
+fn has_tag(note: &Note, tag: &Option<String>) -> bool {
+    match tag {
+        Some(t) => note.has_tag(t),
+        None => true,
+    }
+}
+
 // TODO: better assertions on output
 
 #[cfg(test)]
 mod tests {
     use mockall::predicate::*;
 
-    use crate::cli_format::MockCliFormat;
-    use crate::repository::{MockRepository, RepositoryDir};
+    use crate::cli_format::{CliFormatImpl, MockCliFormat};
+    use crate::git::LogEntry;
+    use crate::json_format::JsonFormat;
+    use crate::prompt::MockPrompt;
+    use crate::repository::{MockRepository, RepositoryDir, SyncResult};
+    use crate::search_index::SearchIndex;
+    use crate::search_match::HistoryLine;
 
     use super::*;
-    use std::path::PathBuf;
+    use crate::test_env::new_test_root;
+    use std::fs;
+    use std::ops::Range;
+    use std::path::{Path, PathBuf};
+
+    /// Results of commands are formatted as text, other outputs are set by tests
+    fn text_format_mock() -> MockCliFormat {
+        let mut fmt_mock = MockCliFormat::new();
+        fmt_mock.expect_command_output().returning(|o| o);
+        fmt_mock.expect_note_changes().returning(|c| CliFormatImpl::new().note_changes(c));
+        fmt_mock.expect_note_content().returning(|n| CliFormatImpl::new().note_content(n));
+        fmt_mock.expect_message().returning(|m| CliFormatImpl::new().message(m));
+        fmt_mock.expect_config_values().returning(|v| CliFormatImpl::new().config_values(v));
+        fmt_mock.expect_config_value().returning(|k, v| CliFormatImpl::new().config_value(k, v));
+        fmt_mock.expect_config_saved().returning(|k, v, p| CliFormatImpl::new().config_saved(k, v, p));
+        fmt_mock
+    }
+
+    fn test_config() -> Config {
+        Config::from_path(&PathBuf::from("/path"))
+    }
 
     fn test_notes() -> Vec<Note> {
         vec![
-            Note::from(0, "0.md".into(), "# Note 0 title \n\n Note 0 content".to_string()).unwrap(),
-            Note::from(1, "0.md".into(), "# Note 1 title \n\n Note 1 content".to_string()).unwrap(),
+            Note::from(0, "0.md".into(), "# Note 0 title \n\n Note 0 content #tag-a".to_string()).unwrap(),
+            Note::from(1, "0.md".into(), "# Note 1 title \n\n Note 1 content #tag-a #tag-b".to_string()).unwrap(),
             Note::from(2, "0.md".into(), "# Note 2 title \n\n Note 2 content".to_string()).unwrap(),
         ]
     }
@@ -170,18 +842,43 @@ mod tests {
         }]
     }
 
+    fn match_ids(matches: &[NotebookItem<SearchMatch>]) -> Vec<usize> {
+        matches.iter().map(|m| m.item.id).collect()
+    }
+
+    fn test_deep_note_tree() -> Vec<RepositoryDir> {
+        vec![
+            RepositoryDir {
+                path: PathBuf::from("/path"),
+                name: "/path".to_string(),
+                level: 0,
+                notes: vec![test_notes()[0].clone()],
+            },
+            RepositoryDir {
+                path: PathBuf::from("/path/to"),
+                name: "to".to_string(),
+                level: 1,
+                notes: vec![test_notes()[1].clone()],
+            },
+            RepositoryDir {
+                path: PathBuf::from("/path/to/dir"),
+                name: "to/dir".to_string(),
+                level: 2,
+                notes: vec![test_notes()[2].clone()],
+            },
+        ]
+    }
+
     #[test]
     fn new_note_should_add_suffix() {
         let path = "new/note".to_string();
 
         let mut repo_mock = MockRepository::new();
-        repo_mock.expect_load_notes().times(1).returning(test_notes);
-
         repo_mock
             .expect_new_note()
-            .with(eq(3), eq("new/note.md"))
+            .with(eq("new/note.md"))
             .times(1)
-            .returning(|_, _| Ok(test_notes()[0].clone()));
+            .returning(|_| Ok(test_notes()[0].clone()));
 
         repo_mock
             .expect_edit_note()
@@ -189,8 +886,10 @@ mod tests {
             .times(1)
             .returning(|_| Ok(ConsoleOutput::empty()));
 
-        let fmt_mock = MockCliFormat::new();
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
 
         let res = handler.apply_command(Command::New { path });
         assert!(res.is_ok())
@@ -199,140 +898,1152 @@ mod tests {
     #[test]
     fn search_notes_should_find_nothing() {
         let mut repo_mock = MockRepository::new();
-        repo_mock.expect_load_notes().times(1).returning(test_notes);
+        repo_mock
+            .expect_load_notes_containing()
+            .times(1)
+            .withf(|words, directory, _| directory == Path::new("") && words == ["abcdef"])
+            .returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock
+            .expect_search_index()
+            .times(1)
+            .returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let mut fmt_mock = MockCliFormat::new();
-        fmt_mock.expect_search_match().times(0).returning(|_| "".to_string());
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|needle, _, matches, _| needle == "abcdef" && matches.is_empty())
+            .returning(|_, _, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "abcdef".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
+        assert!(res.is_ok())
+    }
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
+    #[test]
+    fn search_notes_should_find_note() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes_containing().times(1).returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let res = handler.apply_command(Command::Search { needle: "abcdef".to_string() });
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|_, _, matches, _| match_ids(matches) == vec![2])
+            .returning(|_, _, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "2".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
         assert!(res.is_ok())
     }
 
     #[test]
-    fn search_notes_should_find_note() {
+    fn search_notes_with_query() {
         let mut repo_mock = MockRepository::new();
-        repo_mock.expect_load_notes().times(1).returning(test_notes);
+        repo_mock
+            .expect_load_notes_containing()
+            .times(1)
+            .withf(|words, directory, _| directory == Path::new("") && words == ["note"])
+            .returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let mut fmt_mock = MockCliFormat::new();
+        let mut fmt_mock = text_format_mock();
         fmt_mock
-            .expect_search_match()
+            .expect_search_results()
             .times(1)
-            .withf(|search_m| search_m.id == 2)
-            .returning(|search_m| search_m.title.clone());
+            .withf(|needle, scope, matches, explain| {
+                let mut ids = match_ids(matches);
+                ids.sort();
+                needle == "title:note AND NOT tag:tag-b" && scope == Path::new("") && ids == vec![0, 2] && !explain
+            })
+            .returning(|_, _, matches, _| format!("{} results", matches.len()));
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "title:note AND NOT tag:tag-b".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
+        assert_eq!(res.unwrap().stdout, "2 results");
+    }
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
+    #[test]
+    fn search_notes_with_literal_needle() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock
+            .expect_load_notes_containing()
+            .times(1)
+            .withf(|words, _, _| words == ["a", "b", "content", "tag"])
+            .returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let res = handler.apply_command(Command::Search { needle: "2".to_string() });
-        assert!(res.is_ok())
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|_, _, matches, _| match_ids(matches) == vec![1])
+            .returning(|_, _, matches, _| format!("{} results", matches.len()));
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "content #tag-a #tag-b".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions {
+                literal: true,
+                case_sensitive: true,
+                word: true,
+                ..SearchOptions::default()
+            },
+            explain: false,
+            directory: None,
+        });
+        assert_eq!(res.unwrap().stdout, "1 results");
     }
 
     #[test]
-    fn list_notes() {
+    fn search_notes_with_explanation() {
         let mut repo_mock = MockRepository::new();
-        repo_mock.expect_load_repository_tree().times(1).returning(test_note_tree);
+        repo_mock.expect_load_notes_containing().times(1).returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let mut fmt_mock = MockCliFormat::new();
-        fmt_mock.expect_note_directory().times(1).withf(|t| t == "to/dir").returning(|t| t.to_string());
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|_, _, matches, explain| {
+                let explanation = &matches[0].item.explanation;
+                *explain && explanation.len() == 1 && explanation[0].term == "2" && explanation[0].title == 1
+            })
+            .returning(|_, _, _, _| "explanation".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "2".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: true,
+            directory: None,
+        });
+        assert_eq!(res.unwrap().stdout, "explanation");
+    }
+
+    #[test]
+    fn search_notes_should_fail_with_invalid_query() {
+        let repo_mock = MockRepository::new();
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "(note OR".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
+        assert_eq!(res.unwrap_err().message, "Invalid query: missing term at end of query");
+    }
+
+    #[test]
+    fn search_notes_with_typos() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock
+            .expect_load_notes_containing()
+            .times(1)
+            .withf(|words, directory, _| directory == Path::new("") && words.is_empty())
+            .returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let note_titles: Vec<String> = test_notes().iter().map(|n| n.title.clone()).collect();
+        let mut fmt_mock = text_format_mock();
         fmt_mock
-            .expect_note_list_item()
-            .times(3)
-            .withf(move |n| note_titles.iter().any(|tb| &n.title == tb))
-            .returning(|n| n.title.clone());
+            .expect_search_results()
+            .times(1)
+            .withf(|needle, _, matches, _| needle == "nite 2" && match_ids(matches) == vec![2] && matches[0].item.matched_lines[0].matches == vec![1..5, 6..7])
+            .returning(|_, _, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "nite 2".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions {
+                fuzzy: true,
+                ..SearchOptions::default()
+            },
+            explain: false,
+            directory: None,
+        });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn list_notes() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_directory_tree().times(1).returning(|_| Ok(test_note_tree()));
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
-        let res = handler.apply_command(Command::List);
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_note_tree()
+            .times(1)
+            .withf(|directories| directories.len() == 1 && directories[0].notebook.is_none() && directories[0].item == test_note_tree()[0])
+            .returning(|_| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::List {
+            tag: None,
+            all_notebooks: false,
+            directory: None,
+        });
         assert!(res.is_ok())
     }
 
     #[test]
-    fn edit_note() {
+    fn list_notes_in_directory() {
         let mut repo_mock = MockRepository::new();
-        let note = test_notes()[1].clone();
-        let note_id = note.id;
+        repo_mock
+            .expect_load_directory_tree()
+            .times(1)
+            .withf(|directory| directory == Path::new("to/dir"))
+            .returning(|_| Ok(test_note_tree()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_tree().times(1).returning(|_| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        // Explicit directory is relative to repository root, even if current directory is inside repository
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock).with_current_directory(Some(PathBuf::from("/path/other")));
+        let res = handler.apply_command(Command::List {
+            tag: None,
+            all_notebooks: false,
+            directory: Some("/to/dir/".to_string()),
+        });
+        assert!(res.is_ok())
+    }
 
+    #[test]
+    fn list_notes_should_fail_if_directory_does_not_exist() {
+        let mut repo_mock = MockRepository::new();
         repo_mock
-            .expect_find_note_by_id()
+            .expect_load_directory_tree()
             .times(1)
-            .withf(move |id| *id == note_id)
-            .return_const(Some(note));
+            .returning(|_| Err(DefaultError::new("Directory unknown not found.".to_string())));
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::List {
+            tag: None,
+            all_notebooks: false,
+            directory: Some("unknown".to_string()),
+        });
+        assert_eq!(res.unwrap_err().message, "Directory unknown not found.");
+    }
+
+    #[test]
+    fn search_notes_in_current_directory() {
+        let mut repo_mock = MockRepository::new();
         repo_mock
-            .expect_edit_note()
+            .expect_load_notes_containing()
             .times(1)
-            .withf(move |n| n.id == note_id)
-            .returning(|_| Ok(ConsoleOutput::empty()));
+            .withf(|_, directory, _| directory == Path::new("to/dir"))
+            .returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|_, scope, matches, _| scope == Path::new("to/dir") && match_ids(matches) == vec![2])
+            .returning(|_, _, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock).with_current_directory(Some(PathBuf::from("/path/to/dir")));
+        let res = handler.apply_command(Command::Search {
+            needle: "note 2".to_string(),
+            tag: None,
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn search_notes_should_use_whole_repository_outside_of_it() {
+        for current_directory in &["/other", "/path", "/path/.notes", "/path/a/.idea"] {
+            let mut repo_mock = MockRepository::new();
+            repo_mock
+                .expect_load_notes_containing()
+                .times(1)
+                .withf(|_, directory, _| directory == Path::new(""))
+                .returning(|_, _, _| Ok(vec![]));
+            repo_mock.expect_note_path().returning(|n| n.path.clone());
+            repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
+
+            let mut fmt_mock = text_format_mock();
+            fmt_mock
+                .expect_search_results()
+                .times(1)
+                .withf(|_, scope, _, _| scope == Path::new(""))
+                .returning(|_, _, _, _| "".to_string());
+            let prompt_mock = MockPrompt::new();
+            let config = test_config();
+            let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock).with_current_directory(Some(PathBuf::from(current_directory)));
+            let res = handler.apply_command(Command::Search {
+                needle: "note".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions::default(),
+                explain: false,
+                directory: None,
+            });
+            assert!(res.is_ok());
+        }
+    }
 
-        let fmt_mock = MockCliFormat::new();
+    #[test]
+    fn search_notes_should_filter_by_tag() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes_containing().times(1).returning(|_, _, _| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_search_index().returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
-        let res = handler.apply_command(Command::Edit { id: note_id });
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|_, _, matches, _| match_ids(matches) == vec![1])
+            .returning(|_, _, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Search {
+            needle: "content".to_string(),
+            tag: Some("tag-b".to_string()),
+            all_notebooks: false,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
         assert!(res.is_ok())
     }
 
     #[test]
-    fn edit_note_should_find_nothing() {
+    fn list_notes_should_filter_by_tag() {
         let mut repo_mock = MockRepository::new();
-        let note_id = 5;
+        repo_mock.expect_load_directory_tree().times(1).returning(|_| Ok(test_note_tree()));
 
-        repo_mock.expect_find_note_by_id().times(1).withf(move |id| *id == note_id).return_const(None);
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_note_tree()
+            .times(1)
+            .withf(|directories| directories[0].item.notes.len() == 2 && directories[0].item.notes.iter().all(|n| n.has_tag("tag-a")))
+            .returning(|_| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::List {
+            tag: Some("tag-a".to_string()),
+            all_notebooks: false,
+            directory: None,
+        });
+        assert!(res.is_ok())
+    }
 
-        let fmt_mock = MockCliFormat::new();
+    #[test]
+    fn list_tags() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes().times(1).returning(|| Ok(test_notes()));
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
-        let res = handler.apply_command(Command::Edit { id: note_id });
-        assert_eq!(res.unwrap_err().message, "Note with id 5 not found.")
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_tag_list()
+            .times(1)
+            .withf(|tags| tags.get("tag-a") == Some(&2) && tags.get("tag-b") == Some(&1) && tags.len() == 2)
+            .returning(|tags| format!("{} tags found", tags.len()));
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Tags).unwrap();
+        assert!(res.stdout.contains("2 tags found"));
     }
 
     #[test]
-    fn delete_note() {
+    fn tag_note() {
         let mut repo_mock = MockRepository::new();
-        let note = test_notes()[1].clone();
+        let note = test_notes()[2].clone();
         let note_id = note.id;
 
         repo_mock
-            .expect_find_note_by_id()
+            .expect_find_notes()
             .times(1)
-            .withf(move |id| *id == note_id)
-            .return_const(Some(note));
+            .withf(move |s| s == [NoteSelector::Id(note_id)])
+            .returning(move |_| Ok(vec![note.clone()]));
         repo_mock
-            .expect_delete_note()
+            .expect_update_notes()
             .times(1)
-            .withf(move |n| n.id == note_id)
-            .returning(|_| Ok(ConsoleOutput::empty()));
-
-        let fmt_mock = MockCliFormat::new();
-
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
-        let res = handler.apply_command(Command::Delete { id: note_id });
+            .withf(move |n, m| n.len() == 1 && n[0].id == note_id && n[0].has_tag("new-tag") && m == "Add tag new-tag to note 0.md")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Tag {
+            action: TagAction::Add,
+            selectors: vec![NoteSelector::Id(note_id)],
+            tag: "new-tag".to_string(),
+        });
         assert!(res.is_ok())
     }
 
     #[test]
-    fn delete_note_should_find_nothing() {
+    fn tag_several_notes() {
         let mut repo_mock = MockRepository::new();
-        let note_id = 5;
 
-        repo_mock.expect_find_note_by_id().times(1).withf(move |id| *id == note_id).return_const(None);
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .withf(|s| s == [NoteSelector::Range(0, 2)])
+            .returning(|_| Ok(test_notes()));
+        repo_mock
+            .expect_update_notes()
+            .times(1)
+            .withf(|n, m| n.len() == 3 && m == "Add tag new-tag to 3 notes")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Tag {
+            action: TagAction::Add,
+            selectors: vec![NoteSelector::Range(0, 2)],
+            tag: "new-tag".to_string(),
+        });
+        assert!(res.is_ok())
+    }
 
-        let fmt_mock = MockCliFormat::new();
+    #[test]
+    fn tag_several_notes_should_not_update_any_on_error() {
+        let mut repo_mock = MockRepository::new();
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
-        let res = handler.apply_command(Command::Delete { id: note_id });
-        assert_eq!(res.unwrap_err().message, "Note with id 5 not found.")
+        // Tag of note 0 is inline, it cannot be removed
+        repo_mock.expect_find_notes().times(1).returning(|_| Ok(test_notes()));
+        repo_mock.expect_update_notes().times(0);
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Tag {
+            action: TagAction::Remove,
+            selectors: vec![NoteSelector::Range(0, 2)],
+            tag: "tag-a".to_string(),
+        });
+        assert!(res.is_err())
     }
 
     #[test]
-    fn push_repo() {
+    fn untag_note() {
         let mut repo_mock = MockRepository::new();
-        repo_mock.expect_push_repo().times(1).returning(|| Ok(ConsoleOutput::empty()));
-
-        let fmt_mock = MockCliFormat::new();
+        let note = test_notes()[2].with_tag("old-tag").unwrap();
+        let note_id = note.id;
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
-        let res = handler.apply_command(Command::Push);
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .withf(move |s| s == [NoteSelector::Id(note_id)])
+            .returning(move |_| Ok(vec![note.clone()]));
+        repo_mock
+            .expect_update_notes()
+            .times(1)
+            .withf(move |n, m| n[0].tags().is_empty() && m == "Remove tag old-tag from note 0.md")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Tag {
+            action: TagAction::Remove,
+            selectors: vec![NoteSelector::Id(note_id)],
+            tag: "old-tag".to_string(),
+        });
+        assert!(res.is_ok())
+    }
+
+    #[test]
+    fn edit_note() {
+        let mut repo_mock = MockRepository::new();
+        let note = test_notes()[1].clone();
+        let note_id = note.id;
+
+        repo_mock
+            .expect_find_note_by_id()
+            .times(1)
+            .withf(move |id| *id == note_id)
+            .returning(move |_| Ok(Some(note.clone())));
+        repo_mock
+            .expect_edit_note()
+            .times(1)
+            .withf(move |n| n.id == note_id)
+            .returning(|_| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Edit { id: note_id });
+        assert!(res.is_ok())
+    }
+
+    #[test]
+    fn note_history() {
+        let mut repo_mock = MockRepository::new();
+        let note = test_notes()[1].clone();
+        let note_id = note.id;
+        repo_mock
+            .expect_find_note_by_id()
+            .times(1)
+            .withf(move |id| *id == note_id)
+            .returning(move |_| Ok(Some(note.clone())));
+        repo_mock.expect_note_history().times(1).withf(move |n| n.id == note_id).returning(|_| {
+            Ok(vec![LogEntry {
+                commit: "00c1325".to_string(),
+                date: "2020-10-18".to_string(),
+                author: "Jane Doe".to_string(),
+                message: "Create note".to_string(),
+                path: "a.md".into(),
+                added: 1,
+                removed: 0,
+            }])
+        });
+        repo_mock.expect_note_path().returning(|_| PathBuf::from("a.md"));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_note_history()
+            .times(1)
+            .withf(move |n, p, c| n.id == note_id && p == Path::new("a.md") && c.len() == 1)
+            .returning(|_, _, _| "History".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::History { id: note_id }).unwrap();
+        assert_eq!(res.stdout, "History");
+    }
+
+    fn log_entry(commit: &str, path: &str) -> LogEntry {
+        LogEntry {
+            commit: commit.to_string(),
+            date: "2020-10-18".to_string(),
+            author: "Jane Doe".to_string(),
+            message: "Update note".to_string(),
+            path: path.into(),
+            added: 1,
+            removed: 1,
+        }
+    }
+
+    fn restore_repository(note: Note) -> MockRepository {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_find_note_by_id().returning(move |_| Ok(Some(note.clone())));
+        repo_mock.expect_note_path().returning(|_| PathBuf::from("dir/a.md"));
+        repo_mock.expect_note_history().returning(|_| {
+            Ok(vec![
+                log_entry("00c1325", "dir/a.md"),
+                log_entry("58d921d", "dir/a.md"),
+                log_entry("2a5c82f", "a.md"),
+            ])
+        });
+        repo_mock
+    }
+
+    #[test]
+    fn diff_repository() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock
+            .expect_diff()
+            .times(1)
+            .withf(|range, paths, word| range == "HEAD" && paths.is_empty() && !*word)
+            .returning(|_, _, _| Ok("+new\n".to_string()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_diff()
+            .times(1)
+            .withf(|d, word| d == "+new\n" && !*word)
+            .returning(|_, _| "Diff".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler
+            .apply_command(Command::Diff {
+                id: None,
+                range: None,
+                word: false,
+            })
+            .unwrap();
+        assert_eq!(res.stdout, "Diff");
+    }
+
+    #[test]
+    fn diff_note_should_use_previous_paths() {
+        let note = test_notes()[1].clone();
+        let mut repo_mock = restore_repository(note);
+        repo_mock
+            .expect_diff()
+            .times(1)
+            .withf(|range, paths, word| range == "HEAD~3..HEAD" && paths == [PathBuf::from("dir/a.md"), PathBuf::from("a.md")] && *word)
+            .returning(|_, _, _| Ok("".to_string()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_diff().times(1).returning(|_, _| "No changes\n".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler
+            .apply_command(Command::Diff {
+                id: Some(1),
+                range: Some("HEAD~3..HEAD".to_string()),
+                word: true,
+            })
+            .unwrap();
+        assert_eq!(res.stdout, "No changes\n");
+    }
+
+    #[test]
+    fn restore_note() {
+        let note = test_notes()[1].clone();
+        let mut repo_mock = restore_repository(note.clone());
+        repo_mock
+            .expect_restore_preview()
+            .times(1)
+            .withf(|_, rev, path| rev == "2a5c82f" && path == Path::new("a.md"))
+            .returning(|_, _, _| Ok("@@ -1 +1 @@\n-new\n+old\n".to_string()));
+        repo_mock
+            .expect_restore_note()
+            .times(1)
+            .withf(|n, rev, path| n.id == 1 && rev == "2a5c82f" && path == Path::new("a.md"))
+            .returning(|_, _, _| Ok(ConsoleOutput::empty()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_diff().times(1).returning(|d, _| d.to_string());
+
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_confirm()
+            .times(1)
+            .withf(|q| q.starts_with("@@ -1 +1 @@\n-new\n+old\n\nRestore note '# Note 1 title ' to 2a5c82f ?"))
+            .returning(|_| Ok(true));
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler
+            .apply_command(Command::Restore {
+                id: 1,
+                revision: Revision::Steps(2),
+                confirmed: false,
+            })
+            .unwrap();
+        assert_eq!(res.stdout, "\nNote 'dir/a.md' restored to 2a5c82f\n");
+    }
+
+    #[test]
+    fn restore_note_with_commit() {
+        let note = test_notes()[1].clone();
+        let mut repo_mock = restore_repository(note);
+        repo_mock
+            .expect_restore_preview()
+            .times(2)
+            .withf(|_, rev, path| (rev == "58d921d" || rev == "HEAD~4") && path == Path::new("dir/a.md"))
+            .returning(|_, _, _| Ok("@@ -1 +1 @@\n".to_string()));
+        repo_mock.expect_restore_note().times(2).returning(|_, _, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        for (commit, expected) in &[("58d921d0af", "58d921d"), ("HEAD~4", "HEAD~4")] {
+            let res = handler
+                .apply_command(Command::Restore {
+                    id: 1,
+                    revision: Revision::Commit(commit.to_string()),
+                    confirmed: true,
+                })
+                .unwrap();
+            assert_eq!(res.stdout, format!("\nNote 'dir/a.md' restored to {}\n", expected));
+        }
+    }
+
+    #[test]
+    fn restore_note_should_be_cancelled() {
+        let note = test_notes()[1].clone();
+        let mut repo_mock = restore_repository(note);
+        repo_mock.expect_restore_preview().times(1).returning(|_, _, _| Ok("@@ -1 +1 @@\n".to_string()));
+        repo_mock.expect_restore_note().times(0);
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_diff().returning(|d, _| d.to_string());
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_confirm().times(1).returning(|_| Ok(false));
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler
+            .apply_command(Command::Restore {
+                id: 1,
+                revision: Revision::Steps(1),
+                confirmed: false,
+            })
+            .unwrap();
+        assert_eq!(res.stdout, "Restore cancelled\n");
+    }
+
+    #[test]
+    fn restore_note_should_preview_with_prompt_format() {
+        let note = test_notes()[1].clone();
+        let mut repo_mock = restore_repository(note);
+        repo_mock.expect_restore_preview().times(1).returning(|_, _, _| Ok("@@ -1 +1 @@\n".to_string()));
+        repo_mock
+            .expect_restore_note()
+            .times(1)
+            .returning(|_, _, _| Ok(ConsoleOutput::from_stdout("[master 2a5c82f] Restore note\n")));
+
+        let json_format = JsonFormat::new();
+        let mut text_mock = text_format_mock();
+        text_mock.expect_diff().times(1).returning(|_, _| "Colored diff\n".to_string());
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_confirm()
+            .times(1)
+            .withf(|q| q.starts_with("Colored diff\n"))
+            .returning(|_| Ok(true));
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &json_format, &prompt_mock).with_prompt_format(&text_mock);
+        let res = handler
+            .apply_command(Command::Restore {
+                id: 1,
+                revision: Revision::Steps(1),
+                confirmed: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res.stdout,
+            "{\"changes\":[{\"action\":\"restored\",\"path\":\"dir/a.md\",\"revision\":\"58d921d\"}]}\n"
+        );
+        assert_eq!(res.stderr, "[master 2a5c82f] Restore note\n");
+    }
+
+    #[test]
+    fn restore_note_should_fail() {
+        let note = test_notes()[1].clone();
+        let repo_mock = restore_repository(note);
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Restore {
+            id: 1,
+            revision: Revision::Steps(3),
+            confirmed: true,
+        });
+        assert_eq!(
+            res.unwrap_err().message,
+            "Cannot go back 3 steps, note '# Note 1 title ' has 2 previous versions."
+        );
+    }
+
+    #[test]
+    fn restore_note_should_do_nothing() {
+        let note = test_notes()[1].clone();
+        let mut repo_mock = restore_repository(note);
+        repo_mock.expect_restore_preview().times(1).returning(|_, _, _| Ok("".to_string()));
+        repo_mock.expect_restore_note().times(0);
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler
+            .apply_command(Command::Restore {
+                id: 1,
+                revision: Revision::Steps(1),
+                confirmed: false,
+            })
+            .unwrap();
+        assert_eq!(res.stdout, "Nothing to restore, note '# Note 1 title ' is the same in 58d921d\n");
+    }
+
+    #[test]
+    fn note_history_should_find_nothing() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_find_note_by_id().times(1).returning(|_| Ok(None));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::History { id: 5 });
+        assert_eq!(res.unwrap_err().message, "Note with id 5 not found.")
+    }
+
+    #[test]
+    fn edit_note_should_find_nothing() {
+        let mut repo_mock = MockRepository::new();
+        let note_id = 5;
+
+        repo_mock
+            .expect_find_note_by_id()
+            .times(1)
+            .withf(move |id| *id == note_id)
+            .returning(|_| Ok(None));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Edit { id: note_id });
+        assert_eq!(res.unwrap_err().message, "Note with id 5 not found.")
+    }
+
+    #[test]
+    fn delete_notes() {
+        let mut repo_mock = MockRepository::new();
+
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .withf(|s| s == [NoteSelector::Id(1), NoteSelector::Range(2, 3)])
+            .returning(|_| Ok(test_notes()[1..].to_vec()));
+        repo_mock
+            .expect_delete_notes()
+            .times(1)
+            .withf(|n| n.iter().map(|n| n.id).collect::<Vec<usize>>() == vec![1, 2])
+            .returning(|_| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Delete {
+            selectors: vec![NoteSelector::Id(1), NoteSelector::Range(2, 3)],
+            confirmed: true,
+        });
+        assert!(res.is_ok())
+    }
+
+    #[test]
+    fn delete_notes_should_ask_confirmation() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_find_notes().times(1).returning(|_| Ok(test_notes()[1..].to_vec()));
+        repo_mock
+            .expect_delete_notes()
+            .times(1)
+            .withf(|n| n.len() == 2)
+            .returning(|_| Ok(ConsoleOutput::empty()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_list_item().times(2).returning(|n| n.title.clone());
+
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_confirm()
+            .times(1)
+            .withf(|q| q.contains("Note 1 title") && q.contains("Note 2 title") && q.contains("Delete these 2 notes ?"))
+            .returning(|_| Ok(true));
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Delete {
+            selectors: vec![NoteSelector::Range(1, 2)],
+            confirmed: false,
+        });
+        assert!(res.unwrap().stdout.contains("deleted"));
+    }
+
+    #[test]
+    fn delete_notes_should_be_cancelled() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_find_notes().times(1).returning(|_| Ok(test_notes()[1..].to_vec()));
+        repo_mock.expect_delete_notes().times(0);
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_list_item().times(2).returning(|n| n.title.clone());
+
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_confirm().times(1).returning(|_| Ok(false));
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Delete {
+            selectors: vec![NoteSelector::Glob("*.md".to_string())],
+            confirmed: false,
+        });
+        assert!(res.unwrap().stdout.contains("Deletion cancelled"));
+    }
+
+    #[test]
+    fn delete_notes_should_find_nothing() {
+        let mut repo_mock = MockRepository::new();
+
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .returning(|_| Err(DefaultError::new("Note with id 5 not found.".to_string())));
+        repo_mock.expect_delete_notes().times(0);
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Delete {
+            selectors: vec![NoteSelector::Id(5)],
+            confirmed: false,
+        });
+        assert_eq!(res.unwrap_err().message, "Note with id 5 not found.")
+    }
+
+    #[test]
+    fn delete_directory() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_repository_tree().times(1).returning(|| Ok(test_deep_note_tree()));
+        repo_mock.expect_other_files().times(1).returning(|_| vec![]);
+        repo_mock
+            .expect_delete_directory()
+            .times(1)
+            .withf(|d| d.name == "to")
+            .returning(|_| Ok(ConsoleOutput::empty()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_list_item().times(2).returning(|n| n.title.clone());
+
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_confirm()
+            .times(1)
+            .withf(|q| q.contains("Note 1 title") && q.contains("Note 2 title") && q.contains("Delete directory 'to' and its 2 notes ?"))
+            .returning(|_| Ok(true));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::DeleteDirectory {
+            path: "to/".to_string(),
+            confirmed: false,
+        });
+        assert!(res.unwrap().stdout.contains("Directory 'to' deleted, 2 notes removed"));
+    }
+
+    #[test]
+    fn delete_directory_should_list_other_files() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_repository_tree().times(1).returning(|| Ok(test_deep_note_tree()));
+        repo_mock.expect_other_files().times(1).returning(|_| vec![PathBuf::from("to/image.png")]);
+        repo_mock.expect_delete_directory().times(0);
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_list_item().returning(|n| n.title.clone());
+
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_confirm()
+            .times(1)
+            .withf(|q| q.contains("to/image.png") && q.contains("Delete directory 'to', its 2 notes and 1 other files ?"))
+            .returning(|_| Ok(false));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::DeleteDirectory {
+            path: "to".to_string(),
+            confirmed: false,
+        });
+        assert!(res.unwrap().stdout.contains("Deletion cancelled"));
+    }
+
+    #[test]
+    fn delete_directory_should_be_cancelled() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_repository_tree().times(1).returning(|| Ok(test_deep_note_tree()));
+        repo_mock.expect_other_files().returning(|_| vec![]);
+        repo_mock.expect_delete_directory().times(0);
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_list_item().returning(|n| n.title.clone());
+
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_confirm().times(1).returning(|_| Ok(false));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::DeleteDirectory {
+            path: "to".to_string(),
+            confirmed: false,
+        });
+        assert!(res.unwrap().stdout.contains("Deletion cancelled"));
+    }
+
+    #[test]
+    fn delete_directory_should_not_ask_if_confirmed() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_repository_tree().times(1).returning(|| Ok(test_deep_note_tree()));
+        repo_mock.expect_delete_directory().times(1).returning(|_| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_confirm().times(0);
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::DeleteDirectory {
+            path: "to/dir".to_string(),
+            confirmed: true,
+        });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn delete_directory_should_find_nothing() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_repository_tree().times(1).returning(|| Ok(test_deep_note_tree()));
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::DeleteDirectory {
+            path: "/path".to_string(),
+            confirmed: true,
+        });
+        assert_eq!(res.unwrap_err().message, "Directory /path not found.");
+    }
+
+    #[test]
+    fn move_note() {
+        let mut repo_mock = MockRepository::new();
+        let note = test_notes()[1].clone();
+        let note_id = note.id;
+
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .withf(move |s| s == [NoteSelector::Id(note_id)])
+            .returning(move |_| Ok(vec![note.clone()]));
+        repo_mock
+            .expect_move_notes()
+            .times(1)
+            .withf(move |n, d| n[0].id == note_id && d == "archive/")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Move {
+            selectors: vec![NoteSelector::Id(note_id)],
+            destination: "archive/".to_string(),
+        });
+        assert!(res.unwrap().stdout.contains("moved to 'archive/'"))
+    }
+
+    #[test]
+    fn move_note_should_find_nothing() {
+        let mut repo_mock = MockRepository::new();
+
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .returning(|_| Err(DefaultError::new("Note with id 5 not found.".to_string())));
+        repo_mock.expect_move_notes().times(0);
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Move {
+            selectors: vec![NoteSelector::Id(5)],
+            destination: "archive/".to_string(),
+        });
+        assert_eq!(res.unwrap_err().message, "Note with id 5 not found.")
+    }
+
+    #[test]
+    fn push_repo() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_push_repo().times(1).returning(|| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Push);
         assert!(res.is_ok())
     }
 
@@ -341,10 +2052,522 @@ mod tests {
         let mut repo_mock = MockRepository::new();
         repo_mock.expect_pull_repo().times(1).returning(|| Ok(ConsoleOutput::empty()));
 
-        let fmt_mock = MockCliFormat::new();
+        let fmt_mock = text_format_mock();
 
-        let handler = CommandHandler::new(&repo_mock, &fmt_mock);
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
         let res = handler.apply_command(Command::Pull);
         assert!(res.is_ok())
     }
+
+    #[test]
+    fn sync_repo() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_sync_repo().times(1).returning(|| {
+            Ok(SyncResult {
+                output: ConsoleOutput::empty(),
+                conflicts: vec![],
+            })
+        });
+
+        let fmt_mock = text_format_mock();
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Sync).unwrap();
+        assert!(res.stdout.ends_with("Notes synchronized\n"));
+    }
+
+    #[test]
+    fn sync_repo_should_list_conflicts() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_sync_repo().times(1).returning(|| {
+            Ok(SyncResult {
+                output: ConsoleOutput::empty(),
+                conflicts: vec![PathBuf::from("dir/note.md"), PathBuf::from(".notes/config.toml")],
+            })
+        });
+        repo_mock
+            .expect_load_notes()
+            .times(1)
+            .returning(|| Ok(vec![Note::from(3, "/repo/dir/note.md".into(), "# Conflicted note".to_string()).unwrap()]));
+        repo_mock.expect_note_path().returning(|n| n.path.strip_prefix("/repo").unwrap().to_path_buf());
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock.expect_note_list_item().times(1).returning(|n| format!(" @{} - {}", n.id, n.title));
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let message = handler.apply_command(Command::Sync).unwrap_err().message;
+        assert!(message.contains("nothing was pushed"));
+        assert!(message.contains(" @3 - # Conflicted note (dir/note.md)\n .notes/config.toml\n"));
+        assert!(message.contains("git rebase --continue"));
+    }
+
+    #[test]
+    fn new_note_should_use_configured_extension() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock
+            .expect_new_note()
+            .times(1)
+            .withf(|p| p == "idea.txt")
+            .returning(|p| Note::from(1, p.into(), "# Idea".to_string()));
+        repo_mock.expect_edit_note().times(1).returning(|_| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let mut config = test_config();
+        config.extensions = vec!["txt".to_string(), "md".to_string()];
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        assert!(handler.apply_command(Command::New { path: "idea".to_string() }).is_ok());
+    }
+
+    #[test]
+    fn list_all_notebooks() {
+        let mut work_repo_mock = MockRepository::new();
+        work_repo_mock.expect_load_directory_tree().times(1).returning(|_| Ok(test_note_tree()));
+        let mut personal_repo_mock = MockRepository::new();
+        personal_repo_mock.expect_load_directory_tree().times(1).returning(|_| Ok(test_note_tree()));
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_directory_tree().times(0);
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_note_tree()
+            .times(1)
+            .withf(|directories| {
+                let notebooks: Vec<Option<&str>> = directories.iter().map(|d| d.notebook.as_deref()).collect();
+                notebooks == vec![Some("personal"), Some("work")]
+            })
+            .returning(|_| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let notebooks = vec![
+            Notebook {
+                name: "personal".to_string(),
+                repository: &personal_repo_mock,
+            },
+            Notebook {
+                name: "work".to_string(),
+                repository: &work_repo_mock,
+            },
+        ];
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock).with_notebooks(notebooks);
+        let res = handler.apply_command(Command::List {
+            tag: None,
+            all_notebooks: true,
+            directory: None,
+        });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn search_all_notebooks() {
+        let mut work_repo_mock = MockRepository::new();
+        work_repo_mock
+            .expect_load_notes_containing()
+            .times(1)
+            .withf(|words, directory, _| directory == Path::new("") && words == ["note", "2"])
+            .returning(|_, _, _| Ok(test_notes()));
+        work_repo_mock.expect_note_path().returning(|n| n.path.clone());
+        work_repo_mock
+            .expect_search_index()
+            .returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
+        let mut personal_repo_mock = MockRepository::new();
+        personal_repo_mock.expect_load_notes_containing().times(1).returning(|_, _, _| Ok(Vec::new()));
+        personal_repo_mock
+            .expect_search_index()
+            .returning(|| SearchIndex::new(Path::new("/path/.notes/index")));
+        let repo_mock = MockRepository::new();
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_search_results()
+            .times(1)
+            .withf(|_, _, matches, _| matches.len() == 1 && matches[0].notebook == Some("work".to_string()) && matches[0].item.id == 2)
+            .returning(|_, _, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let notebooks = vec![
+            Notebook {
+                name: "personal".to_string(),
+                repository: &personal_repo_mock,
+            },
+            Notebook {
+                name: "work".to_string(),
+                repository: &work_repo_mock,
+            },
+        ];
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock).with_notebooks(notebooks);
+        let res = handler.apply_command(Command::Search {
+            needle: "Note 2".to_string(),
+            tag: None,
+            all_notebooks: true,
+            options: SearchOptions::default(),
+            explain: false,
+            directory: None,
+        });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn config_set_notebook_should_be_global() {
+        let repo_mock = MockRepository::new();
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Config {
+            action: ConfigAction::Set {
+                key: "notebooks.work".to_string(),
+                value: "/path/to/work".to_string(),
+                global: false,
+            },
+        });
+        assert_eq!(res.unwrap_err().message, "notebooks.work can only be set in user configuration, use --global");
+    }
+
+    #[test]
+    fn config_set_editor_should_be_global() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_save_config().times(0);
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        for key in &["editor", "pager", "git_backend"] {
+            let res = handler.apply_command(Command::Config {
+                action: ConfigAction::Set {
+                    key: key.to_string(),
+                    value: "shell".to_string(),
+                    global: false,
+                },
+            });
+            assert_eq!(res.unwrap_err().message, format!("{} can only be set in user configuration, use --global", key));
+        }
+    }
+
+    #[test]
+    fn config_list() {
+        let repo_mock = MockRepository::new();
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Config { action: ConfigAction::List }).unwrap();
+        assert!(res.stdout.contains("editor = $EDITOR\n"));
+        assert!(res.stdout.contains("auto_commit = true\n"));
+
+        let res = handler.apply_command(Command::Config {
+            action: ConfigAction::Get { key: "pager".to_string() },
+        });
+        assert_eq!(res.unwrap().stdout, "less -R\n");
+    }
+
+    #[test]
+    fn config_set_repository() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock
+            .expect_save_config()
+            .times(1)
+            .withf(|f| f.auto_push == Some(true))
+            .returning(|_| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Config {
+            action: ConfigAction::Set {
+                key: "auto_push".to_string(),
+                value: "true".to_string(),
+                global: false,
+            },
+        });
+        assert!(res.unwrap().stdout.contains("auto_push = true saved in /path/.notes/config.toml"));
+    }
+
+    #[test]
+    fn config_set_global() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_save_config().times(0);
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let mut config = test_config();
+        let user_config_path = new_test_root().join("notes/config.toml");
+        config.user_config_path = Some(user_config_path.clone());
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+
+        let res = handler.apply_command(Command::Config {
+            action: ConfigAction::Set {
+                key: "editor".to_string(),
+                value: "vim".to_string(),
+                global: true,
+            },
+        });
+        assert!(res.is_ok());
+        assert_eq!(fs::read_to_string(&user_config_path).unwrap(), "editor = \"vim\"\n");
+    }
+
+    #[test]
+    fn pick_note_and_edit() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes().times(1).returning(|| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock
+            .expect_find_note_by_id()
+            .times(1)
+            .withf(|id| *id == 1)
+            .returning(|_| Ok(Some(test_notes()[1].clone())));
+        repo_mock.expect_edit_note().times(1).returning(|_| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_pick_note()
+            .times(1)
+            .withf(|candidates, actions| candidates.len() == 3 && actions == [PickAction::Edit])
+            .returning(|_, _| Ok(Some((1, PickAction::Edit))));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Pick {
+            action: Some(PickAction::Edit),
+        });
+        assert!(res.unwrap().stdout.contains("Note '0.md' edited"));
+    }
+
+    #[test]
+    fn pick_note_and_show() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes().times(1).returning(|| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+
+        let fmt_mock = text_format_mock();
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock
+            .expect_pick_note()
+            .times(1)
+            .withf(|_, actions| actions == PickAction::ALL)
+            .returning(|_, _| Ok(Some((2, PickAction::Show))));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Pick { action: None });
+        assert_eq!(res.unwrap().stdout, "# Note 2 title \n\n Note 2 content\n");
+    }
+
+    #[test]
+    fn pick_note_and_move() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes().times(1).returning(|| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock
+            .expect_find_notes()
+            .times(1)
+            .withf(|s| s == [NoteSelector::Id(0)])
+            .returning(|_| Ok(vec![test_notes()[0].clone()]));
+        repo_mock
+            .expect_move_notes()
+            .times(1)
+            .withf(|_, destination| destination == "archive/")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_pick_note().times(1).returning(|_, _| Ok(Some((0, PickAction::Move))));
+        prompt_mock.expect_input().times(1).returning(|_| Ok("archive/".to_string()));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Pick { action: None });
+        assert!(res.unwrap().stdout.contains("Note '0.md' moved to 'archive/'"));
+    }
+
+    #[test]
+    fn pick_note_and_cancel_deletion() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes().times(1).returning(|| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+        repo_mock.expect_delete_notes().times(0);
+
+        let fmt_mock = text_format_mock();
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_pick_note().times(1).returning(|_, _| Ok(Some((0, PickAction::Delete))));
+        prompt_mock.expect_confirm().times(1).returning(|_| Ok(false));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Pick { action: None });
+        assert!(res.unwrap().stdout.contains("Deletion cancelled"));
+    }
+
+    #[test]
+    fn pick_nothing() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_load_notes().times(1).returning(|| Ok(test_notes()));
+        repo_mock.expect_note_path().returning(|n| n.path.clone());
+
+        let fmt_mock = text_format_mock();
+        let mut prompt_mock = MockPrompt::new();
+        prompt_mock.expect_pick_note().times(1).returning(|_, _| Ok(None));
+
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::Pick { action: None });
+        assert_eq!(res.unwrap().stdout, "Nothing selected\n");
+    }
+
+    fn test_history() -> Vec<HistoryMatch> {
+        let line = |added: bool, content: &str| HistoryLine {
+            added,
+            content: content.to_string(),
+            matches: vec![],
+        };
+        vec![
+            HistoryMatch {
+                commit: "00c1325".to_string(),
+                date: "2020-10-18".to_string(),
+                path: "tools/ssh.md".into(),
+                revision: "00c1325".to_string(),
+                lines: vec![line(false, "Use rsync over ssh"), line(true, "Use scp")],
+            },
+            HistoryMatch {
+                commit: "58d921d".to_string(),
+                date: "2020-10-17".to_string(),
+                path: "rsync.md".into(),
+                revision: "58d921d".to_string(),
+                lines: vec![line(true, "# Rsync")],
+            },
+            HistoryMatch {
+                commit: "58d921d".to_string(),
+                date: "2020-10-17".to_string(),
+                path: "other.md".into(),
+                revision: "58d921d".to_string(),
+                lines: vec![line(true, "# Other")],
+            },
+        ]
+    }
+
+    #[test]
+    fn search_history() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock
+            .expect_search_history()
+            .times(1)
+            .withf(|p| p == "rsync")
+            .returning(|_| Ok(test_history()));
+
+        let mut fmt_mock = text_format_mock();
+        fmt_mock
+            .expect_history_results()
+            .times(1)
+            .withf(|needle, scope, matches| {
+                needle == "rsync"
+                    && scope == Path::new("")
+                    && matches.len() == 2
+                    && matches[0].revision == "00c1325^"
+                    && matches[0].lines.len() == 1
+                    && matches[0].lines[0].matches == vec![Range { start: 4, end: 9 }]
+                    && matches[1].revision == "58d921d"
+            })
+            .returning(|_, _, _| "".to_string());
+
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::SearchHistory {
+            needle: "rsync".to_string(),
+            directory: None,
+            restore: None,
+        });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn search_history_and_restore() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_search_history().times(1).returning(|_| Ok(test_history()));
+        repo_mock
+            .expect_restore_file()
+            .times(1)
+            .withf(|path, revision| path == Path::new("tools/ssh.md") && revision == "00c1325^")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::SearchHistory {
+            needle: "rsync".to_string(),
+            directory: None,
+            restore: Some("00c1325ab".to_string()),
+        });
+        assert_eq!(res.unwrap().stdout, "\nNote 'tools/ssh.md' restored to 00c1325^\n");
+    }
+
+    #[test]
+    fn search_history_should_not_restore_ambiguous_commit() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_search_history().times(1).returning(|_| {
+            let mut history = test_history();
+            history[1].commit = "00c9f1a".to_string();
+            history[1].revision = "00c9f1a".to_string();
+            Ok(history)
+        });
+        repo_mock.expect_restore_file().times(0);
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::SearchHistory {
+            needle: "rsync".to_string(),
+            directory: None,
+            restore: Some("00c".to_string()),
+        });
+        assert_eq!(res.unwrap_err().message, "Commit 00c is ambiguous, it can designate: 00c1325, 00c9f1a");
+    }
+
+    #[test]
+    fn search_history_should_fail() {
+        let mut repo_mock = MockRepository::new();
+        repo_mock.expect_search_history().returning(|_| Ok(test_history()));
+        repo_mock.expect_restore_file().times(0);
+
+        let fmt_mock = text_format_mock();
+        let prompt_mock = MockPrompt::new();
+        let config = test_config();
+        let handler = CommandHandler::new(&config, &repo_mock, &fmt_mock, &prompt_mock);
+        let res = handler.apply_command(Command::SearchHistory {
+            needle: "rsync".to_string(),
+            directory: None,
+            restore: Some("abcdef".to_string()),
+        });
+        assert_eq!(res.unwrap_err().message, "Commit abcdef not found in history of 'rsync'.");
+
+        let res = handler.apply_command(Command::SearchHistory {
+            needle: "rsync(".to_string(),
+            directory: None,
+            restore: None,
+        });
+        assert!(res.unwrap_err().message.starts_with("Invalid pattern 'rsync('"));
+
+        let res = handler.apply_command(Command::SearchHistory {
+            needle: "rsync \\d+".to_string(),
+            directory: None,
+            restore: None,
+        });
+        assert!(res.unwrap_err().message.starts_with("Pattern 'rsync \\d+' uses '\\d'"));
+    }
 }
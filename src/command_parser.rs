@@ -2,12 +2,27 @@ extern crate clap;
 
 use clap::{App, Arg};
 
-use crate::command_handler::Command;
+use crate::cli_format::OutputFormat;
+use crate::command_handler::{Command, ConfigAction, PickAction, Revision, TagAction};
 use crate::default_error::DefaultError;
+use crate::note_selector::NoteSelector;
+use crate::search_match::Context;
+use crate::search_query::SearchOptions;
 use crate::{PKG_AUTHORS, PKG_DESCRIPTION, PKG_NAME, PKG_VERSION};
 
 use self::clap::ArgMatches;
 
+/// Parsed command, with options that apply to all commands
+#[derive(Debug, PartialEq)]
+pub struct Arguments {
+    pub command: Command,
+    /// Name of the notebook to use instead of default repository
+    pub notebook: Option<String>,
+    pub format: OutputFormat,
+    /// False if long outputs must not be displayed with a pager
+    pub pager: bool,
+}
+
 pub struct CommandParser;
 
 impl CommandParser {
@@ -15,56 +30,273 @@ impl CommandParser {
         CommandParser
     }
 
-    pub fn parse_arguments(&self, args: Vec<String>) -> Result<Command, DefaultError> {
+    pub fn parse_arguments(&self, args: Vec<String>) -> Result<Arguments, DefaultError> {
         let matches = App::new(PKG_NAME)
             .version(PKG_VERSION)
             .author(PKG_AUTHORS)
             .about(PKG_DESCRIPTION)
+            .arg(
+                Arg::with_name("notebook")
+                    .long("notebook")
+                    .takes_value(true)
+                    .global(true)
+                    .help("Name of the notebook to use, as declared in configuration"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .global(true)
+                    .help("Output format of all commands except push, pull and help"),
+            )
+            .arg(
+                Arg::with_name("no-pager")
+                    .long("no-pager")
+                    .global(true)
+                    .help("Do not display long outputs with a pager"),
+            )
             .subcommand(
                 App::new("new")
                     .alias("n")
                     .about("Create a new note")
                     .arg(Arg::with_name("title").help("The note title in one word")),
             )
-            .subcommand(App::new("list").alias("l").about("List all notes from repository"))
+            .subcommand(
+                App::new("list")
+                    .alias("l")
+                    .about("List all notes from repository")
+                    .arg(tag_filter_arg())
+                    .arg(all_notebooks_arg())
+                    .arg(directory_arg()),
+            )
             .subcommand(
                 App::new("search")
                     .alias("s")
                     .about("Search in all repository")
-                    .arg(Arg::with_name("needle").help("The pattern to search. You can use regular expressions")),
+                    .arg(Arg::with_name("needle").help("The query to search, e.g: 'title:ssh AND NOT path:archive/'. Terms are regular expressions"))
+                    .arg(
+                        Arg::with_name("fuzzy")
+                            .long("fuzzy")
+                            .short("f")
+                            .help("Tolerate typos and search words in titles, file names and contents"),
+                    )
+                    .arg(
+                        Arg::with_name("explain")
+                            .long("explain")
+                            .conflicts_with("fuzzy")
+                            .help("Show how the score of each result is computed"),
+                    )
+                    .arg(
+                        Arg::with_name("literal")
+                            .long("literal")
+                            .short("l")
+                            .conflicts_with("fuzzy")
+                            .help("Search needle as is, without query syntax nor regular expressions"),
+                    )
+                    .arg(
+                        Arg::with_name("case-sensitive")
+                            .long("case-sensitive")
+                            .short("c")
+                            .conflicts_with("fuzzy")
+                            .help("Do not ignore case"),
+                    )
+                    .arg(
+                        Arg::with_name("word")
+                            .long("word")
+                            .short("w")
+                            .conflicts_with("fuzzy")
+                            .help("Only match whole words"),
+                    )
+                    .args(&context_args())
+                    .arg(
+                        Arg::with_name("history")
+                            .long("history")
+                            .conflicts_with_all(&[
+                                "fuzzy",
+                                "explain",
+                                "tag",
+                                "all-notebooks",
+                                "literal",
+                                "case-sensitive",
+                                "word",
+                                "context",
+                                "after-context",
+                                "before-context",
+                            ])
+                            .help("Search needle in versions of notes, in git history. Needle is a regular expression"),
+                    )
+                    .arg(
+                        Arg::with_name("restore")
+                            .long("restore")
+                            .takes_value(true)
+                            .value_name("commit")
+                            .requires("history")
+                            .help("Restore notes as they were in a commit found with --history"),
+                    )
+                    .arg(tag_filter_arg())
+                    .arg(all_notebooks_arg())
+                    .arg(directory_arg()),
             )
             .subcommand(
                 App::new("edit")
                     .alias("e")
                     .about("Edit a note with the default editor")
-                    .arg(Arg::with_name("id").help("The id of the note to edit")),
+                    .arg(Arg::with_name("id").help("The id of the note to edit, if missing note is chosen in a fuzzy finder")),
+            )
+            .subcommand(
+                App::new("history")
+                    .about("List commits that changed a note, with their date, author, message and changed lines")
+                    .arg(Arg::with_name("id").required(true).help("The id of the note")),
+            )
+            .subcommand(
+                App::new("diff")
+                    .about("Show uncommitted changes of a note, or of whole repository")
+                    .arg(Arg::with_name("id").help("The id of the note, if missing all changes are shown"))
+                    .arg(
+                        Arg::with_name("rev")
+                            .long("rev")
+                            .takes_value(true)
+                            .help("Show changes since a commit, or between two commits: A..B"),
+                    )
+                    .arg(Arg::with_name("word").long("word").short("w").help("Show changed words instead of lines")),
+            )
+            .subcommand(
+                App::new("restore")
+                    .about("Restore a note to an earlier version, after a preview of changes")
+                    .arg(Arg::with_name("id").required(true).help("The id of the note to restore"))
+                    .arg(
+                        Arg::with_name("rev")
+                            .long("rev")
+                            .takes_value(true)
+                            .help("Commit of the version to restore, as listed by history command"),
+                    )
+                    .arg(
+                        Arg::with_name("steps")
+                            .long("steps")
+                            .takes_value(true)
+                            .conflicts_with("rev")
+                            .help("Number of versions to go back, 1 by default"),
+                    )
+                    .arg(Arg::with_name("yes").long("yes").short("y").help("Do not ask for confirmation")),
+            )
+            .subcommand(
+                App::new("pick")
+                    .about("Choose a note in a fuzzy finder, then edit, show, delete or move it")
+                    .arg(
+                        Arg::with_name("action")
+                            .help("Action to apply to chosen note, chosen in finder if missing")
+                            .possible_values(&["edit", "show", "delete", "move"]),
+                    ),
             )
             .subcommand(
                 App::new("delete")
                     .alias("d")
-                    .about("Delete a note from repository")
-                    .arg(Arg::with_name("id").help("The id of the note to delete")),
+                    .about("Delete notes from repository")
+                    .arg(note_selector_arg().help("Ids, id ranges or glob paths of notes to delete"))
+                    .arg(
+                        Arg::with_name("dir")
+                            .long("dir")
+                            .takes_value(true)
+                            .conflicts_with("id")
+                            .help("Delete a directory, with all its notes, files and sub directories"),
+                    )
+                    .arg(Arg::with_name("yes").long("yes").short("y").help("Do not ask for confirmation")),
+            )
+            .subcommand(
+                App::new("move")
+                    .alias("m")
+                    .about("Move or rename a note")
+                    .arg(note_selector_arg().required(true).help("Ids, id ranges or glob paths of notes to move"))
+                    .arg(Arg::with_name("destination").required(true).help("The new path of note, or a directory")),
+            )
+            .subcommand(App::new("tags").about("List all tags with number of notes"))
+            .subcommand(
+                App::new("tag")
+                    .about("Add or remove a tag on a note")
+                    .subcommand(App::new("add").about("Add a tag to a note").args(&tag_args()))
+                    .subcommand(App::new("remove").about("Remove a tag from a note").args(&tag_args())),
+            )
+            .subcommand(
+                App::new("config")
+                    .about("Show or change configuration")
+                    .subcommand(App::new("list").about("Show all configuration values"))
+                    .subcommand(
+                        App::new("get")
+                            .about("Show a configuration value")
+                            .arg(Arg::with_name("key").required(true).help("The configuration key")),
+                    )
+                    .subcommand(
+                        App::new("set")
+                            .about("Change a configuration value, in repository configuration by default")
+                            .arg(Arg::with_name("key").required(true).help("The configuration key"))
+                            .arg(Arg::with_name("value").required(true).help("The new value, lists are comma separated"))
+                            .arg(Arg::with_name("global").long("global").help("Change user configuration instead")),
+                    ),
             )
             .subcommand(App::new("pull").alias("ll").about("Pull note repository"))
             .subcommand(App::new("push").alias("p").about("Push note repository"))
+            .subcommand(App::new("sync").alias("sy").about("Commit changes, pull with rebase then push note repository"))
             .subcommand(App::new("help").alias("h").about("Show help"))
             .get_matches_from(args);
-        self.build_command(matches)
+        let command = self.build_command(&matches)?;
+        let format = match global_value(&matches, "format").as_deref() {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        };
+        if format == OutputFormat::Json && !command.has_json_output() {
+            return Err(DefaultError::new(format!(
+                "{} command does not support --format json",
+                matches.subcommand_name().unwrap_or("")
+            )));
+        }
+        Ok(Arguments {
+            command,
+            notebook: global_value(&matches, "notebook"),
+            format,
+            pager: !global_flag(&matches, "no-pager"),
+        })
     }
 
-    fn build_command(&self, matches: ArgMatches) -> Result<Command, DefaultError> {
+    fn build_command(&self, matches: &ArgMatches) -> Result<Command, DefaultError> {
         if let Some(cmd_matches) = matches.subcommand_matches("new") {
             match cmd_matches.value_of("title") {
                 Some(title) => return Ok(Command::New { path: title.to_string() }),
                 None => return Err(DefaultError::new("You must specify a title".to_string())),
             }
         }
-        if matches.subcommand_matches("list").is_some() {
-            return Ok(Command::List);
+        if let Some(cmd_matches) = matches.subcommand_matches("list") {
+            let tag = cmd_matches.value_of("tag").map(String::from);
+            let all_notebooks = cmd_matches.is_present("all-notebooks");
+            let directory = cmd_matches.value_of("in").map(String::from);
+            return Ok(Command::List { tag, all_notebooks, directory });
         }
         if let Some(cmd_matches) = matches.subcommand_matches("search") {
+            let tag = cmd_matches.value_of("tag").map(String::from);
             match cmd_matches.value_of("needle") {
-                Some(needle) => return Ok(Command::Search { needle: needle.to_string() }),
+                Some(needle) if cmd_matches.is_present("history") => {
+                    return Ok(Command::SearchHistory {
+                        needle: needle.to_string(),
+                        directory: cmd_matches.value_of("in").map(String::from),
+                        restore: cmd_matches.value_of("restore").map(String::from),
+                    })
+                }
+                Some(needle) => {
+                    return Ok(Command::Search {
+                        needle: needle.to_string(),
+                        tag,
+                        all_notebooks: cmd_matches.is_present("all-notebooks"),
+                        options: SearchOptions {
+                            fuzzy: cmd_matches.is_present("fuzzy"),
+                            literal: cmd_matches.is_present("literal"),
+                            case_sensitive: cmd_matches.is_present("case-sensitive"),
+                            word: cmd_matches.is_present("word"),
+                            context: search_context(cmd_matches)?,
+                        },
+                        explain: cmd_matches.is_present("explain"),
+                        directory: cmd_matches.value_of("in").map(String::from),
+                    })
+                }
                 None => return Err(DefaultError::new("You must specify something to search".to_string())),
             }
         }
@@ -74,24 +306,115 @@ impl CommandParser {
                     let numeric_id = id.parse::<usize>()?;
                     return Ok(Command::Edit { id: numeric_id });
                 }
-                None => return Err(DefaultError::new("You must specify a note id".to_string())),
+                None => {
+                    return Ok(Command::Pick {
+                        action: Some(PickAction::Edit),
+                    })
+                }
             }
         }
+        if let Some(cmd_matches) = matches.subcommand_matches("history") {
+            let id = cmd_matches.value_of("id").unwrap().parse::<usize>()?;
+            return Ok(Command::History { id });
+        }
+        if let Some(cmd_matches) = matches.subcommand_matches("diff") {
+            let id = match cmd_matches.value_of("id") {
+                Some(id) => Some(id.parse::<usize>()?),
+                None => None,
+            };
+            return Ok(Command::Diff {
+                id,
+                range: cmd_matches.value_of("rev").map(String::from),
+                word: cmd_matches.is_present("word"),
+            });
+        }
+        if let Some(cmd_matches) = matches.subcommand_matches("restore") {
+            let id = cmd_matches.value_of("id").unwrap().parse::<usize>()?;
+            let revision = match (cmd_matches.value_of("rev"), cmd_matches.value_of("steps")) {
+                (Some(commit), _) => Revision::Commit(commit.to_string()),
+                (None, Some(steps)) => Revision::Steps(steps.parse::<usize>()?),
+                (None, None) => Revision::Steps(1),
+            };
+            return Ok(Command::Restore {
+                id,
+                revision,
+                confirmed: cmd_matches.is_present("yes"),
+            });
+        }
+        if let Some(cmd_matches) = matches.subcommand_matches("pick") {
+            let action = cmd_matches
+                .value_of("action")
+                .and_then(|name| PickAction::ALL.iter().find(|a| a.name() == name).copied());
+            return Ok(Command::Pick { action });
+        }
         if let Some(cmd_matches) = matches.subcommand_matches("delete") {
-            match cmd_matches.value_of("id") {
-                Some(id) => {
-                    let numeric_id = id.parse::<usize>()?;
-                    return Ok(Command::Delete { id: numeric_id });
+            if let Some(path) = cmd_matches.value_of("dir") {
+                return Ok(Command::DeleteDirectory {
+                    path: path.to_string(),
+                    confirmed: cmd_matches.is_present("yes"),
+                });
+            }
+            let selectors = note_selectors(cmd_matches)?;
+            return Ok(Command::Delete {
+                selectors,
+                confirmed: cmd_matches.is_present("yes"),
+            });
+        }
+        if let Some(cmd_matches) = matches.subcommand_matches("move") {
+            let selectors = note_selectors(cmd_matches)?;
+            match cmd_matches.value_of("destination") {
+                Some(destination) => {
+                    return Ok(Command::Move {
+                        selectors,
+                        destination: destination.to_string(),
+                    });
                 }
-                None => return Err(DefaultError::new("You must specify a note id".to_string())),
+                None => return Err(DefaultError::new("You must specify a note id and a destination".to_string())),
             }
         }
+        if matches.subcommand_matches("tags").is_some() {
+            return Ok(Command::Tags);
+        }
+        if let Some(cmd_matches) = matches.subcommand_matches("tag") {
+            let (action, action_matches) = match cmd_matches.subcommand() {
+                ("add", Some(m)) => (TagAction::Add, m),
+                ("remove", Some(m)) => (TagAction::Remove, m),
+                _ => return Err(DefaultError::new("You must specify an action: add or remove".to_string())),
+            };
+            let selectors = note_selectors(action_matches)?;
+            return match action_matches.value_of("tag") {
+                Some(tag) => Ok(Command::Tag {
+                    action,
+                    selectors,
+                    tag: tag.to_string(),
+                }),
+                None => Err(DefaultError::new("You must specify a note id and a tag".to_string())),
+            };
+        }
+        if let Some(cmd_matches) = matches.subcommand_matches("config") {
+            let action = match cmd_matches.subcommand() {
+                ("list", Some(_)) => ConfigAction::List,
+                ("get", Some(m)) => ConfigAction::Get {
+                    key: m.value_of("key").unwrap().to_string(),
+                },
+                ("set", Some(m)) => ConfigAction::Set {
+                    key: m.value_of("key").unwrap().to_string(),
+                    value: m.value_of("value").unwrap().to_string(),
+                    global: m.is_present("global"),
+                },
+                _ => ConfigAction::List,
+            };
+            return Ok(Command::Config { action });
+        }
         if matches.subcommand_matches("pull").is_some() {
             return Ok(Command::Pull);
         }
         if matches.subcommand_matches("push").is_some() {
             return Ok(Command::Push);
         }
+        if matches.subcommand_matches("sync").is_some() {
+            return Ok(Command::Sync);
+        }
         if matches.subcommand_matches("help").is_some() {
             return Ok(Command::Help);
         }
@@ -100,6 +423,96 @@ impl CommandParser {
     }
 }
 
+fn tag_filter_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("tag").long("tag").takes_value(true).help("Only notes with this tag")
+}
+
+fn all_notebooks_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("all-notebooks")
+        .long("all-notebooks")
+        .conflicts_with("notebook")
+        .help("Use all notebooks declared in configuration")
+}
+
+fn directory_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("in")
+        .long("in")
+        .takes_value(true)
+        .value_name("directory")
+        .conflicts_with("all-notebooks")
+        .help("Only notes of this directory, relative to repository root. Default is current directory if it is inside repository")
+}
+
+fn context_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("context")
+            .long("context")
+            .short("C")
+            .takes_value(true)
+            .value_name("lines")
+            .help("Number of lines to display around matches, default is 1"),
+        Arg::with_name("after-context")
+            .long("after-context")
+            .short("A")
+            .takes_value(true)
+            .value_name("lines")
+            .help("Number of lines to display after matches"),
+        Arg::with_name("before-context")
+            .long("before-context")
+            .short("B")
+            .takes_value(true)
+            .value_name("lines")
+            .help("Number of lines to display before matches"),
+    ]
+}
+
+/// Options -A and -B take precedence over -C
+fn search_context(matches: &ArgMatches) -> Result<Context, DefaultError> {
+    let lines = |name: &str| matches.value_of(name).map(|v| v.parse::<usize>()).transpose();
+    let around = lines("context")?;
+    let default = Context::default();
+    Ok(Context {
+        before: lines("before-context")?.or(around).unwrap_or(default.before),
+        after: lines("after-context")?.or(around).unwrap_or(default.after),
+    })
+}
+
+/// Global arguments can be specified before or after subcommands
+fn global_value(matches: &ArgMatches, name: &str) -> Option<String> {
+    match (matches.value_of(name), matches.subcommand()) {
+        (Some(value), _) => Some(value.to_string()),
+        (None, (_, Some(sub_matches))) => global_value(sub_matches, name),
+        _ => None,
+    }
+}
+
+fn global_flag(matches: &ArgMatches, name: &str) -> bool {
+    match (matches.is_present(name), matches.subcommand()) {
+        (true, _) => true,
+        (false, (_, Some(sub_matches))) => global_flag(sub_matches, name),
+        _ => false,
+    }
+}
+
+fn tag_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        note_selector_arg().required(true).help("Ids, id ranges or glob paths of notes to tag"),
+        Arg::with_name("tag").required(true).help("The tag, without #"),
+    ]
+}
+
+/// Notes can be designated by ids (3 5), id ranges (8-12) or glob paths ('archive/**/*.md')
+fn note_selector_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("id").multiple(true)
+}
+
+fn note_selectors(matches: &ArgMatches) -> Result<Vec<NoteSelector>, DefaultError> {
+    match matches.values_of("id") {
+        Some(values) => values.map(NoteSelector::parse).collect(),
+        None => Err(DefaultError::new("You must specify a note id".to_string())),
+    }
+}
+
 impl Default for CommandParser {
     fn default() -> Self {
         CommandParser::new()
@@ -114,7 +527,7 @@ mod tests {
     fn should_match_new() {
         let args: Vec<String> = vec!["notes".to_string(), "new".to_string(), "one-word-title".to_string()];
         let cp = CommandParser::new();
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(
             command,
             Command::New {
@@ -123,7 +536,7 @@ mod tests {
         );
 
         let args: Vec<String> = vec!["notes".to_string(), "n".to_string(), "one-word-title".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(
             command,
             Command::New {
@@ -136,59 +549,653 @@ mod tests {
     fn should_match_list() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "list".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
-        assert_eq!(command, Command::List);
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::List {
+                tag: None,
+                all_notebooks: false,
+                directory: None
+            }
+        );
 
         let args: Vec<String> = vec!["notes".to_string(), "l".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
-        assert_eq!(command, Command::List);
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::List {
+                tag: None,
+                all_notebooks: false,
+                directory: None
+            }
+        );
+
+        let args: Vec<String> = ["notes", "list", "--in", "/"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::List {
+                tag: None,
+                all_notebooks: false,
+                directory: Some("/".to_string())
+            }
+        );
+
+        let args: Vec<String> = vec!["notes".to_string(), "list".to_string(), "--tag".to_string(), "rsync".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::List {
+                tag: Some("rsync".to_string()),
+                all_notebooks: false,
+                directory: None
+            }
+        );
     }
 
     #[test]
     fn should_match_search() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "search".to_string(), "needle".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
-        assert_eq!(command, Command::Search { needle: "needle".to_string() });
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "needle".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions::default(),
+                explain: false,
+                directory: None
+            }
+        );
 
         let args: Vec<String> = vec!["notes".to_string(), "s".to_string(), "needle".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
-        assert_eq!(command, Command::Search { needle: "needle".to_string() });
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "needle".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions::default(),
+                explain: false,
+                directory: None
+            }
+        );
+
+        let args: Vec<String> = vec![
+            "notes".to_string(),
+            "search".to_string(),
+            "needle".to_string(),
+            "--tag".to_string(),
+            "rsync".to_string(),
+        ];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "needle".to_string(),
+                tag: Some("rsync".to_string()),
+                all_notebooks: false,
+                options: SearchOptions::default(),
+                explain: false,
+                directory: None
+            }
+        );
+
+        let args: Vec<String> = ["notes", "search", "rsync", "--explain"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "rsync".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions::default(),
+                explain: true,
+                directory: None
+            }
+        );
+
+        let args: Vec<String> = ["notes", "search", "rsync", "--in", "work/tools"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "rsync".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions::default(),
+                explain: false,
+                directory: Some("work/tools".to_string())
+            }
+        );
+
+        let args: Vec<String> = ["notes", "search", "rsnyc tips", "--fuzzy"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "rsnyc tips".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions {
+                    fuzzy: true,
+                    ..SearchOptions::default()
+                },
+                explain: false,
+                directory: None
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_delete_directory() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "delete".to_string(), "--dir".to_string(), "a/b".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::DeleteDirectory {
+                path: "a/b".to_string(),
+                confirmed: false
+            }
+        );
+
+        let args: Vec<String> = vec!["notes".to_string(), "d".to_string(), "--dir".to_string(), "a/b".to_string(), "-y".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::DeleteDirectory {
+                path: "a/b".to_string(),
+                confirmed: true
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_move() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "move".to_string(), "111".to_string(), "dir/".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Move {
+                selectors: vec![NoteSelector::Id(111)],
+                destination: "dir/".to_string()
+            }
+        );
+
+        let args: Vec<String> = vec!["notes".to_string(), "m".to_string(), "111".to_string(), "dir/".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Move {
+                selectors: vec![NoteSelector::Id(111)],
+                destination: "dir/".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_move_several_notes() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "move", "3", "8-12", "archive/**/*.md", "dir/"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Move {
+                selectors: vec![
+                    NoteSelector::Id(3),
+                    NoteSelector::Range(8, 12),
+                    NoteSelector::Glob("archive/**/*.md".to_string())
+                ],
+                destination: "dir/".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_tag_several_notes() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "tag", "add", "3", "5", "rsync"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Tag {
+                action: TagAction::Add,
+                selectors: vec![NoteSelector::Id(3), NoteSelector::Id(5)],
+                tag: "rsync".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_tags() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "tags".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(command, Command::Tags);
+    }
+
+    #[test]
+    fn should_match_tag() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "tag".to_string(), "add".to_string(), "12".to_string(), "rsync".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Tag {
+                action: TagAction::Add,
+                selectors: vec![NoteSelector::Id(12)],
+                tag: "rsync".to_string()
+            }
+        );
+
+        let args: Vec<String> = vec![
+            "notes".to_string(),
+            "tag".to_string(),
+            "remove".to_string(),
+            "12".to_string(),
+            "rsync".to_string(),
+        ];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Tag {
+                action: TagAction::Remove,
+                selectors: vec![NoteSelector::Id(12)],
+                tag: "rsync".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_search_options() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "search", "C++", "--literal", "--case-sensitive", "--word"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "C++".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions {
+                    fuzzy: false,
+                    literal: true,
+                    case_sensitive: true,
+                    word: true,
+                    context: Context::default()
+                },
+                explain: false,
+                directory: None
+            }
+        );
+
+        let args: Vec<String> = ["notes", "s", "-lw", "foo("].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "foo(".to_string(),
+                tag: None,
+                all_notebooks: false,
+                options: SearchOptions {
+                    literal: true,
+                    word: true,
+                    ..SearchOptions::default()
+                },
+                explain: false,
+                directory: None
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_search_context() {
+        let cp = CommandParser::new();
+        let context = |args: Vec<&str>| match cp.parse_arguments(args.iter().map(|s| s.to_string()).collect()).unwrap().command {
+            Command::Search { options, .. } => options.context,
+            command => panic!("Unexpected command {:?}", command),
+        };
+
+        assert_eq!(context(vec!["notes", "search", "ssh"]), Context { before: 1, after: 1 });
+        assert_eq!(context(vec!["notes", "search", "ssh", "-C", "3"]), Context { before: 3, after: 3 });
+        assert_eq!(context(vec!["notes", "search", "ssh", "-C", "3", "-A", "0"]), Context { before: 3, after: 0 });
+        assert_eq!(
+            context(vec!["notes", "search", "ssh", "--before-context", "2", "--after-context", "4"]),
+            Context { before: 2, after: 4 }
+        );
+        let args: Vec<String> = ["notes", "search", "ssh", "-C", "two"].iter().map(|s| s.to_string()).collect();
+        assert!(cp.parse_arguments(args).is_err());
+    }
+
+    #[test]
+    fn should_match_search_history() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "search", "--history", "rsync"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::SearchHistory {
+                needle: "rsync".to_string(),
+                directory: None,
+                restore: None
+            }
+        );
+
+        let args: Vec<String> = ["notes", "search", "rsync", "--history", "--restore", "00c1325", "--in", "tools"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::SearchHistory {
+                needle: "rsync".to_string(),
+                directory: Some("tools".to_string()),
+                restore: Some("00c1325".to_string())
+            }
+        );
     }
 
     #[test]
     fn should_match_edit() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "edit".to_string(), "111".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Edit { id: 111 });
 
         let args: Vec<String> = vec!["notes".to_string(), "e".to_string(), "111".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Edit { id: 111 });
+
+        let args: Vec<String> = vec!["notes".to_string(), "edit".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Pick {
+                action: Some(PickAction::Edit)
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_history() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "history".to_string(), "12".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(command, Command::History { id: 12 });
+
+        let args: Vec<String> = vec!["notes".to_string(), "history".to_string(), "abc".to_string()];
+        assert!(cp.parse_arguments(args).is_err());
+    }
+
+    #[test]
+    fn should_match_diff() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "diff"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Diff {
+                id: None,
+                range: None,
+                word: false
+            }
+        );
+
+        let args: Vec<String> = ["notes", "diff", "12", "--rev", "HEAD~3..HEAD", "--word"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Diff {
+                id: Some(12),
+                range: Some("HEAD~3..HEAD".to_string()),
+                word: true
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_restore() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "restore", "12"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Restore {
+                id: 12,
+                revision: Revision::Steps(1),
+                confirmed: false
+            }
+        );
+
+        let args: Vec<String> = ["notes", "restore", "12", "--steps", "3", "-y"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Restore {
+                id: 12,
+                revision: Revision::Steps(3),
+                confirmed: true
+            }
+        );
+
+        let args: Vec<String> = ["notes", "restore", "12", "--rev", "00c1325"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Restore {
+                id: 12,
+                revision: Revision::Commit("00c1325".to_string()),
+                confirmed: false
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_pick() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "pick".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(command, Command::Pick { action: None });
+
+        let args: Vec<String> = vec!["notes".to_string(), "pick".to_string(), "move".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Pick {
+                action: Some(PickAction::Move)
+            }
+        );
     }
 
     #[test]
     fn should_match_delete() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "delete".to_string(), "111".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
-        assert_eq!(command, Command::Delete { id: 111 });
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Delete {
+                selectors: vec![NoteSelector::Id(111)],
+                confirmed: false,
+            }
+        );
 
         let args: Vec<String> = vec!["notes".to_string(), "d".to_string(), "111".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
-        assert_eq!(command, Command::Delete { id: 111 });
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Delete {
+                selectors: vec![NoteSelector::Id(111)],
+                confirmed: false,
+            }
+        );
+    }
+
+    #[test]
+    fn should_match_delete_several_notes() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "delete", "3", "5", "8-12", "-y"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Delete {
+                selectors: vec![NoteSelector::Id(3), NoteSelector::Id(5), NoteSelector::Range(8, 12)],
+                confirmed: true,
+            }
+        );
+    }
+
+    #[test]
+    fn should_fail_delete_without_id() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "delete".to_string()];
+        let result = cp.parse_arguments(args);
+        assert_eq!(result.unwrap_err().message, "You must specify a note id");
+    }
+
+    #[test]
+    fn should_match_config() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "config"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(command, Command::Config { action: ConfigAction::List });
+
+        let args: Vec<String> = ["notes", "config", "get", "editor"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Config {
+                action: ConfigAction::Get { key: "editor".to_string() }
+            }
+        );
+
+        let args: Vec<String> = ["notes", "config", "set", "editor", "vim", "--global"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Config {
+                action: ConfigAction::Set {
+                    key: "editor".to_string(),
+                    value: "vim".to_string(),
+                    global: true
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn should_accept_no_pager() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "search", "rsync", "--no-pager"].iter().map(|s| s.to_string()).collect();
+        let arguments = cp.parse_arguments(args).unwrap();
+        assert!(matches!(arguments.command, Command::Search { .. }));
+        assert!(!arguments.pager);
+
+        let args: Vec<String> = ["notes", "--no-pager", "list"].iter().map(|s| s.to_string()).collect();
+        assert!(!cp.parse_arguments(args).unwrap().pager);
+
+        // Only the option disables pager, not a value equal to it
+        let args: Vec<String> = ["notes", "search", "--literal", "--", "--no-pager"].iter().map(|s| s.to_string()).collect();
+        let arguments = cp.parse_arguments(args).unwrap();
+        assert!(arguments.pager);
+        assert!(matches!(arguments.command, Command::Search { ref needle, .. } if needle == "--no-pager"));
+    }
+
+    #[test]
+    fn should_match_format() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "list", "--format", "json"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cp.parse_arguments(args).unwrap().format, OutputFormat::Json);
+
+        let args: Vec<String> = ["notes", "--format", "text", "tags"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cp.parse_arguments(args).unwrap().format, OutputFormat::Text);
+
+        let args: Vec<String> = ["notes", "tags"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cp.parse_arguments(args).unwrap().format, OutputFormat::Text);
+    }
+
+    #[test]
+    fn should_fail_format_without_json_output() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "delete", "3", "--format", "json"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cp.parse_arguments(args).unwrap().format, OutputFormat::Json);
+
+        let args: Vec<String> = ["notes", "push", "--format", "json"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cp.parse_arguments(args).unwrap_err().message, "push command does not support --format json");
+    }
+
+    #[test]
+    fn should_match_notebook() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "--notebook", "work", "edit", "3"].iter().map(|s| s.to_string()).collect();
+        let arguments = cp.parse_arguments(args).unwrap();
+        assert_eq!(arguments.notebook, Some("work".to_string()));
+        assert_eq!(arguments.command, Command::Edit { id: 3 });
+
+        let args: Vec<String> = ["notes", "tag", "add", "3", "rust", "--notebook", "work"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let arguments = cp.parse_arguments(args).unwrap();
+        assert_eq!(arguments.notebook, Some("work".to_string()));
+
+        let args: Vec<String> = ["notes", "list"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cp.parse_arguments(args).unwrap().notebook, None);
+    }
+
+    #[test]
+    fn should_match_all_notebooks() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = ["notes", "search", "needle", "--all-notebooks"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::Search {
+                needle: "needle".to_string(),
+                tag: None,
+                all_notebooks: true,
+                options: SearchOptions::default(),
+                explain: false,
+                directory: None
+            }
+        );
+
+        let args: Vec<String> = ["notes", "list", "--all-notebooks"].iter().map(|s| s.to_string()).collect();
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(
+            command,
+            Command::List {
+                tag: None,
+                all_notebooks: true,
+                directory: None
+            }
+        );
     }
 
     #[test]
     fn should_match_pull() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "pull".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Pull);
 
         let args: Vec<String> = vec!["notes".to_string(), "ll".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Pull);
     }
 
@@ -196,23 +1203,35 @@ mod tests {
     fn should_match_push() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "push".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Push);
 
         let args: Vec<String> = vec!["notes".to_string(), "p".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Push);
     }
 
+    #[test]
+    fn should_match_sync() {
+        let cp = CommandParser::new();
+        let args: Vec<String> = vec!["notes".to_string(), "sync".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(command, Command::Sync);
+
+        let args: Vec<String> = vec!["notes".to_string(), "sy".to_string()];
+        let command = cp.parse_arguments(args).unwrap().command;
+        assert_eq!(command, Command::Sync);
+    }
+
     #[test]
     fn should_match_help() {
         let cp = CommandParser::new();
         let args: Vec<String> = vec!["notes".to_string(), "help".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Help);
 
         let args: Vec<String> = vec!["notes".to_string(), "h".to_string()];
-        let command = cp.parse_arguments(args).unwrap();
+        let command = cp.parse_arguments(args).unwrap().command;
         assert_eq!(command, Command::Help);
     }
 }
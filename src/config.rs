@@ -1,46 +1,304 @@
 extern crate dirs;
 
+use crate::config_file::{unknown_key, ConfigFile, CONFIG_KEYS};
+use crate::default_error::DefaultError;
 use crate::env::{Env, EnvImpl};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 pub const NOTES_STORAGE_DIRECTORY: &str = "NOTES_STORAGE_DIRECTORY";
+pub const XDG_CONFIG_HOME: &str = "XDG_CONFIG_HOME";
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorTheme {
+    Default,
+    /// Darker colors, for terminals with a light background
+    Light,
+    None,
+}
+
+impl ColorTheme {
+    fn from_name(name: &str) -> ColorTheme {
+        match name {
+            "light" => ColorTheme::Light,
+            "none" => ColorTheme::None,
+            _ => ColorTheme::Default,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            ColorTheme::Default => "default",
+            ColorTheme::Light => "light",
+            ColorTheme::None => "none",
+        }
+    }
+}
+
+/// Implementation used to run Git operations
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitBackend {
+    /// Git command line, run in user shell
+    Shell,
+    /// In-process Git library, without spawning processes
+    Library,
+}
+
+impl GitBackend {
+    fn from_name(name: &str) -> GitBackend {
+        match name {
+            "library" => GitBackend::Library,
+            _ => GitBackend::Shell,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            GitBackend::Shell => "shell",
+            GitBackend::Library => "library",
+        }
+    }
+}
+
+/// Configuration is loaded from, by order of precedence:
+/// - environment variables, e.g. NOTES_EDITOR
+/// - repository configuration file, in .notes/config.toml
+/// - user configuration file, in $XDG_CONFIG_HOME/notes/config.toml
+/// - default values
+///
+/// Notebooks are named repositories, they can only be declared in user configuration or environment.
+/// Repository is taken from NOTES_STORAGE_DIRECTORY, then from default notebook, then ~/.notes is used.
 pub struct Config {
     pub storage_directory: PathBuf,
     pub template_path: PathBuf,
+    /// Directory used by notes to store its own data, e.g. note ids
+    pub data_directory: PathBuf,
+    /// Command used to edit notes. If not set, $EDITOR is used.
+    pub editor: Option<String>,
+    pub pager: String,
+    /// Directories where notes are not searched, in addition to .git and .notes
+    pub ignored_directories: Vec<String>,
+    /// Extensions of note files, without dot. New notes are created with the first one.
+    pub extensions: Vec<String>,
+    pub color_theme: ColorTheme,
+    /// Commit each change of notes
+    pub auto_commit: bool,
+    /// Push repository after each commit
+    pub auto_push: bool,
+    pub git_backend: GitBackend,
+    pub notebooks: BTreeMap<String, PathBuf>,
+    pub default_notebook: Option<String>,
+    pub user_config_path: Option<PathBuf>,
+    /// Sources are kept to build configuration of other notebooks
+    user_file: ConfigFile,
+    env_file: ConfigFile,
 }
 
 impl<'a> Config {
-    pub fn new(env: &'a dyn Env) -> Self {
-        let storage_directory = Config::get_storage_path(env);
-        Config::from_path(&storage_directory)
+    pub fn new(env: &'a dyn Env) -> Result<Self, DefaultError> {
+        let user_config_path = Config::get_user_config_path(env);
+        let mut user_file = match &user_config_path {
+            Some(path) => ConfigFile::load(path)?,
+            None => ConfigFile::default(),
+        };
+        let env_file = Config::load_env(env)?;
+        // $PAGER is used if pager is not configured
+        if let (None, Ok(pager)) = (&user_file.pager, env.get("PAGER")) {
+            user_file.pager = Some(pager);
+        }
+
+        let mut config = Config::from_path(&PathBuf::new());
+        config.user_config_path = user_config_path;
+        config.user_file = user_file;
+        config.env_file = env_file;
+        config.apply_notebooks();
+
+        let storage_directory = match (env.get(NOTES_STORAGE_DIRECTORY), &config.default_notebook) {
+            (Ok(path), _) => PathBuf::from(path),
+            (_, Some(name)) => config.notebook_path(name)?,
+            _ => Config::default_storage_path(),
+        };
+        config.with_storage(&storage_directory)
     }
 
+    /// Return configuration of specified notebook
+    pub fn for_notebook(&self, name: &str) -> Result<Config, DefaultError> {
+        let path = self.notebook_path(name)?;
+        self.with_storage(&path)
+    }
+
+    /// Return configuration of all notebooks, by name
+    pub fn notebook_configs(&self) -> Result<Vec<(String, Config)>, DefaultError> {
+        if self.notebooks.is_empty() {
+            return Err(DefaultError::new(
+                "No notebook configured, e.g: notes config set --global notebooks.work ~/notes/work".to_string(),
+            ));
+        }
+        self.notebooks
+            .keys()
+            .map(|name| self.for_notebook(name).map(|config| (name.clone(), config)))
+            .collect()
+    }
+
+    fn notebook_path(&self, name: &str) -> Result<PathBuf, DefaultError> {
+        match self.notebooks.get(name) {
+            Some(path) => Ok(path.clone()),
+            None => {
+                let names: Vec<&str> = self.notebooks.keys().map(String::as_str).collect();
+                Err(DefaultError::new(format!(
+                    "Unknown notebook '{}', configured notebooks: {}",
+                    name,
+                    names.join(", ")
+                )))
+            }
+        }
+    }
+
+    /// Build configuration of repository at specified path, with same user configuration and environment
+    fn with_storage(&self, storage_directory: &PathBuf) -> Result<Config, DefaultError> {
+        let mut config = Config::from_path(storage_directory);
+        config.user_config_path = self.user_config_path.clone();
+        config.user_file = self.user_file.clone();
+        config.env_file = self.env_file.clone();
+        config.apply_notebooks();
+
+        config.apply(&self.user_file);
+        config.apply(&ConfigFile::load(&config.repository_config_path())?.without_user_keys());
+        config.apply(&self.env_file);
+        Ok(config)
+    }
+
+    /// Load configuration from environment, files and defaults
+    pub fn load() -> Result<Self, DefaultError> {
+        Config::new(&EnvImpl::new())
+    }
+
+    /// Return default configuration for repository at specified path, configuration files are not read
     pub fn from_path(path: &PathBuf) -> Self {
         let storage_directory = path.clone();
         let template_path: PathBuf = [storage_directory.to_str().unwrap(), ".template.md"].iter().collect();
+        let data_directory: PathBuf = [storage_directory.to_str().unwrap(), ".notes"].iter().collect();
 
         Config {
             storage_directory,
             template_path,
+            data_directory,
+            editor: None,
+            pager: "less -R".to_string(),
+            ignored_directories: vec![".idea".to_string()],
+            extensions: vec!["md".to_string()],
+            color_theme: ColorTheme::Default,
+            auto_commit: true,
+            auto_push: false,
+            git_backend: GitBackend::Shell,
+            notebooks: BTreeMap::new(),
+            default_notebook: None,
+            user_config_path: None,
+            user_file: ConfigFile::default(),
+            env_file: ConfigFile::default(),
+        }
+    }
+
+    pub fn repository_config_path(&self) -> PathBuf {
+        self.data_directory.join("config.toml")
+    }
+
+    /// Return text representation of a configuration value
+    pub fn get(&self, key: &str) -> Result<String, DefaultError> {
+        let value = match key {
+            "editor" => self.editor.clone().unwrap_or_else(|| "$EDITOR".to_string()),
+            "pager" => self.pager.clone(),
+            "ignored_directories" => self.ignored_directories.join(","),
+            "extensions" => self.extensions.join(","),
+            "color_theme" => self.color_theme.name().to_string(),
+            "auto_commit" => self.auto_commit.to_string(),
+            "auto_push" => self.auto_push.to_string(),
+            "git_backend" => self.git_backend.name().to_string(),
+            "default_notebook" => self.default_notebook.clone().unwrap_or_default(),
+            "notebooks" => self
+                .notebooks
+                .iter()
+                .map(|(name, path)| format!("{}={}", name, path.to_str().unwrap()))
+                .collect::<Vec<String>>()
+                .join(","),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    fn apply(&mut self, file: &ConfigFile) {
+        if let Some(editor) = &file.editor {
+            self.editor = Some(editor.clone());
+        }
+        if let Some(pager) = &file.pager {
+            self.pager = pager.clone();
+        }
+        if let Some(ignored_directories) = &file.ignored_directories {
+            self.ignored_directories = ignored_directories.clone();
+        }
+        // At least one extension is needed to create notes
+        if let Some(extensions) = file.extensions.as_ref().filter(|e| !e.is_empty()) {
+            self.extensions = extensions.clone();
+        }
+        if let Some(color_theme) = &file.color_theme {
+            self.color_theme = ColorTheme::from_name(color_theme);
+        }
+        if let Some(auto_commit) = file.auto_commit {
+            self.auto_commit = auto_commit;
+        }
+        if let Some(auto_push) = file.auto_push {
+            self.auto_push = auto_push;
+        }
+        if let Some(git_backend) = &file.git_backend {
+            self.git_backend = GitBackend::from_name(git_backend);
         }
     }
 
-    fn get_storage_path(env: &'a dyn Env) -> PathBuf {
-        let env_path = env.get(NOTES_STORAGE_DIRECTORY).map(PathBuf::from);
-        let mut alternative = dirs::home_dir().unwrap_or_else(|| "/tmp".into());
-        alternative.push(".notes");
+    fn apply_notebooks(&mut self) {
+        for file in [&self.user_file, &self.env_file].iter() {
+            if let Some(notebooks) = &file.notebooks {
+                for (name, path) in notebooks {
+                    self.notebooks.insert(name.clone(), expand_home(path));
+                }
+            }
+            if let Some(default_notebook) = &file.default_notebook {
+                self.default_notebook = Some(default_notebook.clone());
+            }
+        }
+    }
 
-        match env_path {
-            Ok(repository_path) => repository_path,
-            _ => alternative,
+    /// Each key can be overridden with an environment variable, e.g. NOTES_AUTO_PUSH=false
+    fn load_env(env: &'a dyn Env) -> Result<ConfigFile, DefaultError> {
+        let mut file = ConfigFile::default();
+        for key in CONFIG_KEYS.iter() {
+            let var_name = format!("NOTES_{}", key.to_uppercase());
+            if let Ok(value) = env.get(&var_name) {
+                file.set(key, &value)
+                    .map_err(|e| DefaultError::new(format!("Invalid variable {}: {}", var_name, e.message)))?;
+            }
         }
+        Ok(file)
+    }
+
+    fn default_storage_path() -> PathBuf {
+        let mut path = dirs::home_dir().unwrap_or_else(|| "/tmp".into());
+        path.push(".notes");
+        path
+    }
+
+    fn get_user_config_path(env: &'a dyn Env) -> Option<PathBuf> {
+        let config_home = match env.get(XDG_CONFIG_HOME) {
+            Ok(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => dirs::home_dir().map(|home| home.join(".config")),
+        };
+        config_home.map(|dir| dir.join("notes").join("config.toml"))
     }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config::new(&EnvImpl::new())
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(relative), Some(home)) => home.join(relative),
+        _ => PathBuf::from(path),
     }
 }
 
@@ -48,10 +306,21 @@ impl Default for Config {
 mod tests {
     use super::*;
     use crate::env::MockEnv;
+    use crate::test_env::new_test_root;
     use mockall::predicate::*;
     use std::env::VarError;
+    use std::fs;
     use std::path::PathBuf;
 
+    fn mock_env(vars: Vec<(&'static str, String)>) -> MockEnv {
+        let mut mock_env = MockEnv::new();
+        mock_env.expect_get().returning(move |key| match vars.iter().find(|(k, _)| *k == key) {
+            Some((_, value)) => Ok(value.clone()),
+            None => Err(VarError::NotPresent),
+        });
+        mock_env
+    }
+
     #[test]
     fn should_return_path_from_env_var() {
         let mut mock_env = MockEnv::new();
@@ -60,8 +329,9 @@ mod tests {
             .with(eq(NOTES_STORAGE_DIRECTORY))
             .times(1)
             .returning(|_| Ok("/path/to/dir".to_string()));
+        mock_env.expect_get().returning(|_| Err(VarError::NotPresent));
 
-        let config = Config::new(&mock_env);
+        let config = Config::new(&mock_env).unwrap();
         assert_eq!(config.storage_directory, PathBuf::from("/path/to/dir"))
     }
 
@@ -73,8 +343,13 @@ mod tests {
             .with(eq(NOTES_STORAGE_DIRECTORY))
             .times(1)
             .returning(|_| Err(VarError::NotPresent));
+        mock_env
+            .expect_get()
+            .with(eq(XDG_CONFIG_HOME))
+            .returning(|_| Ok(new_test_root().to_str().unwrap().to_string()));
+        mock_env.expect_get().returning(|_| Err(VarError::NotPresent));
 
-        let config = Config::new(&mock_env);
+        let config = Config::new(&mock_env).unwrap();
         let path_str: String = config.storage_directory.to_str().unwrap().to_string();
         assert!(
             path_str.starts_with("/home") || path_str.starts_with("/root"),
@@ -82,4 +357,135 @@ mod tests {
         );
         assert!(path_str.ends_with(".notes"), format!("Path must end with .notes {}", path_str));
     }
+
+    #[test]
+    fn should_merge_files_and_env() {
+        let test_root = new_test_root();
+        let storage = test_root.join("repo");
+        let config_home = test_root.join("config");
+        fs::create_dir_all(storage.join(".notes")).unwrap();
+        fs::create_dir_all(config_home.join("notes")).unwrap();
+        fs::write(config_home.join("notes/config.toml"), "editor = \"vim\"\npager = \"more\"\nauto_push = true\n").unwrap();
+        fs::write(storage.join(".notes/config.toml"), "color_theme = \"light\"\nextensions = [\"md\", \"txt\"]\n").unwrap();
+
+        let env = mock_env(vec![
+            (NOTES_STORAGE_DIRECTORY, storage.to_str().unwrap().to_string()),
+            (XDG_CONFIG_HOME, config_home.to_str().unwrap().to_string()),
+            ("NOTES_AUTO_PUSH", "false".to_string()),
+            ("NOTES_COLOR_THEME", "none".to_string()),
+        ]);
+        let config = Config::new(&env).unwrap();
+
+        assert_eq!(config.user_config_path, Some(config_home.join("notes/config.toml")));
+        assert_eq!(config.editor, Some("vim".to_string()));
+        assert_eq!(config.pager, "more");
+        assert_eq!(config.extensions, vec!["md".to_string(), "txt".to_string()]);
+        assert!(!config.auto_push);
+        assert_eq!(config.color_theme, ColorTheme::None);
+        assert!(config.auto_commit);
+    }
+
+    #[test]
+    fn should_ignore_commands_in_repository_config() {
+        let test_root = new_test_root();
+        fs::create_dir_all(test_root.join(".notes")).unwrap();
+        fs::write(
+            test_root.join(".notes/config.toml"),
+            "editor = \"touch pwned; true\"\npager = \"touch pwned; true\"\ngit_backend = \"library\"\nauto_push = true\n",
+        )
+        .unwrap();
+
+        let env = mock_env(vec![
+            (NOTES_STORAGE_DIRECTORY, test_root.to_str().unwrap().to_string()),
+            (XDG_CONFIG_HOME, new_test_root().to_str().unwrap().to_string()),
+        ]);
+        let config = Config::new(&env).unwrap();
+        assert_eq!(config.editor, None);
+        assert_eq!(config.pager, "less -R");
+        assert_eq!(config.git_backend, GitBackend::Shell);
+        assert!(config.auto_push);
+    }
+
+    #[test]
+    fn should_use_pager_from_env() {
+        let test_root = new_test_root();
+        let env = mock_env(vec![
+            (NOTES_STORAGE_DIRECTORY, test_root.to_str().unwrap().to_string()),
+            (XDG_CONFIG_HOME, test_root.to_str().unwrap().to_string()),
+            ("PAGER", "more".to_string()),
+        ]);
+        assert_eq!(Config::new(&env).unwrap().pager, "more");
+
+        // Configured pager has precedence
+        fs::create_dir_all(test_root.join("notes")).unwrap();
+        fs::write(test_root.join("notes/config.toml"), "pager = \"most\"\n").unwrap();
+        assert_eq!(Config::new(&env).unwrap().pager, "most");
+    }
+
+    #[test]
+    fn should_fail_with_invalid_env() {
+        let env = mock_env(vec![
+            (NOTES_STORAGE_DIRECTORY, new_test_root().to_str().unwrap().to_string()),
+            (XDG_CONFIG_HOME, new_test_root().to_str().unwrap().to_string()),
+            ("NOTES_AUTO_COMMIT", "sometimes".to_string()),
+        ]);
+        let result = Config::new(&env);
+        assert!(result.err().unwrap().message.starts_with("Invalid variable NOTES_AUTO_COMMIT"));
+    }
+
+    #[test]
+    fn should_use_default_notebook() {
+        let test_root = new_test_root();
+        let config_home = test_root.join("config");
+        fs::create_dir_all(config_home.join("notes")).unwrap();
+        fs::write(
+            config_home.join("notes/config.toml"),
+            format!(
+                "default_notebook = \"work\"\n[notebooks]\nwork = \"{root}/work\"\npersonal = \"{root}/personal\"\n",
+                root = test_root.to_str().unwrap()
+            ),
+        )
+        .unwrap();
+        fs::create_dir_all(test_root.join("personal/.notes")).unwrap();
+        fs::write(test_root.join("personal/.notes/config.toml"), "auto_push = true\n").unwrap();
+
+        let env = mock_env(vec![(XDG_CONFIG_HOME, config_home.to_str().unwrap().to_string())]);
+        let config = Config::new(&env).unwrap();
+        assert_eq!(config.storage_directory, test_root.join("work"));
+        assert!(!config.auto_push);
+
+        let personal = config.for_notebook("personal").unwrap();
+        assert_eq!(personal.storage_directory, test_root.join("personal"));
+        assert!(personal.auto_push);
+        assert_eq!(personal.notebooks.len(), 2);
+
+        let names: Vec<String> = config.notebook_configs().unwrap().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["personal", "work"]);
+
+        let result = config.for_notebook("other");
+        assert_eq!(result.err().unwrap().message, "Unknown notebook 'other', configured notebooks: personal, work");
+    }
+
+    #[test]
+    fn notebook_configs_should_fail_without_notebooks() {
+        let config = Config::from_path(&PathBuf::from("/path/to/dir"));
+        assert!(config.notebook_configs().is_err());
+    }
+
+    #[test]
+    fn expand_home_directory() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home("~/notes"), home.join("notes"));
+        assert_eq!(expand_home("/tmp/notes"), PathBuf::from("/tmp/notes"));
+    }
+
+    #[test]
+    fn get() {
+        let config = Config::from_path(&PathBuf::from("/path/to/dir"));
+        assert_eq!(config.get("editor").unwrap(), "$EDITOR");
+        assert_eq!(config.get("ignored_directories").unwrap(), ".idea");
+        assert_eq!(config.get("auto_commit").unwrap(), "true");
+        assert_eq!(config.get("git_backend").unwrap(), "shell");
+        assert!(config.get("unknown").is_err());
+    }
 }
@@ -0,0 +1,264 @@
+extern crate toml;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::default_error::DefaultError;
+
+/// Keys that can be used in configuration files, or as environment variables prefixed by NOTES_
+pub const CONFIG_KEYS: [&str; 10] = [
+    "editor",
+    "pager",
+    "ignored_directories",
+    "extensions",
+    "color_theme",
+    "auto_commit",
+    "auto_push",
+    "git_backend",
+    "default_notebook",
+    "notebooks",
+];
+
+/// Keys that can only be set in user configuration. Editor, pager and Git backend run commands,
+/// a shared repository must not be able to choose them.
+pub const USER_CONFIG_KEYS: [&str; 5] = ["editor", "pager", "git_backend", "default_notebook", "notebooks"];
+
+pub const COLOR_THEMES: [&str; 3] = ["default", "light", "none"];
+
+pub const GIT_BACKENDS: [&str; 2] = ["shell", "library"];
+
+/// Content of a TOML configuration file. All values are optional, missing values are taken
+/// from another file or from defaults. E.g:
+///
+/// ```toml
+/// editor = "vim"
+/// ignored_directories = [".idea", "drafts"]
+/// auto_push = true
+///
+/// [notebooks]
+/// work = "~/notes/work"
+/// personal = "~/notes/personal"
+/// ```
+#[derive(Debug, Eq, PartialEq, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub editor: Option<String>,
+    pub pager: Option<String>,
+    pub ignored_directories: Option<Vec<String>>,
+    pub extensions: Option<Vec<String>>,
+    pub color_theme: Option<String>,
+    pub auto_commit: Option<bool>,
+    pub auto_push: Option<bool>,
+    pub git_backend: Option<String>,
+    pub default_notebook: Option<String>,
+    /// Notebook names and paths. Must be the last field, TOML tables are written after values.
+    pub notebooks: Option<BTreeMap<String, String>>,
+}
+
+impl ConfigFile {
+    /// Load configuration from specified file. If file does not exist, an empty configuration is returned.
+    pub fn load(path: &Path) -> Result<ConfigFile, DefaultError> {
+        if !path.exists() {
+            return Ok(ConfigFile::default());
+        }
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| DefaultError::new(format!("Invalid configuration file {}: {}", path.to_str().unwrap(), e)))
+    }
+
+    /// Return configuration without values of USER_CONFIG_KEYS, which are ignored in repository configuration
+    pub fn without_user_keys(&self) -> ConfigFile {
+        ConfigFile {
+            editor: None,
+            pager: None,
+            git_backend: None,
+            default_notebook: None,
+            notebooks: None,
+            ..self.clone()
+        }
+    }
+
+    /// Write configuration on disk. Comments of an existing file are not kept.
+    pub fn save(&self, path: &Path) -> Result<(), DefaultError> {
+        let content = toml::to_string(self).map_err(|e| DefaultError::new(format!("Cannot serialize configuration: {}", e)))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Set a value from its text representation. Lists are comma separated, notebooks are
+    /// written 'name=path'. A single notebook can be set with key 'notebooks.<name>'.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), DefaultError> {
+        if let Some(name) = key.strip_prefix("notebooks.") {
+            let path = non_empty(key, value)?;
+            self.notebooks.get_or_insert_with(BTreeMap::new).insert(name.to_string(), path);
+            return Ok(());
+        }
+
+        match key {
+            "editor" => self.editor = Some(non_empty(key, value)?),
+            "pager" => self.pager = Some(non_empty(key, value)?),
+            "ignored_directories" => self.ignored_directories = Some(parse_list(value)),
+            "extensions" => {
+                let extensions: Vec<String> = parse_list(value).iter().map(|e| e.trim_start_matches('.').to_string()).collect();
+                if extensions.is_empty() {
+                    return Err(DefaultError::new("At least one extension is required".to_string()));
+                }
+                self.extensions = Some(extensions)
+            }
+            "color_theme" => {
+                if !COLOR_THEMES.contains(&value) {
+                    return Err(DefaultError::new(format!(
+                        "Invalid color theme '{}', use one of: {}",
+                        value,
+                        COLOR_THEMES.join(", ")
+                    )));
+                }
+                self.color_theme = Some(value.to_string())
+            }
+            "auto_commit" => self.auto_commit = Some(parse_bool(key, value)?),
+            "auto_push" => self.auto_push = Some(parse_bool(key, value)?),
+            "git_backend" => {
+                if !GIT_BACKENDS.contains(&value) {
+                    return Err(DefaultError::new(format!(
+                        "Invalid Git backend '{}', use one of: {}",
+                        value,
+                        GIT_BACKENDS.join(", ")
+                    )));
+                }
+                self.git_backend = Some(value.to_string())
+            }
+            "default_notebook" => self.default_notebook = Some(non_empty(key, value)?),
+            "notebooks" => self.notebooks = Some(parse_notebooks(value)?),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(())
+    }
+}
+
+pub fn unknown_key(key: &str) -> DefaultError {
+    DefaultError::new(format!("Unknown configuration key '{}', use one of: {}", key, CONFIG_KEYS.join(", ")))
+}
+
+fn non_empty(key: &str, value: &str) -> Result<String, DefaultError> {
+    match value.trim() {
+        "" => Err(DefaultError::new(format!("Value of {} cannot be empty", key))),
+        v => Ok(v.to_string()),
+    }
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect()
+}
+
+fn parse_notebooks(value: &str) -> Result<BTreeMap<String, String>, DefaultError> {
+    let mut notebooks = BTreeMap::new();
+    for item in parse_list(value) {
+        let mut parts = item.splitn(2, '=');
+        match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+            (Some(name), Some(path)) if !name.is_empty() && !path.is_empty() => {
+                notebooks.insert(name.to_string(), path.to_string());
+            }
+            _ => return Err(DefaultError::new(format!("Invalid notebook '{}', use name=path", item))),
+        }
+    }
+    Ok(notebooks)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, DefaultError> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(DefaultError::new(format!("Value of {} must be true or false, not '{}'", key, value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::new_test_root;
+
+    #[test]
+    pub fn load() {
+        let path = new_test_root().join("config.toml");
+        fs::write(&path, "editor = \"vim\"\nextensions = [\"md\", \"markdown\"]\nauto_push = true\n").unwrap();
+
+        let config_file = ConfigFile::load(&path).unwrap();
+        assert_eq!(
+            config_file,
+            ConfigFile {
+                editor: Some("vim".to_string()),
+                extensions: Some(vec!["md".to_string(), "markdown".to_string()]),
+                auto_push: Some(true),
+                ..ConfigFile::default()
+            }
+        );
+    }
+
+    #[test]
+    pub fn load_should_fail() {
+        let path = new_test_root().join("config.toml");
+        fs::write(&path, "editr = \"vim\"\n").unwrap();
+        assert!(ConfigFile::load(&path).unwrap_err().message.starts_with("Invalid configuration file"));
+    }
+
+    #[test]
+    pub fn load_missing_file() {
+        let path = new_test_root().join("config.toml");
+        assert_eq!(ConfigFile::load(&path).unwrap(), ConfigFile::default());
+    }
+
+    #[test]
+    pub fn set_and_save() {
+        let path = new_test_root().join("notes/config.toml");
+        let mut config_file = ConfigFile::default();
+        config_file.set("pager", "less -R").unwrap();
+        config_file.set("ignored_directories", ".idea, drafts").unwrap();
+        config_file.set("extensions", ".md,txt").unwrap();
+        config_file.set("auto_commit", "false").unwrap();
+        config_file.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap();
+        assert_eq!(loaded, config_file);
+        assert_eq!(loaded.ignored_directories, Some(vec![".idea".to_string(), "drafts".to_string()]));
+        assert_eq!(loaded.extensions, Some(vec!["md".to_string(), "txt".to_string()]));
+        assert_eq!(loaded.auto_commit, Some(false));
+    }
+
+    #[test]
+    pub fn set_notebooks() {
+        let path = new_test_root().join("config.toml");
+        let mut config_file = ConfigFile::default();
+        config_file.set("notebooks", "work=~/work, personal=/tmp/personal").unwrap();
+        config_file.set("notebooks.other", "/tmp/other").unwrap();
+        config_file.set("default_notebook", "work").unwrap();
+        config_file.set("editor", "vim").unwrap();
+        config_file.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap();
+        assert_eq!(loaded, config_file);
+        let notebooks = loaded.notebooks.unwrap();
+        assert_eq!(notebooks.keys().cloned().collect::<Vec<String>>(), vec!["other", "personal", "work"]);
+        assert_eq!(notebooks.get("work"), Some(&"~/work".to_string()));
+        assert!(ConfigFile::default().set("notebooks", "work").is_err());
+    }
+
+    #[test]
+    pub fn set_should_fail() {
+        let mut config_file = ConfigFile::default();
+        assert!(config_file
+            .set("unknown", "value")
+            .unwrap_err()
+            .message
+            .starts_with("Unknown configuration key"));
+        assert!(config_file.set("auto_push", "maybe").is_err());
+        assert!(config_file.set("color_theme", "pink").is_err());
+        assert!(config_file.set("git_backend", "svn").is_err());
+        assert!(config_file.set("extensions", " , ").is_err());
+        assert!(config_file.set("editor", "").is_err());
+    }
+}
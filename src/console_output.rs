@@ -1,6 +1,6 @@
 use crate::shell::CommandOutput;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ConsoleOutput {
     pub stdout: String,
     pub stderr: String,
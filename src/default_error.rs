@@ -29,6 +29,24 @@ impl From<std::io::Error> for DefaultError {
     }
 }
 
+impl From<crossterm::ErrorKind> for DefaultError {
+    fn from(error: crossterm::ErrorKind) -> DefaultError {
+        DefaultError {
+            message: error.to_string(),
+            backtrace: error.backtrace().map(|bt| format!("{:?}", bt)),
+        }
+    }
+}
+
+impl From<git2::Error> for DefaultError {
+    fn from(error: git2::Error) -> DefaultError {
+        DefaultError {
+            message: error.message().to_string(),
+            backtrace: error.backtrace().map(|bt| format!("{:?}", bt)),
+        }
+    }
+}
+
 impl From<std::num::ParseIntError> for DefaultError {
     fn from(error: std::num::ParseIntError) -> DefaultError {
         DefaultError {
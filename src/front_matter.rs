@@ -0,0 +1,266 @@
+extern crate serde_yaml;
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::Value;
+
+use crate::default_error::DefaultError;
+
+const DELIMITER: &str = "---";
+const END_DELIMITERS: [&str; 2] = ["---", "..."];
+
+/// Optional YAML block at the beginning of a note, e.g:
+///
+/// ```yaml
+/// ---
+/// title: SSH tips
+/// tags: [ssh, network]
+/// ---
+/// ```
+#[derive(Debug, Eq, PartialEq, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    #[serde(deserialize_with = "scalar")]
+    pub title: Option<String>,
+    #[serde(deserialize_with = "scalar_or_sequence")]
+    pub tags: Vec<String>,
+    #[serde(deserialize_with = "scalar")]
+    pub created: Option<String>,
+    #[serde(deserialize_with = "scalar")]
+    pub updated: Option<String>,
+    #[serde(deserialize_with = "scalar_or_sequence")]
+    pub aliases: Vec<String>,
+    /// All other keys of front matter
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+    /// Number of note lines used by front matter, delimiters included
+    #[serde(skip)]
+    pub line_count: usize,
+}
+
+impl FrontMatter {
+    /// Parse front matter from the first lines of a note. Return None if note does not start
+    /// with a front matter block, or if block is not valid YAML. In this last case block is
+    /// considered as regular note content.
+    pub fn parse(lines: &[String]) -> Option<FrontMatter> {
+        if lines.first().map(|l| l.trim_end()) != Some(DELIMITER) {
+            return None;
+        }
+
+        let end = lines.iter().skip(1).position(|l| END_DELIMITERS.contains(&l.trim_end()))? + 1;
+        let yaml = lines[1..end].join("\n");
+
+        let parsed = if yaml.trim().is_empty() {
+            Some(FrontMatter::default())
+        } else {
+            serde_yaml::from_str::<FrontMatter>(&yaml).ok()
+        };
+
+        parsed.map(|front_matter| FrontMatter {
+            line_count: end + 1,
+            ..front_matter
+        })
+    }
+
+    /// Return note lines with tags of front matter replaced by specified tags. Other lines of
+    /// front matter are kept as is. If note does not have a front matter, one is created. If note
+    /// starts with a front matter that cannot be parsed, an error is returned.
+    pub fn replace_tags(lines: &[String], tags: &[String]) -> Result<Vec<String>, DefaultError> {
+        let tags_line = format!("tags: [{}]", tags.iter().map(|t| yaml_tag(t)).collect::<Vec<String>>().join(", "));
+        let line_count = match FrontMatter::parse(lines) {
+            Some(front_matter) => front_matter.line_count,
+            None if lines.first().map(|l| l.trim_end()) == Some(DELIMITER) => {
+                return Err(DefaultError::new("Front matter of note is not valid, edit note to fix it".to_string()));
+            }
+            None => {
+                let mut result = vec![DELIMITER.to_string(), tags_line, DELIMITER.to_string()];
+                result.extend(lines.iter().cloned());
+                return Ok(result);
+            }
+        };
+
+        let yaml_lines = &lines[1..line_count - 1];
+        let tags_start = yaml_lines.iter().position(|l| l.starts_with("tags:"));
+        // Tags can be written as a block sequence, on several lines
+        let tags_end = tags_start.map(|start| {
+            yaml_lines
+                .iter()
+                .skip(start + 1)
+                .position(|l| !(l.starts_with(' ') || l.starts_with('\t') || l.starts_with('-')))
+                .map_or(yaml_lines.len(), |position| start + 1 + position)
+        });
+
+        let mut new_yaml: Vec<String> = yaml_lines.to_vec();
+        match (tags_start, tags_end) {
+            (Some(start), Some(end)) if tags.is_empty() => {
+                new_yaml.drain(start..end);
+            }
+            (Some(start), Some(end)) => {
+                new_yaml.splice(start..end, vec![tags_line]);
+            }
+            _ if tags.is_empty() => (),
+            _ => new_yaml.push(tags_line),
+        };
+
+        let mut result = vec![lines[0].clone()];
+        result.extend(new_yaml);
+        result.extend(lines.iter().skip(line_count - 1).cloned());
+        Ok(result)
+    }
+}
+
+/// Tags are written unquoted when YAML reads them back as the same string, e.g. 'ssh', otherwise
+/// they are written as double quoted strings, e.g. '"a: b"'
+fn yaml_tag(tag: &str) -> String {
+    let plain = serde_yaml::from_str::<Vec<Value>>(&format!("[{}]", tag)).ok();
+    match plain {
+        Some(values) if values == [Value::String(tag.to_string())] => tag.to_string(),
+        // A JSON string is a valid YAML double quoted string
+        _ => serde_json::to_string(tag).unwrap_or_else(|_| tag.to_string()),
+    }
+}
+
+/// Return scalar value as string, e.g. 'title: 2021' gives "2021"
+fn scalar_to_string(value: Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(Value::deserialize(deserializer).ok().and_then(scalar_to_string))
+}
+
+/// Accept a single value as well as a sequence, e.g. 'tags: ssh' or 'tags: [ssh, 2020]'
+fn scalar_or_sequence<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let values = match Value::deserialize(deserializer)? {
+        Value::Sequence(values) => values,
+        value => vec![value],
+    };
+    Ok(values.into_iter().filter_map(scalar_to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(content: &str) -> Vec<String> {
+        content.split('\n').map(String::from).collect()
+    }
+
+    #[test]
+    pub fn parse() {
+        let content = "---\ntitle: SSH tips\ntags: [ssh, network]\ncreated: 2020-09-01\naliases:\n  - ssh\nauthor: me\n---\n# SSH\n";
+        let front_matter = FrontMatter::parse(&lines(content)).unwrap();
+        assert_eq!(front_matter.title, Some("SSH tips".to_string()));
+        assert_eq!(front_matter.tags, vec!["ssh".to_string(), "network".to_string()]);
+        assert_eq!(front_matter.created, Some("2020-09-01".to_string()));
+        assert_eq!(front_matter.updated, None);
+        assert_eq!(front_matter.aliases, vec!["ssh".to_string()]);
+        assert_eq!(front_matter.extra.get("author"), Some(&serde_yaml::Value::String("me".to_string())));
+        assert_eq!(front_matter.line_count, 8);
+    }
+
+    #[test]
+    pub fn parse_empty_block() {
+        let front_matter = FrontMatter::parse(&lines("---\n---\n# Title")).unwrap();
+        assert_eq!(
+            front_matter,
+            FrontMatter {
+                line_count: 2,
+                ..FrontMatter::default()
+            }
+        );
+    }
+
+    #[test]
+    pub fn parse_scalar_tags() {
+        let front_matter = FrontMatter::parse(&lines("---\ntitle: SSH tips\ntags: ssh\naliases: tips\n---\n# SSH")).unwrap();
+        assert_eq!(front_matter.title, Some("SSH tips".to_string()));
+        assert_eq!(front_matter.tags, vec!["ssh".to_string()]);
+        assert_eq!(front_matter.aliases, vec!["tips".to_string()]);
+        assert_eq!(front_matter.line_count, 5);
+    }
+
+    #[test]
+    pub fn parse_non_string_values() {
+        let content = "---\ntitle: 2021\ntags: [ssh, 2020, true]\ncreated: 2020-09-01 10:00:00\nupdated: 20200901\n---\n# SSH";
+        let front_matter = FrontMatter::parse(&lines(content)).unwrap();
+        assert_eq!(front_matter.title, Some("2021".to_string()));
+        assert_eq!(front_matter.tags, vec!["ssh".to_string(), "2020".to_string(), "true".to_string()]);
+        assert_eq!(front_matter.created, Some("2020-09-01 10:00:00".to_string()));
+        assert_eq!(front_matter.updated, Some("20200901".to_string()));
+    }
+
+    #[test]
+    pub fn parse_empty_values() {
+        let front_matter = FrontMatter::parse(&lines("---\ntitle:\ntags:\n---\n# SSH")).unwrap();
+        assert_eq!(front_matter.title, None);
+        assert!(front_matter.tags.is_empty());
+    }
+
+    #[test]
+    pub fn parse_without_front_matter() {
+        assert!(FrontMatter::parse(&lines("# Title\n---\nContent")).is_none());
+        assert!(FrontMatter::parse(&lines("---\ntitle: not closed")).is_none());
+    }
+
+    #[test]
+    pub fn replace_tags() {
+        let content = "---\ntitle: Title\ntags:\n  - a\n  - b\nauthor: me\n---\n# Title";
+        let actual = FrontMatter::replace_tags(&lines(content), &["c".to_string(), "d".to_string()]).unwrap();
+        assert_eq!(actual.join("\n"), "---\ntitle: Title\ntags: [c, d]\nauthor: me\n---\n# Title");
+    }
+
+    #[test]
+    pub fn replace_tags_should_quote_special_tags() {
+        let tags: Vec<String> = ["c++", "a: b", "x, y", "#hash", "[list]", "'quoted", "\"double\"", "2020", "true", "ssh"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let content = "---\ntags: [\"a: b\"]\n---\n# Title";
+        let actual = FrontMatter::replace_tags(&lines(content), &tags).unwrap();
+        assert_eq!(
+            actual[1],
+            "tags: [c++, \"a: b\", \"x, y\", \"#hash\", \"[list]\", \"'quoted\", \"\\\"double\\\"\", \"2020\", \"true\", ssh]"
+        );
+        assert_eq!(FrontMatter::parse(&actual).unwrap().tags, tags);
+    }
+
+    #[test]
+    pub fn replace_tags_should_add_tags_line() {
+        let content = "---\ntitle: Title\n---\n# Title";
+        let actual = FrontMatter::replace_tags(&lines(content), &["c".to_string()]).unwrap();
+        assert_eq!(actual.join("\n"), "---\ntitle: Title\ntags: [c]\n---\n# Title");
+    }
+
+    #[test]
+    pub fn replace_tags_should_remove_tags_line() {
+        let content = "---\ntags: [a]\ntitle: Title\n---\n# Title";
+        let actual = FrontMatter::replace_tags(&lines(content), &[]).unwrap();
+        assert_eq!(actual.join("\n"), "---\ntitle: Title\n---\n# Title");
+    }
+
+    #[test]
+    pub fn replace_tags_should_create_front_matter() {
+        let actual = FrontMatter::replace_tags(&lines("# Title\nContent"), &["a".to_string()]).unwrap();
+        assert_eq!(actual.join("\n"), "---\ntags: [a]\n---\n# Title\nContent");
+    }
+
+    #[test]
+    pub fn replace_tags_should_fail_on_invalid_front_matter() {
+        let actual = FrontMatter::replace_tags(&lines("---\ntags: [a, b\n---\n# Title"), &["a".to_string()]);
+        assert!(actual.is_err());
+        let actual = FrontMatter::replace_tags(&lines("---\ntitle: not closed\n# Title"), &["a".to_string()]);
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    pub fn parse_invalid_yaml() {
+        assert!(FrontMatter::parse(&lines("---\ntags: [a, b\n---\n# Title")).is_none());
+    }
+}
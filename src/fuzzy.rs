@@ -0,0 +1,115 @@
+use std::cmp::min_by_key;
+
+/// Approximate occurrence of a word in a text
+#[derive(Debug, Eq, PartialEq)]
+pub struct FuzzyMatch {
+    /// Byte offsets of matched part of text
+    pub start: usize,
+    pub end: usize,
+    /// Number of edits needed to transform matched part of text into searched word
+    pub distance: usize,
+}
+
+/// Number of typos accepted for a word. Short words must match exactly.
+pub fn max_distance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Find the part of text closest to specified word, ignoring case. Insertions, deletions,
+/// substitutions and transpositions of characters count as one typo each.
+/// Return None if the best match needs more than max_distance(word) typos.
+pub fn find(word: &str, text: &str) -> Option<FuzzyMatch> {
+    let needle: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+    let haystack: Vec<(usize, char)> = text
+        .char_indices()
+        .filter_map(|(offset, c)| c.to_lowercase().next().map(|lower| (offset, lower)))
+        .collect();
+    if needle.is_empty() {
+        return None;
+    }
+
+    // distances[i][j]: minimal typos between needle[..i] and a part of text ending at haystack[j - 1],
+    // starts[i][j]: position of the first character of this part of text.
+    let (rows, columns) = (needle.len() + 1, haystack.len() + 1);
+    let by_distance = |candidate: &(usize, usize)| candidate.0;
+    let mut distances = vec![vec![0; columns]; rows];
+    let mut starts = vec![vec![0; columns]; rows];
+    starts[0] = (0..columns).collect();
+    for i in 1..rows {
+        distances[i][0] = i;
+        for j in 1..columns {
+            let cost = if needle[i - 1] == haystack[j - 1].1 { 0 } else { 1 };
+            // With equal distances, the first candidate is kept
+            let substitution = (distances[i - 1][j - 1] + cost, starts[i - 1][j - 1]);
+            let deletion = (distances[i - 1][j] + 1, starts[i - 1][j]);
+            let insertion = (distances[i][j - 1] + 1, starts[i][j - 1]);
+            let mut best = min_by_key(min_by_key(substitution, deletion, by_distance), insertion, by_distance);
+            if i > 1 && j > 1 && needle[i - 1] == haystack[j - 2].1 && needle[i - 2] == haystack[j - 1].1 {
+                best = min_by_key(best, (distances[i - 2][j - 2] + 1, starts[i - 2][j - 2]), by_distance);
+            }
+            let (distance, start) = best;
+            distances[i][j] = distance;
+            starts[i][j] = start;
+        }
+    }
+
+    let last = rows - 1;
+    // With equal distances, matches ending at the end of a word are preferred
+    let ends_word = |j: usize| !matches!(haystack.get(j), Some((_, c)) if c.is_alphanumeric());
+    let (end, distance) = (1..columns)
+        .map(|j| (j, distances[last][j]))
+        .min_by_key(|(j, distance)| (*distance, !ends_word(*j)))?;
+    let start = starts[last][end];
+    if distance > max_distance(word) || start >= end {
+        return None;
+    }
+
+    let byte_end = haystack.get(end).map_or(text.len(), |(offset, _)| *offset);
+    Some(FuzzyMatch {
+        start: haystack[start].0,
+        end: byte_end,
+        distance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched<'a>(word: &str, text: &'a str) -> Option<(&'a str, usize)> {
+        find(word, text).map(|m| (&text[m.start..m.end], m.distance))
+    }
+
+    #[test]
+    pub fn max_distance_depends_on_length() {
+        assert_eq!(max_distance("ssh"), 0);
+        assert_eq!(max_distance("rsync"), 1);
+        assert_eq!(max_distance("kubernetes"), 2);
+    }
+
+    #[test]
+    pub fn find_exact_match() {
+        assert_eq!(matched("rsync", "About Rsync tips"), Some(("Rsync", 0)));
+        assert_eq!(matched("ssh", "Use openssh"), Some(("ssh", 0)));
+    }
+
+    #[test]
+    pub fn find_with_typos() {
+        assert_eq!(matched("rsnyc", "About Rsync tips"), Some(("Rsync", 1)));
+        assert_eq!(matched("rsinc", "About Rsync tips"), Some(("Rsync", 1)));
+        assert_eq!(matched("kubernets", "Deploy on Kubernetes"), Some(("Kubernetes", 1)));
+        assert_eq!(matched("élan", "Un bel Élan"), Some(("Élan", 0)));
+    }
+
+    #[test]
+    pub fn find_nothing() {
+        assert_eq!(matched("ssh", "Use sh"), None);
+        assert_eq!(matched("rsync", "About rust"), None);
+        assert_eq!(matched("", "About rust"), None);
+        assert_eq!(matched("rsync", ""), None);
+    }
+}
@@ -1,18 +1,72 @@
+use std::path::{Path, PathBuf};
+
 #[cfg(test)]
 use mockall::automock;
+use serde::Serialize;
 
 use crate::console_output::ConsoleOutput;
 use crate::default_error::DefaultError;
 use crate::note::Note;
+use crate::search_match::{HistoryLine, HistoryMatch};
 use crate::shell::Shell;
 
 #[cfg_attr(test, automock)]
 pub trait Git {
     fn init(&self) -> Result<ConsoleOutput, DefaultError>;
     fn commit(&self, note: &Note, message: &str) -> Result<ConsoleOutput, DefaultError>;
+    /// Commit all changes of specified paths, including deletions
+    fn commit_paths(&self, paths: &[PathBuf], message: &str) -> Result<ConsoleOutput, DefaultError>;
+    fn move_path(&self, from: &Path, to: &Path) -> Result<ConsoleOutput, DefaultError>;
     fn has_changed(&self, note: &Note) -> bool;
     fn push(&self) -> Result<ConsoleOutput, DefaultError>;
     fn pull(&self) -> Result<ConsoleOutput, DefaultError>;
+    /// True if repository has uncommitted changes, untracked files included
+    fn has_pending_changes(&self) -> Result<bool, DefaultError>;
+    /// Commit all changes of repository, untracked files included
+    fn commit_all(&self, message: &str) -> Result<ConsoleOutput, DefaultError>;
+    /// Pull and rebase local commits. If rebase stops on conflicts, it is left in progress so that
+    /// user can resolve them.
+    fn pull_rebase(&self) -> Result<RebaseOutcome, DefaultError>;
+    /// Commits that add or remove lines matching an extended regular expression, ignoring case,
+    /// newest first. All lines changed in matching files are returned, without matches.
+    fn log_matching(&self, pattern: &str) -> Result<Vec<HistoryMatch>, DefaultError>;
+    /// Commits that changed a file, newest first, following renames. Path is relative to repository root.
+    fn log_for_path(&self, path: &Path) -> Result<Vec<LogEntry>, DefaultError>;
+    /// Unified diff of paths, or of whole repository if paths are empty. Range is a revision compared
+    /// to working tree, e.g. HEAD for uncommitted changes, or two revisions 'A..B'.
+    /// If word is true, changed words are marked with [-removed-] and {+added+}.
+    fn diff(&self, range: &str, paths: &[PathBuf], word: bool) -> Result<String, DefaultError>;
+    /// Files that are neither tracked nor ignored, among paths or in whole repository if paths are empty.
+    /// Paths are relative to repository root.
+    fn untracked_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, DefaultError>;
+    /// Diff of an untracked file against an empty file, formatted as diff()
+    fn diff_untracked(&self, path: &Path, word: bool) -> Result<String, DefaultError>;
+    /// Changes that would bring files of working tree back to their content at revision, renames
+    /// are detected. Paths are relative to repository root.
+    fn diff_to_revision(&self, revision: &str, paths: &[PathBuf]) -> Result<String, DefaultError>;
+    /// Content of a file at specified revision, path is relative to repository root
+    fn show_file(&self, revision: &str, path: &Path) -> Result<String, DefaultError>;
+}
+
+/// Commit that changed a file, see Git::log_for_path()
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    pub commit: String,
+    pub date: String,
+    pub author: String,
+    pub message: String,
+    /// Path of file in this commit, it differs from current path if file was moved since
+    #[serde(serialize_with = "crate::json_format::lossy_path")]
+    pub path: PathBuf,
+    pub added: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RebaseOutcome {
+    Done(ConsoleOutput),
+    /// Paths of conflicted files, relative to repository root
+    Conflicts(Vec<PathBuf>),
 }
 
 pub struct GitImpl<'a> {
@@ -34,17 +88,38 @@ impl<'a> Git for GitImpl<'a> {
     }
 
     fn commit(&self, note: &Note, message: &str) -> Result<ConsoleOutput, DefaultError> {
-        let path = &note.path.to_str().unwrap();
+        let path = quote(note.path.to_str().unwrap());
+        let mut out = ConsoleOutput::empty();
+        out.append_command_output(self.shell.execute_in_repo(format!("git add {}", path).as_str())?);
+        out.append_command_output(self.shell.execute_in_repo(format!("git commit -m {} {}", quote(message), path).as_str())?);
+        Ok(out)
+    }
+
+    fn commit_paths(&self, paths: &[PathBuf], message: &str) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
-        out.append_command_output(self.shell.execute_in_repo(format!("git add '{}'", path).as_str())?);
-        out.append_command_output(self.shell.execute_in_repo(format!("git commit -m '{}' '{}'", message, path).as_str())?);
+        // Removed or moved paths are already known by git, and would make 'git add' fail
+        let existing_paths: Vec<PathBuf> = paths.iter().filter(|p| p.exists()).cloned().collect();
+        if !existing_paths.is_empty() {
+            let command = format!("git add -A -- {}", quote_paths(&existing_paths));
+            out.append_command_output(self.shell.execute_in_repo(command.as_str())?);
+        }
+        let command = format!("git commit -m {} -- {}", quote(message), quote_paths(paths));
+        out.append_command_output(self.shell.execute_in_repo(command.as_str())?);
         Ok(out)
     }
 
+    fn move_path(&self, from: &Path, to: &Path) -> Result<ConsoleOutput, DefaultError> {
+        let command = format!("git mv {} {}", quote(from.to_str().unwrap()), quote(to.to_str().unwrap()));
+        match self.shell.execute_in_repo(command.as_str()) {
+            Ok(o) => Ok(o.into()),
+            Err(e) => Err(e),
+        }
+    }
+
     fn has_changed(&self, note: &Note) -> bool {
-        let path = note.path.to_str().unwrap();
+        let path = quote(note.path.to_str().unwrap());
         self.shell
-            .execute_in_repo(format!("git add '{p}' && git diff --exit-code HEAD '{p}' > /dev/null", p = path).as_str())
+            .execute_in_repo(format!("git add {p} && git diff --exit-code HEAD {p} > /dev/null", p = path).as_str())
             .is_err()
     }
 
@@ -61,13 +136,234 @@ impl<'a> Git for GitImpl<'a> {
             Err(e) => Err(e),
         }
     }
+
+    fn has_pending_changes(&self) -> Result<bool, DefaultError> {
+        let output = self.shell.execute_in_repo("git status --porcelain")?;
+        Ok(!output.stdout.trim().is_empty())
+    }
+
+    fn commit_all(&self, message: &str) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = ConsoleOutput::empty();
+        out.append_command_output(self.shell.execute_in_repo("git add -A")?);
+        out.append_command_output(self.shell.execute_in_repo(format!("git commit -m {}", quote(message)).as_str())?);
+        Ok(out)
+    }
+
+    fn pull_rebase(&self) -> Result<RebaseOutcome, DefaultError> {
+        let error = match self.shell.execute_interactive_in_repo("git pull --rebase") {
+            Ok(o) => return Ok(RebaseOutcome::Done(o.into())),
+            Err(e) => e,
+        };
+        // Pull can also fail because of network or authentication
+        let conflicts = self.shell.execute_in_repo("git -c core.quotePath=false diff --name-only --diff-filter=U")?;
+        let paths: Vec<PathBuf> = conflicts.stdout.lines().filter(|l| !l.is_empty()).map(PathBuf::from).collect();
+        match paths.is_empty() {
+            true => Err(error),
+            false => Ok(RebaseOutcome::Conflicts(paths)),
+        }
+    }
+
+    fn log_matching(&self, pattern: &str) -> Result<Vec<HistoryMatch>, DefaultError> {
+        let command = format!(
+            "git -c core.quotePath=false log -G {} --regexp-ignore-case --extended-regexp --date=short \
+             --format='commit %h %ad' --patch --unified=0 --no-color --no-ext-diff --no-renames",
+            quote(pattern)
+        );
+        let output = self.shell.execute_in_repo(command.as_str())?;
+        Ok(parse_log(&output.stdout))
+    }
+
+    fn log_for_path(&self, path: &Path) -> Result<Vec<LogEntry>, DefaultError> {
+        let command = format!(
+            "git -c core.quotePath=false log --follow --date=short --format='commit%x09%h%x09%ad%x09%an%x09%s' --numstat -- {}",
+            quote(path.to_str().unwrap())
+        );
+        let output = self.shell.execute_in_repo(command.as_str())?;
+        Ok(parse_numstat_log(&output.stdout))
+    }
+
+    fn diff(&self, range: &str, paths: &[PathBuf], word: bool) -> Result<String, DefaultError> {
+        let mut command = format!("git -c core.quotePath=false diff --no-color --no-ext-diff -M {}", quote(range));
+        if word {
+            command.push_str(" --word-diff=plain");
+        }
+        if !paths.is_empty() {
+            command.push_str(&format!(" -- {}", quote_paths(paths)));
+        }
+        Ok(self.shell.execute_in_repo(command.as_str())?.stdout)
+    }
+
+    fn untracked_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, DefaultError> {
+        let mut command = "git -c core.quotePath=false ls-files --others --exclude-standard".to_string();
+        if !paths.is_empty() {
+            command.push_str(&format!(" -- {}", quote_paths(paths)));
+        }
+        let output = self.shell.execute_in_repo(command.as_str())?;
+        Ok(output.stdout.lines().filter(|l| !l.is_empty()).map(PathBuf::from).collect())
+    }
+
+    fn diff_untracked(&self, path: &Path, word: bool) -> Result<String, DefaultError> {
+        let mut command = "git -c core.quotePath=false diff --no-color --no-ext-diff --no-index".to_string();
+        if word {
+            command.push_str(" --word-diff=plain");
+        }
+        // Exit code is 1 when files differ
+        command.push_str(&format!(" -- /dev/null {} || test $? -eq 1", quote(path.to_str().unwrap())));
+        Ok(self.shell.execute_in_repo(command.as_str())?.stdout)
+    }
+
+    fn diff_to_revision(&self, revision: &str, paths: &[PathBuf]) -> Result<String, DefaultError> {
+        let paths: Vec<String> = paths.iter().map(|p| quote(p.to_str().unwrap())).collect();
+        let command = format!(
+            "git -c core.quotePath=false diff --no-color --no-ext-diff -R -M {} -- {}",
+            quote(revision),
+            paths.join(" ")
+        );
+        Ok(self.shell.execute_in_repo(command.as_str())?.stdout)
+    }
+
+    fn show_file(&self, revision: &str, path: &Path) -> Result<String, DefaultError> {
+        let command = format!("git show {}", quote(&format!("{}:{}", revision, path.to_str().unwrap())));
+        Ok(self.shell.execute_in_repo(command.as_str())?.stdout)
+    }
+}
+
+fn quote_paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|p| quote(p.to_str().unwrap())).collect::<Vec<String>>().join(" ")
+}
+
+/// Check that a pattern only uses syntax that Git extended regular expressions and Rust regular
+/// expressions read the same way: escaped letters like \\d, groups like (?i) and lazy quantifiers
+/// are refused. Characters inside brackets are not checked.
+pub fn check_extended_regexp(pattern: &str) -> Result<(), DefaultError> {
+    let unsupported = |syntax: &str| {
+        Err(DefaultError::new(format!(
+            "Pattern '{}' uses '{}', which is not supported by git: use POSIX extended regular expressions, e.g. [0-9] instead of \\d",
+            pattern, syntax
+        )))
+    };
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut in_brackets = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let next = chars.get(i + 1).cloned();
+        match (chars[i], next) {
+            (']', _) if in_brackets => in_brackets = false,
+            (_, _) if in_brackets => (),
+            ('[', _) => {
+                in_brackets = true;
+                // A closing bracket right after opening one is a character of the class
+                if next == Some('^') {
+                    i += 1;
+                }
+                if chars.get(i + 1) == Some(&']') {
+                    i += 1;
+                }
+            }
+            ('\\', Some(c)) if c.is_alphanumeric() => return unsupported(&format!("\\{}", c)),
+            ('\\', _) => i += 1,
+            ('(', Some('?')) => return unsupported("(?"),
+            ('*', Some('?')) | ('+', Some('?')) | ('?', Some('?')) | ('}', Some('?')) => return unsupported(&format!("{}?", chars[i])),
+            _ => (),
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Quote an argument for shell, single quotes included
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Parse output of 'git log --numstat' with one line header per commit, fields separated by tabs:
+/// 'commit <hash> <date> <author> <subject>'
+fn parse_numstat_log(output: &str) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = vec![];
+    for line in output.lines() {
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        match (fields.as_slice(), entries.last_mut()) {
+            (["commit", commit, date, author, message], _) => entries.push(LogEntry {
+                commit: commit.to_string(),
+                date: date.to_string(),
+                author: author.to_string(),
+                message: message.to_string(),
+                path: PathBuf::new(),
+                added: 0,
+                removed: 0,
+            }),
+            // Binary files have '-' instead of line counts
+            ([added, removed, path], Some(entry)) => {
+                entry.added = added.parse().unwrap_or(0);
+                entry.removed = removed.parse().unwrap_or(0);
+                entry.path = PathBuf::from(renamed_path(path));
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Numstat shows renames as 'old => new', or with common parts outside of braces: 'dir/{old => new}.md'
+fn renamed_path(path: &str) -> String {
+    let (start, end) = match (path.find('{'), path.rfind('}')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return path.rsplit(" => ").next().unwrap_or(path).to_string(),
+    };
+    let new = path[start + 1..end].rsplit(" => ").next().unwrap_or("");
+    // Empty parts leave a double slash, e.g. 'dir/{ => sub}/a.md'
+    let path = format!("{}{}{}", &path[..start], new, &path[end + 1..]).replace("//", "/");
+    path.trim_start_matches('/').to_string()
+}
+
+/// Parse output of 'git log --patch' with one line header per commit: 'commit <hash> <date>'
+fn parse_log(output: &str) -> Vec<HistoryMatch> {
+    let mut matches: Vec<HistoryMatch> = vec![];
+    let (mut commit, mut date) = ("", "");
+    let mut in_hunk = false;
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("commit ") {
+            let mut parts = header.split(' ');
+            commit = parts.next().unwrap_or("");
+            date = parts.next().unwrap_or("");
+            in_hunk = false;
+        } else if line.starts_with("diff --git ") {
+            in_hunk = false;
+            matches.push(HistoryMatch {
+                commit: commit.to_string(),
+                date: date.to_string(),
+                path: PathBuf::new(),
+                revision: commit.to_string(),
+                lines: vec![],
+            });
+        } else if line.starts_with("@@") {
+            in_hunk = true;
+        } else if let Some(current) = matches.last_mut() {
+            match (in_hunk, line.chars().next()) {
+                (true, Some(sign)) if sign == '+' || sign == '-' => current.lines.push(HistoryLine {
+                    added: sign == '+',
+                    content: line[1..].to_string(),
+                    matches: vec![],
+                }),
+                // Deleted files only have a path before changes, file names with spaces end with a tab
+                (false, _) => {
+                    if let Some(path) = line.strip_prefix("--- a/").or_else(|| line.strip_prefix("+++ b/")) {
+                        current.path = PathBuf::from(path.trim_end_matches('\t'));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    matches
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::shell::{CommandOutput, MockShell};
-    use std::path::PathBuf;
+    use crate::test_env::new_test_root;
+    use std::fs;
 
     fn test_note() -> Note {
         Note::from(0, PathBuf::from("/repository/test.md"), "# Title\nContent\n".to_string()).unwrap()
@@ -110,6 +406,74 @@ mod tests {
         git.commit(&note, "message").unwrap();
     }
 
+    #[test]
+    fn commit_paths() {
+        let existing = new_test_root().join("a.md");
+        fs::write(&existing, "# A").unwrap();
+        let paths = vec![existing.clone(), PathBuf::from("/repository/b.md")];
+
+        let mut shell_mock = MockShell::new();
+        let exp_command = format!("git add -A -- '{}'", existing.to_str().unwrap());
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(move |c| c == exp_command)
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let exp_command = format!("git commit -m 'message' -- '{}' '/repository/b.md'", existing.to_str().unwrap());
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(move |c| c == exp_command)
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        git.commit_paths(&paths, "message").unwrap();
+    }
+
+    #[test]
+    fn move_path() {
+        let mut shell_mock = MockShell::new();
+        let exp_command = "git mv '/repository/a.md' '/repository/b/a.md'";
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(move |c| c == exp_command)
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        git.move_path(&PathBuf::from("/repository/a.md"), &PathBuf::from("/repository/b/a.md")).unwrap();
+    }
+
+    #[test]
+    fn move_path_should_escape_quotes() {
+        let mut shell_mock = MockShell::new();
+        let exp_command = "git mv '/repository/a.md' '/repository/bob'\\''s/a.md'";
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(move |c| c == exp_command)
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        git.move_path(&PathBuf::from("/repository/a.md"), &PathBuf::from("/repository/bob's/a.md"))
+            .unwrap();
+    }
+
+    #[test]
+    fn commit_paths_should_escape_quotes() {
+        let mut shell_mock = MockShell::new();
+        let exp_command = "git commit -m 'Move 1 notes to bob'\\''s/' -- '/repository/bob'\\''s/a.md'";
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(move |c| c == exp_command)
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        git.commit_paths(&[PathBuf::from("/repository/bob's/a.md")], "Move 1 notes to bob's/").unwrap();
+    }
+
     #[test]
     fn has_changed() {
         let note = test_note();
@@ -153,4 +517,285 @@ mod tests {
         let git = GitImpl::new(&shell_mock);
         git.pull().unwrap();
     }
+
+    #[test]
+    fn has_pending_changes() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git status --porcelain")
+            .returning(|_| Ok(CommandOutput::new(0, "?? a.md\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        assert!(git.has_pending_changes().unwrap());
+    }
+
+    #[test]
+    fn commit_all() {
+        let mut shell_mock = MockShell::new();
+        let mut sequence = mockall::Sequence::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git add -A")
+            .in_sequence(&mut sequence)
+            .returning(|_| Ok(CommandOutput::default()));
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git commit -m 'message'")
+            .in_sequence(&mut sequence)
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        git.commit_all("message").unwrap();
+    }
+
+    #[test]
+    fn pull_rebase() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_interactive_in_repo()
+            .times(1)
+            .withf(|c| c == "git pull --rebase")
+            .returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(git.pull_rebase().unwrap(), RebaseOutcome::Done(ConsoleOutput::empty()));
+    }
+
+    #[test]
+    fn pull_rebase_with_conflicts() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_interactive_in_repo()
+            .times(1)
+            .returning(|_| Err(DefaultError::new("Command failed".to_string())));
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git -c core.quotePath=false diff --name-only --diff-filter=U")
+            .returning(|_| Ok(CommandOutput::new(0, "a.md\ndir/b c.md\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(
+            git.pull_rebase().unwrap(),
+            RebaseOutcome::Conflicts(vec![PathBuf::from("a.md"), PathBuf::from("dir/b c.md")])
+        );
+    }
+
+    #[test]
+    fn pull_rebase_should_fail() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_interactive_in_repo()
+            .times(1)
+            .returning(|_| Err(DefaultError::new("Could not read from remote repository".to_string())));
+        shell_mock.expect_execute_in_repo().times(1).returning(|_| Ok(CommandOutput::default()));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(git.pull_rebase().unwrap_err().message, "Could not read from remote repository");
+    }
+
+    #[test]
+    fn log_for_path() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c.starts_with("git -c core.quotePath=false log --follow") && c.ends_with("--numstat -- 'd/it'\\''s.md'"))
+            .returning(|_| {
+                let stdout = "commit\t00c1325\t2020-10-18\tJane Doe\tUpdate note it's.md\n\n\
+                              3\t1\td/it's.md\n\
+                              commit\t58d921d\t2020-10-17\tJohn Doe\tMove\tnotes\n\n\
+                              0\t0\t{a => d}/it's.md\n\
+                              commit\t2a5c82f\t2020-10-16\tJohn Doe\tCreate note\n\n\
+                              5\t0\ta/it's.md\n";
+                Ok(CommandOutput::new(0, stdout.to_string(), "".to_string()))
+            });
+
+        let git = GitImpl::new(&shell_mock);
+        let entries = git.log_for_path(&PathBuf::from("d/it's.md")).unwrap();
+        let summary: Vec<(&str, &str, &str, PathBuf, usize, usize)> = entries
+            .iter()
+            .map(|e| (e.commit.as_str(), e.author.as_str(), e.message.as_str(), e.path.clone(), e.added, e.removed))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("00c1325", "Jane Doe", "Update note it's.md", PathBuf::from("d/it's.md"), 3, 1),
+                ("58d921d", "John Doe", "Move\tnotes", PathBuf::from("d/it's.md"), 0, 0),
+                ("2a5c82f", "John Doe", "Create note", PathBuf::from("a/it's.md"), 5, 0),
+            ]
+        );
+        assert_eq!(entries[0].date, "2020-10-18");
+    }
+
+    #[test]
+    fn diff() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git -c core.quotePath=false diff --no-color --no-ext-diff -M 'HEAD'")
+            .returning(|_| Ok(CommandOutput::default()));
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git -c core.quotePath=false diff --no-color --no-ext-diff -M 'HEAD~2..HEAD' --word-diff=plain -- 'a.md' 'b.md'")
+            .returning(|_| Ok(CommandOutput::new(0, "diff --git a/a.md b/a.md\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(git.diff("HEAD", &[], false).unwrap(), "");
+        let diff = git.diff("HEAD~2..HEAD", &[PathBuf::from("a.md"), PathBuf::from("b.md")], true).unwrap();
+        assert_eq!(diff, "diff --git a/a.md b/a.md\n");
+    }
+
+    #[test]
+    fn untracked_files() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git -c core.quotePath=false ls-files --others --exclude-standard -- 'a.md'")
+            .returning(|_| Ok(CommandOutput::new(0, "a.md\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(git.untracked_files(&[PathBuf::from("a.md")]).unwrap(), vec![PathBuf::from("a.md")]);
+    }
+
+    #[test]
+    fn diff_untracked() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git -c core.quotePath=false diff --no-color --no-ext-diff --no-index --word-diff=plain -- /dev/null 'new.md' || test $? -eq 1")
+            .returning(|_| Ok(CommandOutput::new(0, "diff --git a/new.md b/new.md\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(git.diff_untracked(Path::new("new.md"), true).unwrap(), "diff --git a/new.md b/new.md\n");
+    }
+
+    #[test]
+    fn diff_to_revision() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c == "git -c core.quotePath=false diff --no-color --no-ext-diff -R -M 'HEAD~2' -- 'a.md' 'd/b c.md'")
+            .returning(|_| Ok(CommandOutput::new(0, "diff --git a/a.md b/a.md\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        let diff = git.diff_to_revision("HEAD~2", &[PathBuf::from("a.md"), PathBuf::from("d/b c.md")]).unwrap();
+        assert_eq!(diff, "diff --git a/a.md b/a.md\n");
+    }
+
+    #[test]
+    fn renamed_path() {
+        assert_eq!(super::renamed_path("a.md"), "a.md");
+        assert_eq!(super::renamed_path("a.md => b/a.md"), "b/a.md");
+        assert_eq!(super::renamed_path("dir/{a => b}.md"), "dir/b.md");
+        assert_eq!(super::renamed_path("dir/{ => sub}/a.md"), "dir/sub/a.md");
+        assert_eq!(super::renamed_path("{sub => }/a.md"), "a.md");
+    }
+
+    #[test]
+    fn check_extended_regexp() {
+        for pattern in &["rsync -avz", "ssh|scp", "[0-9]+ (items?|files)", "a\\.b", "[\\d?]", "[]?]", "x{2,3}"] {
+            assert!(super::check_extended_regexp(pattern).is_ok(), "{}", pattern);
+        }
+        for pattern in &["\\d+", "(?i)ssh", "a.*?b", "a+?", "x{2}?", "\\bword"] {
+            assert!(super::check_extended_regexp(pattern).is_err(), "{}", pattern);
+        }
+        let message = super::check_extended_regexp("port \\d+").unwrap_err().message;
+        assert!(message.starts_with("Pattern 'port \\d+' uses '\\d', which is not supported by git"));
+    }
+
+    #[test]
+    fn log_matching() {
+        let mut shell_mock = MockShell::new();
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(|c| c.starts_with("git -c core.quotePath=false log -G 'it'\\''s' --regexp-ignore-case"))
+            .returning(|_| {
+                let stdout = "commit 00c1325 2020-10-18\n\n\
+                              diff --git a/d/a b.md b/d/a b.md\n\
+                              index 8294050..9937f27 100644\n\
+                              --- a/d/a b.md\t\n\
+                              +++ b/d/a b.md\t\n\
+                              @@ -2 +2 @@\n\
+                              -It's SSH\n\
+                              +bye\n\
+                              commit 58d921d 2020-10-17\n\n\
+                              diff --git a/c.md b/c.md\n\
+                              new file mode 100644\n\
+                              --- /dev/null\n\
+                              +++ b/c.md\n\
+                              @@ -0,0 +1,2 @@\n\
+                              +# T\n\
+                              +--- it's ssh\n";
+                Ok(CommandOutput::new(0, stdout.to_string(), "".to_string()))
+            });
+
+        let git = GitImpl::new(&shell_mock);
+        let matches = git.log_matching("it's").unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                HistoryMatch {
+                    commit: "00c1325".to_string(),
+                    date: "2020-10-18".to_string(),
+                    path: PathBuf::from("d/a b.md"),
+                    revision: "00c1325".to_string(),
+                    lines: vec![
+                        HistoryLine {
+                            added: false,
+                            content: "It's SSH".to_string(),
+                            matches: vec![]
+                        },
+                        HistoryLine {
+                            added: true,
+                            content: "bye".to_string(),
+                            matches: vec![]
+                        },
+                    ]
+                },
+                HistoryMatch {
+                    commit: "58d921d".to_string(),
+                    date: "2020-10-17".to_string(),
+                    path: PathBuf::from("c.md"),
+                    revision: "58d921d".to_string(),
+                    lines: vec![
+                        HistoryLine {
+                            added: true,
+                            content: "# T".to_string(),
+                            matches: vec![]
+                        },
+                        HistoryLine {
+                            added: true,
+                            content: "--- it's ssh".to_string(),
+                            matches: vec![]
+                        },
+                    ]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn show_file() {
+        let mut shell_mock = MockShell::new();
+        let exp_command = "git show '00c1325^:d/a b.md'";
+        shell_mock
+            .expect_execute_in_repo()
+            .times(1)
+            .withf(move |c| c == exp_command)
+            .returning(|_| Ok(CommandOutput::new(0, "# Title\n".to_string(), "".to_string())));
+
+        let git = GitImpl::new(&shell_mock);
+        assert_eq!(git.show_file("00c1325^", Path::new("d/a b.md")).unwrap(), "# Title\n");
+    }
 }
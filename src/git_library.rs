@@ -0,0 +1,453 @@
+extern crate git2;
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use self::git2::build::CheckoutBuilder;
+use self::git2::{Commit, Cred, CredentialType, ErrorCode, FetchOptions, Index, IndexAddOption, Oid, PushOptions, RemoteCallbacks, Repository, StatusOptions};
+use crate::config::Config;
+use crate::console_output::ConsoleOutput;
+use crate::default_error::DefaultError;
+use crate::git::{Git, GitImpl, LogEntry, RebaseOutcome};
+use crate::note::Note;
+use crate::search_match::HistoryMatch;
+use crate::shell::Shell;
+
+/// Git implementation backed by libgit2: no process is spawned, and paths or messages are never
+/// interpreted by a shell.
+///
+/// History logs, diffs and rebase still use Git command line, a stopped rebase must be continued
+/// by user with the same tool.
+pub struct GitLibraryImpl<'a> {
+    storage_directory: PathBuf,
+    command_line: GitImpl<'a>,
+}
+
+/// Remote branch tracked by current branch
+struct Upstream {
+    branch: String,
+    remote: String,
+    /// Name of branch on remote, e.g. refs/heads/master
+    merge: String,
+    /// Remote tracking branch, e.g. refs/remotes/origin/master
+    tracking: String,
+}
+
+impl<'a> GitLibraryImpl<'a> {
+    pub fn new(config: &Config, shell: &'a dyn Shell) -> GitLibraryImpl<'a> {
+        GitLibraryImpl {
+            storage_directory: config.storage_directory.clone(),
+            command_line: GitImpl::new(shell),
+        }
+    }
+
+    fn open(&self) -> Result<Repository, DefaultError> {
+        Ok(Repository::open(&self.storage_directory)?)
+    }
+
+    /// Path relative to repository root, as used in index
+    fn relative_path(&self, path: &Path) -> Result<String, DefaultError> {
+        let relative = match path.is_absolute() {
+            true => path
+                .strip_prefix(&self.storage_directory)
+                .map_err(|_| DefaultError::new(format!("Path {} is not in repository", path.to_str().unwrap())))?,
+            false => path,
+        };
+        Ok(relative.to_str().unwrap().to_string())
+    }
+
+    /// Stage specified paths, then commit them only, like 'git commit -- <paths>'. Other staged
+    /// changes are kept in index.
+    fn commit_only(&self, paths: &[PathBuf], message: &str) -> Result<ConsoleOutput, DefaultError> {
+        let repository = self.open()?;
+        let relative_paths = paths.iter().map(|p| self.relative_path(p)).collect::<Result<Vec<String>, DefaultError>>()?;
+        let mut index = repository.index()?;
+        index.add_all(relative_paths.iter(), IndexAddOption::DEFAULT, None)?;
+        index.update_all(relative_paths.iter(), None)?;
+        index.write()?;
+
+        // Tree of commit is tree of HEAD, with staged versions of specified paths
+        let parent = head_commit(&repository)?;
+        let mut tree_index = Index::new()?;
+        if let Some(parent) = &parent {
+            tree_index.read_tree(&parent.tree()?)?;
+        }
+        tree_index.remove_all(relative_paths.iter(), None)?;
+        for entry in index.iter() {
+            let path = String::from_utf8_lossy(&entry.path).to_string();
+            if relative_paths.iter().any(|p| path == *p || path.starts_with(&format!("{}/", p))) {
+                tree_index.add(&entry)?;
+            }
+        }
+        let tree = tree_index.write_tree_to(&repository)?;
+        commit_tree(&repository, tree, parent, message)
+    }
+}
+
+impl<'a> Git for GitLibraryImpl<'a> {
+    fn init(&self) -> Result<ConsoleOutput, DefaultError> {
+        Repository::init(&self.storage_directory)?;
+        Ok(ConsoleOutput::from_stdout(&format!(
+            "Initialized Git repository in {}\n",
+            self.storage_directory.to_str().unwrap()
+        )))
+    }
+
+    fn commit(&self, note: &Note, message: &str) -> Result<ConsoleOutput, DefaultError> {
+        self.commit_only(std::slice::from_ref(&note.path), message)
+    }
+
+    fn commit_paths(&self, paths: &[PathBuf], message: &str) -> Result<ConsoleOutput, DefaultError> {
+        self.commit_only(paths, message)
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<ConsoleOutput, DefaultError> {
+        let repository = self.open()?;
+        let (relative_from, relative_to) = (self.relative_path(from)?, self.relative_path(to)?);
+        std::fs::rename(from, to)?;
+        let mut index = repository.index()?;
+        index.remove_all(&[relative_from], None)?;
+        index.add_all(&[relative_to], IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        Ok(ConsoleOutput::empty())
+    }
+
+    fn has_changed(&self, note: &Note) -> bool {
+        let changed = || -> Result<bool, DefaultError> {
+            let repository = self.open()?;
+            let path = PathBuf::from(self.relative_path(&note.path)?);
+            let mut index = repository.index()?;
+            index.add_path(&path)?;
+            index.write()?;
+            let staged = index.get_path(&path, 0).map(|e| e.id);
+            let committed = match head_commit(&repository)? {
+                Some(commit) => commit.tree()?.get_path(&path).ok().map(|e| e.id()),
+                None => None,
+            };
+            Ok(staged != committed)
+        };
+        changed().unwrap_or(true)
+    }
+
+    fn push(&self) -> Result<ConsoleOutput, DefaultError> {
+        let repository = self.open()?;
+        let upstream = upstream(&repository)?;
+        let config = repository.config()?;
+        let rejection: RefCell<Option<String>> = RefCell::new(None);
+        {
+            let mut callbacks = remote_callbacks(&config);
+            callbacks.push_update_reference(|_, status| {
+                *rejection.borrow_mut() = status.map(String::from);
+                Ok(())
+            });
+            let refspec = format!("refs/heads/{}:{}", upstream.branch, upstream.merge);
+            let mut remote = repository.find_remote(&upstream.remote)?;
+            remote.push(&[refspec.as_str()], Some(PushOptions::new().remote_callbacks(callbacks)))?;
+        }
+        if let Some(reason) = rejection.into_inner() {
+            return Err(DefaultError::new(format!(
+                "Push of {} to {} was rejected: {}",
+                upstream.branch, upstream.remote, reason
+            )));
+        }
+        Ok(ConsoleOutput::from_stdout(&format!("Pushed {} to {}\n", upstream.branch, upstream.remote)))
+    }
+
+    /// Fetch remote changes, then fast-forward current branch. Diverged branches are not merged.
+    fn pull(&self) -> Result<ConsoleOutput, DefaultError> {
+        let repository = self.open()?;
+        let upstream = upstream(&repository)?;
+        let config = repository.config()?;
+        let mut remote = repository.find_remote(&upstream.remote)?;
+        remote.fetch::<&str>(&[], Some(FetchOptions::new().remote_callbacks(remote_callbacks(&config))), None)?;
+
+        let tracking = repository.find_reference(&upstream.tracking)?;
+        let fetched = repository.reference_to_annotated_commit(&tracking)?;
+        let (analysis, _) = repository.merge_analysis(&[&fetched])?;
+        if analysis.is_up_to_date() {
+            return Ok(ConsoleOutput::from_stdout("Already up to date.\n"));
+        }
+        if !analysis.is_fast_forward() {
+            return Err(DefaultError::new(format!(
+                "Local and remote changes of {} diverged, use 'notes sync' to rebase local changes",
+                upstream.branch
+            )));
+        }
+
+        // Local changes are kept, checkout fails if they conflict with remote changes
+        let target = repository.find_object(fetched.id(), None)?;
+        repository.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))?;
+        repository
+            .head()?
+            .set_target(fetched.id(), &format!("pull: Fast-forward to {}", upstream.tracking))?;
+        Ok(ConsoleOutput::from_stdout(&format!(
+            "Fast-forwarded {} to {}\n",
+            upstream.branch,
+            short_id(fetched.id())
+        )))
+    }
+
+    fn has_pending_changes(&self) -> Result<bool, DefaultError> {
+        let repository = self.open()?;
+        let statuses = repository.statuses(Some(StatusOptions::new().include_untracked(true).recurse_untracked_dirs(true)))?;
+        Ok(!statuses.is_empty())
+    }
+
+    fn commit_all(&self, message: &str) -> Result<ConsoleOutput, DefaultError> {
+        let repository = self.open()?;
+        let mut index = repository.index()?;
+        index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+        index.update_all(["*"], None)?;
+        index.write()?;
+        let tree = index.write_tree()?;
+        let parent = head_commit(&repository)?;
+        commit_tree(&repository, tree, parent, message)
+    }
+
+    fn pull_rebase(&self) -> Result<RebaseOutcome, DefaultError> {
+        self.command_line.pull_rebase()
+    }
+
+    fn log_matching(&self, pattern: &str) -> Result<Vec<HistoryMatch>, DefaultError> {
+        self.command_line.log_matching(pattern)
+    }
+
+    fn log_for_path(&self, path: &Path) -> Result<Vec<LogEntry>, DefaultError> {
+        self.command_line.log_for_path(path)
+    }
+
+    fn diff(&self, range: &str, paths: &[PathBuf], word: bool) -> Result<String, DefaultError> {
+        self.command_line.diff(range, paths, word)
+    }
+
+    fn untracked_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, DefaultError> {
+        self.command_line.untracked_files(paths)
+    }
+
+    fn diff_untracked(&self, path: &Path, word: bool) -> Result<String, DefaultError> {
+        self.command_line.diff_untracked(path, word)
+    }
+
+    fn diff_to_revision(&self, revision: &str, paths: &[PathBuf]) -> Result<String, DefaultError> {
+        self.command_line.diff_to_revision(revision, paths)
+    }
+
+    fn show_file(&self, revision: &str, path: &Path) -> Result<String, DefaultError> {
+        let repository = self.open()?;
+        let blob = repository
+            .revparse_single(&format!("{}:{}", revision, path.to_str().unwrap()))?
+            .peel_to_blob()?;
+        Ok(String::from_utf8_lossy(blob.content()).to_string())
+    }
+}
+
+/// Last commit of current branch, None if branch has no commit yet
+fn head_commit(repository: &Repository) -> Result<Option<Commit<'_>>, DefaultError> {
+    match repository.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit()?)),
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Commit tree on current branch. Like Git command line, commit fails if nothing changed.
+fn commit_tree(repository: &Repository, tree: Oid, parent: Option<Commit>, message: &str) -> Result<ConsoleOutput, DefaultError> {
+    if let Some(parent) = &parent {
+        if parent.tree_id() == tree {
+            return Err(DefaultError::new("Nothing to commit".to_string()));
+        }
+    }
+    let tree = repository.find_tree(tree)?;
+    let signature = repository.signature()?;
+    let parents: Vec<&Commit> = parent.iter().collect();
+    let id = repository.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+    let head = repository.head()?;
+    Ok(ConsoleOutput::from_stdout(&format!(
+        "[{} {}] {}\n",
+        head.shorthand().unwrap_or("HEAD"),
+        short_id(id),
+        message
+    )))
+}
+
+fn upstream(repository: &Repository) -> Result<Upstream, DefaultError> {
+    let head = repository.head()?;
+    let refname = head.name().unwrap_or("").to_string();
+    let branch = head.shorthand().unwrap_or("").to_string();
+    let remote = repository.branch_upstream_remote(&refname).ok().and_then(|r| r.as_str().map(String::from));
+    let tracking = repository.branch_upstream_name(&refname).ok().and_then(|r| r.as_str().map(String::from));
+    let merge = repository.config()?.get_string(&format!("branch.{}.merge", branch)).ok();
+    match (remote, merge, tracking) {
+        (Some(remote), Some(merge), Some(tracking)) => Ok(Upstream {
+            branch,
+            remote,
+            merge,
+            tracking,
+        }),
+        _ => Err(DefaultError::new(format!(
+            "No upstream branch configured for {}, e.g: git push -u origin {}",
+            branch, branch
+        ))),
+    }
+}
+
+/// Credentials are taken from SSH agent, or from Git credential helpers
+fn remote_callbacks(config: &git2::Config) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut attempts = 0;
+    callbacks.credentials(move |url, username, allowed| {
+        // Callback is called again as long as authentication fails
+        attempts += 1;
+        if attempts > 3 {
+            return Err(git2::Error::from_str("Authentication failed"));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            Cred::credential_helper(config, url, username)
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+fn short_id(id: Oid) -> String {
+    id.to_string().chars().take(7).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::shell::ShellImpl;
+    use crate::test_env::{new_sample_repo, new_test_root};
+
+    fn note(config: &Config, path: &str, content: &str) -> Note {
+        let path = config.storage_directory.join(path);
+        fs::write(&path, content).unwrap();
+        Note::from(0, path, content.to_string()).unwrap()
+    }
+
+    #[test]
+    pub fn init() {
+        let config = Config::from_path(&new_test_root().join("repo"));
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+
+        git.init().unwrap();
+        assert!(config.storage_directory.join(".git").is_dir());
+    }
+
+    #[test]
+    pub fn commit_and_has_changed() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+
+        let note = note(&config, "b.md", "# It's a note\n");
+        assert!(git.has_changed(&note));
+        git.commit(&note, "Update note 'b.md'").unwrap();
+        assert!(!git.has_changed(&note));
+
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, "Update note 'b.md'\n");
+        let status = shell.execute_in_repo("git status --porcelain").unwrap();
+        assert_eq!(status.stdout, "");
+        assert!(git.commit(&note, "Update note 'b.md'").is_err());
+    }
+
+    #[test]
+    pub fn commit_paths_should_only_commit_paths() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+
+        note(&config, "a.md", "# A\n");
+        note(&config, "c d.md", "# C\n");
+        fs::remove_dir_all(config.storage_directory.join("a/a")).unwrap();
+        let paths = vec![config.storage_directory.join("c d.md"), config.storage_directory.join("a/a")];
+        git.commit_paths(&paths, "Update notes").unwrap();
+
+        let status = shell.execute_in_repo("git status --porcelain").unwrap();
+        assert_eq!(status.stdout, " M a.md\n");
+        let files = shell.execute_in_repo("git -c core.quotePath=false show --name-status --format= HEAD").unwrap();
+        assert_eq!(files.stdout, "D\ta/a/aaa.md\nD\ta/a/aab.md\nA\tc d.md\n");
+    }
+
+    #[test]
+    pub fn move_path() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+
+        let (from, to) = (config.storage_directory.join("a.md"), config.storage_directory.join("b/a.md"));
+        git.move_path(&from, &to).unwrap();
+        git.commit_paths(&[from.clone(), to.clone()], "Move note").unwrap();
+
+        assert!(!from.exists());
+        let files = shell.execute_in_repo("git show --name-status --format= HEAD").unwrap();
+        assert_eq!(files.stdout, "R100\ta.md\tb/a.md\n");
+    }
+
+    #[test]
+    pub fn commit_all() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+
+        assert!(!git.has_pending_changes().unwrap());
+        note(&config, "a/new.md", "# New\n");
+        fs::remove_file(config.storage_directory.join("b.md")).unwrap();
+        assert!(git.has_pending_changes().unwrap());
+
+        git.commit_all("Synchronize notes").unwrap();
+        assert!(!git.has_pending_changes().unwrap());
+        let files = shell.execute_in_repo("git show --name-status --format= HEAD").unwrap();
+        assert_eq!(files.stdout, "A\ta/new.md\nD\tb.md\n");
+    }
+
+    #[test]
+    pub fn show_file() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+
+        let content = fs::read_to_string(config.storage_directory.join("a/aa.md")).unwrap();
+        note(&config, "a/aa.md", "# Changed\n");
+        assert_eq!(git.show_file("HEAD", &PathBuf::from("a/aa.md")).unwrap(), content);
+        assert!(git.show_file("HEAD", &PathBuf::from("unknown.md")).is_err());
+    }
+
+    #[test]
+    pub fn push_and_pull() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitLibraryImpl::new(&config, &shell);
+        assert!(git.push().unwrap_err().message.starts_with("No upstream branch configured"));
+
+        let root = config.storage_directory.parent().unwrap().to_path_buf();
+        let (remote, other) = (root.join("remote.git"), root.join("other"));
+        shell.execute(&format!("git init --bare {}", remote.display()), &root).unwrap();
+        shell
+            .execute_in_repo(&format!("git remote add origin {} && git push -u origin HEAD", remote.display()))
+            .unwrap();
+        shell.execute(&format!("git clone {} {}", remote.display(), other.display()), &root).unwrap();
+
+        git.commit(&note(&config, "a.md", "# Local\n"), "Update a").unwrap();
+        assert!(git.push().unwrap().stdout.starts_with("Pushed"));
+        shell.execute("git pull", &other).unwrap();
+        assert_eq!(fs::read_to_string(other.join("a.md")).unwrap(), "# Local\n");
+
+        fs::write(other.join("b.md"), "# Remote\n").unwrap();
+        shell
+            .execute("git -c user.name=other -c user.email=other@localhost commit -am 'Update b' && git push", &other)
+            .unwrap();
+        assert!(git.pull().unwrap().stdout.starts_with("Fast-forwarded"));
+        assert_eq!(fs::read_to_string(config.storage_directory.join("b.md")).unwrap(), "# Remote\n");
+        assert_eq!(git.pull().unwrap().stdout, "Already up to date.\n");
+        let status = shell.execute_in_repo("git status --porcelain").unwrap();
+        assert_eq!(status.stdout, "");
+    }
+}
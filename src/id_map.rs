@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::default_error::DefaultError;
+
+const HEADER: &str = "# Note ids, generated by notes. Do not edit.";
+
+/// Persistent mapping between note paths (relative to repository) and note ids.
+///
+/// Ids are never reused: a new note always receives the next id, even if notes with
+/// a greater id were deleted.
+#[derive(Debug)]
+pub struct IdMap {
+    path: PathBuf,
+    next_id: usize,
+    ids: BTreeMap<PathBuf, usize>,
+    changed: bool,
+}
+
+impl IdMap {
+    pub fn new(path: &Path) -> IdMap {
+        IdMap {
+            path: path.to_path_buf(),
+            next_id: 1,
+            ids: BTreeMap::new(),
+            changed: false,
+        }
+    }
+
+    /// Load id map from specified file. If file does not exist, an empty map is returned.
+    pub fn load(path: &Path) -> Result<IdMap, DefaultError> {
+        let mut id_map = IdMap::new(path);
+        if !path.exists() {
+            return Ok(id_map);
+        }
+
+        let content = fs::read_to_string(path)?;
+        for line in content.lines().filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some("next"), Some(next_id)) => id_map.next_id = next_id.parse::<usize>()?,
+                (Some(id), Some(note_path)) => {
+                    id_map.ids.insert(PathBuf::from(note_path), id.parse::<usize>()?);
+                }
+                _ => return Err(DefaultError::new(format!("Invalid line in {}: {}", path.to_str().unwrap(), line))),
+            }
+        }
+
+        let max_id = id_map.ids.values().max().cloned().unwrap_or(0);
+        if id_map.next_id <= max_id {
+            id_map.next_id = max_id + 1;
+        }
+        Ok(id_map)
+    }
+
+    pub fn get(&self, note_path: &Path) -> Option<usize> {
+        self.ids.get(note_path).cloned()
+    }
+
+    /// Return id of specified note, assign a new one if note is not known yet
+    pub fn id_for(&mut self, note_path: &Path) -> usize {
+        if let Some(id) = self.get(note_path) {
+            return id;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ids.insert(note_path.to_path_buf(), id);
+        self.changed = true;
+        id
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if let Some(id) = self.ids.remove(from) {
+            self.ids.insert(to.to_path_buf(), id);
+            self.changed = true;
+        }
+    }
+
+    pub fn remove(&mut self, note_path: &Path) {
+        if self.ids.remove(note_path).is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// Forget all notes in specified directory and its sub directories
+    pub fn remove_directory(&mut self, directory: &Path) {
+        let before = self.ids.len();
+        self.ids.retain(|path, _| !path.starts_with(directory));
+        if self.ids.len() != before {
+            self.changed = true;
+        }
+    }
+
+    /// Forget all notes that are not in specified list
+    pub fn retain(&mut self, existing: &[PathBuf]) {
+        let before = self.ids.len();
+        self.ids.retain(|path, _| existing.contains(path));
+        if self.ids.len() != before {
+            self.changed = true;
+        }
+    }
+
+    /// Write id map on disk, only if something changed since loading
+    pub fn save(&self) -> Result<(), DefaultError> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let mut lines = vec![HEADER.to_string(), format!("next {}", self.next_id)];
+        let mut entries: Vec<(&PathBuf, &usize)> = self.ids.iter().collect();
+        entries.sort_by(|a, b| a.1.cmp(b.1));
+        lines.extend(entries.iter().map(|(path, id)| format!("{} {}", id, path.to_str().unwrap())));
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, format!("{}\n", lines.join("\n")))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::new_test_root;
+
+    fn id_map_path() -> PathBuf {
+        new_test_root().join(".notes").join("ids")
+    }
+
+    #[test]
+    pub fn id_for_should_assign_ids_in_order() {
+        let mut id_map = IdMap::load(&id_map_path()).unwrap();
+        assert_eq!(id_map.id_for(&PathBuf::from("a.md")), 1);
+        assert_eq!(id_map.id_for(&PathBuf::from("b.md")), 2);
+        assert_eq!(id_map.id_for(&PathBuf::from("a.md")), 1);
+    }
+
+    #[test]
+    pub fn ids_should_survive_save_and_load() {
+        let path = id_map_path();
+        let mut id_map = IdMap::load(&path).unwrap();
+        id_map.id_for(&PathBuf::from("a.md"));
+        id_map.id_for(&PathBuf::from("dir/b.md"));
+        id_map.save().unwrap();
+
+        let mut id_map = IdMap::load(&path).unwrap();
+        assert_eq!(id_map.get(&PathBuf::from("dir/b.md")), Some(2));
+        assert_eq!(id_map.id_for(&PathBuf::from("c.md")), 3);
+    }
+
+    #[test]
+    pub fn save_should_only_write_changes() {
+        let path = id_map_path();
+        let mut id_map = IdMap::load(&path).unwrap();
+        id_map.id_for(&PathBuf::from("a.md"));
+        id_map.save().unwrap();
+
+        let mut id_map = IdMap::load(&path).unwrap();
+        id_map.id_for(&PathBuf::from("a.md"));
+        id_map.retain(&[PathBuf::from("a.md")]);
+        fs::remove_file(&path).unwrap();
+        id_map.save().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    pub fn ids_should_not_be_reused() {
+        let path = id_map_path();
+        let mut id_map = IdMap::load(&path).unwrap();
+        id_map.id_for(&PathBuf::from("a.md"));
+        id_map.id_for(&PathBuf::from("b.md"));
+        id_map.remove(&PathBuf::from("b.md"));
+        id_map.save().unwrap();
+
+        let mut id_map = IdMap::load(&path).unwrap();
+        assert_eq!(id_map.id_for(&PathBuf::from("c.md")), 3);
+    }
+
+    #[test]
+    pub fn rename() {
+        let mut id_map = IdMap::load(&id_map_path()).unwrap();
+        id_map.id_for(&PathBuf::from("a.md"));
+        id_map.rename(&PathBuf::from("a.md"), &PathBuf::from("dir/a.md"));
+        assert_eq!(id_map.get(&PathBuf::from("a.md")), None);
+        assert_eq!(id_map.get(&PathBuf::from("dir/a.md")), Some(1));
+    }
+
+    #[test]
+    pub fn remove_directory() {
+        let mut id_map = IdMap::load(&id_map_path()).unwrap();
+        id_map.id_for(&PathBuf::from("a/a.md"));
+        id_map.id_for(&PathBuf::from("a/b/b.md"));
+        id_map.id_for(&PathBuf::from("ab.md"));
+        id_map.remove_directory(&PathBuf::from("a"));
+        assert_eq!(id_map.get(&PathBuf::from("a/a.md")), None);
+        assert_eq!(id_map.get(&PathBuf::from("a/b/b.md")), None);
+        assert_eq!(id_map.get(&PathBuf::from("ab.md")), Some(3));
+    }
+
+    #[test]
+    pub fn retain() {
+        let mut id_map = IdMap::load(&id_map_path()).unwrap();
+        id_map.id_for(&PathBuf::from("a.md"));
+        id_map.id_for(&PathBuf::from("b.md"));
+        id_map.retain(&[PathBuf::from("b.md")]);
+        assert_eq!(id_map.get(&PathBuf::from("a.md")), None);
+        assert_eq!(id_map.get(&PathBuf::from("b.md")), Some(2));
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Serialize, Serializer};
+use serde_json::json;
+
+use crate::cli_format::{CliFormat, NoteChange, NotebookItem};
+use crate::console_output::ConsoleOutput;
+use crate::git::LogEntry;
+use crate::note::Note;
+use crate::repository::RepositoryDir;
+use crate::search_match::{HistoryMatch, SearchMatch, TermScore};
+
+/// Machine readable format, used by editor plugins and scripts. Output of each command is a single
+/// JSON document on one line.
+pub struct JsonFormat;
+
+impl JsonFormat {
+    pub fn new() -> Self {
+        JsonFormat {}
+    }
+}
+
+impl Default for JsonFormat {
+    fn default() -> Self {
+        JsonFormat::new()
+    }
+}
+
+/// Paths are serialized as strings, characters that are not valid UTF-8 are replaced. Default
+/// serialization of paths fails on them.
+pub fn lossy_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+/// Formatted types only contain strings, numbers and sequences, so serialization cannot fail
+fn to_json<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+/// Directory of a search, None for whole repository
+fn directory(scope: &Path) -> Option<String> {
+    match scope.to_string_lossy().as_ref() {
+        "" => None,
+        scope => Some(scope.to_string()),
+    }
+}
+
+impl CliFormat for JsonFormat {
+    fn note_tree(&self, directories: &[NotebookItem<RepositoryDir>]) -> String {
+        format!("{}\n", to_json(directories))
+    }
+
+    /// Explanations of scores are always included
+    fn search_results(&self, needle: &str, scope: &Path, matches: &[NotebookItem<SearchMatch>], _explain: bool) -> String {
+        let document = json!({
+            "query": needle,
+            "directory": directory(scope),
+            "count": matches.len(),
+            "results": matches,
+        });
+        format!("{}\n", document)
+    }
+
+    fn tag_list(&self, tags: &BTreeMap<String, usize>) -> String {
+        let items: Vec<_> = tags.iter().map(|(tag, count)| json!({ "tag": tag, "count": count })).collect();
+        format!("{}\n", to_json(&items))
+    }
+
+    fn history_results(&self, needle: &str, scope: &Path, matches: &[HistoryMatch]) -> String {
+        let document = json!({
+            "query": needle,
+            "directory": directory(scope),
+            "count": matches.len(),
+            "results": matches,
+        });
+        format!("{}\n", document)
+    }
+
+    fn note_history(&self, note: &Note, path: &Path, commits: &[LogEntry]) -> String {
+        let document = json!({
+            "id": note.id,
+            "title": note.title,
+            "path": path.to_string_lossy(),
+            "count": commits.len(),
+            "commits": commits,
+        });
+        format!("{}\n", document)
+    }
+
+    fn diff(&self, diff: &str, _word: bool) -> String {
+        format!("{}\n", json!({ "diff": diff }))
+    }
+
+    fn note_content(&self, note: &Note) -> String {
+        let mut document = serde_json::to_value(note).unwrap();
+        document["content"] = json!(note.content());
+        format!("{}\n", document)
+    }
+
+    fn note_changes(&self, changes: &[NoteChange]) -> String {
+        format!("{}\n", json!({ "changes": changes }))
+    }
+
+    fn message(&self, message: &str) -> String {
+        format!("{}\n", json!({ "message": message }))
+    }
+
+    fn config_values(&self, values: &[(String, String)]) -> String {
+        let document: BTreeMap<&str, &str> = values.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+        format!("{}\n", to_json(&document))
+    }
+
+    fn config_value(&self, key: &str, value: &str) -> String {
+        format!("{}\n", json!({ "key": key, "value": value }))
+    }
+
+    fn config_saved(&self, key: &str, value: &str, path: &Path) -> String {
+        format!("{}\n", json!({ "key": key, "value": value, "path": path.to_string_lossy() }))
+    }
+
+    /// Standard output only contains the JSON document, other outputs are written on standard error
+    fn command_output(&self, output: ConsoleOutput) -> ConsoleOutput {
+        ConsoleOutput::from_stderr(&format!("{}{}", output.stdout, output.stderr))
+    }
+
+    fn search_match(&self, search_m: &SearchMatch) -> String {
+        to_json(search_m)
+    }
+
+    fn note_list_item(&self, note: &Note) -> String {
+        to_json(note)
+    }
+
+    fn match_score(&self, score: f64) -> String {
+        to_json(&score)
+    }
+
+    fn score_explanation(&self, explanation: &[TermScore]) -> String {
+        to_json(explanation)
+    }
+
+    fn note_id(&self, id: usize) -> String {
+        to_json(&id)
+    }
+
+    fn note_title(&self, title: &str) -> String {
+        to_json(title)
+    }
+
+    fn note_directory(&self, name: &str) -> String {
+        to_json(name)
+    }
+
+    fn tag_list_item(&self, tag: &str, count: usize) -> String {
+        json!({ "tag": tag, "count": count }).to_string()
+    }
+
+    fn notebook_name(&self, name: &str) -> String {
+        to_json(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_match::{HistoryLine, MatchedLine};
+    use std::ffi::OsStr;
+    use std::ops::Range;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    fn note() -> Note {
+        Note::from(3, PathBuf::from("/repo/tools/rsync.md"), "# Rsync\n\nUse it over #ssh".to_string()).unwrap()
+    }
+
+    #[test]
+    pub fn note_tree() {
+        let directories = vec![NotebookItem {
+            notebook: Some("work".to_string()),
+            item: RepositoryDir {
+                name: "tools".to_string(),
+                path: PathBuf::from("/repo/tools"),
+                notes: vec![note()],
+                level: 1,
+            },
+        }];
+        assert_eq!(
+            JsonFormat::new().note_tree(&directories),
+            "[{\"notebook\":\"work\",\"name\":\"tools\",\"path\":\"/repo/tools\",\"notes\":[{\"id\":3,\"path\":\"/repo/tools/rsync.md\",\"title\":\"# Rsync\",\"tags\":[\"ssh\"]}],\"level\":1}]\n"
+        );
+    }
+
+    #[test]
+    pub fn search_results() {
+        let matches = vec![NotebookItem {
+            notebook: None,
+            item: SearchMatch {
+                id: 3,
+                score: 1.5,
+                path: PathBuf::from("/repo/tools/rsync.md"),
+                title: "# Rsync".to_string(),
+                matched_lines: vec![MatchedLine {
+                    display_number: 3,
+                    line_number: 1,
+                    content: "Use it over #ssh".to_string(),
+                    matches: vec![Range { start: 12, end: 16 }],
+                    before: vec![],
+                    after: vec![],
+                }],
+                explanation: vec![],
+            },
+        }];
+        let output = JsonFormat::new().search_results("ssh", Path::new("tools"), &matches, false);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({
+                "query": "ssh",
+                "directory": "tools",
+                "count": 1,
+                "results": [{
+                    "id": 3,
+                    "score": 1.5,
+                    "path": "/repo/tools/rsync.md",
+                    "title": "# Rsync",
+                    "matched_lines": [{
+                        "display_number": 3,
+                        "line_number": 1,
+                        "content": "Use it over #ssh",
+                        "matches": [{ "start": 12, "end": 16 }],
+                        "before": [],
+                        "after": []
+                    }],
+                    "explanation": []
+                }]
+            })
+        );
+
+        let output = JsonFormat::new().search_results("ssh", Path::new(""), &[], false);
+        assert_eq!(output, "{\"count\":0,\"directory\":null,\"query\":\"ssh\",\"results\":[]}\n");
+    }
+
+    #[test]
+    pub fn tag_list() {
+        let mut tags = BTreeMap::new();
+        tags.insert("rsync".to_string(), 2);
+        tags.insert("ssh".to_string(), 1);
+        assert_eq!(
+            JsonFormat::new().tag_list(&tags),
+            "[{\"count\":2,\"tag\":\"rsync\"},{\"count\":1,\"tag\":\"ssh\"}]\n"
+        );
+    }
+
+    #[test]
+    pub fn history_results() {
+        let matches = vec![HistoryMatch {
+            commit: "00c1325".to_string(),
+            date: "2020-10-18".to_string(),
+            path: PathBuf::from("tools/ssh.md"),
+            revision: "00c1325^".to_string(),
+            lines: vec![HistoryLine {
+                added: false,
+                content: "Use rsync".to_string(),
+                matches: vec![Range { start: 4, end: 9 }],
+            }],
+        }];
+        let output = JsonFormat::new().history_results("rsync", Path::new(""), &matches);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({
+                "query": "rsync",
+                "directory": null,
+                "count": 1,
+                "results": [{
+                    "commit": "00c1325",
+                    "date": "2020-10-18",
+                    "path": "tools/ssh.md",
+                    "revision": "00c1325^",
+                    "lines": [{ "added": false, "content": "Use rsync", "matches": [{ "start": 4, "end": 9 }] }]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    pub fn diff() {
+        let output = JsonFormat::new().diff("+new\n", false);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&output).unwrap(), json!({ "diff": "+new\n" }));
+    }
+
+    #[test]
+    pub fn note_content() {
+        let output = JsonFormat::new().note_content(&note());
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({
+                "id": 3,
+                "path": "/repo/tools/rsync.md",
+                "title": "# Rsync",
+                "tags": ["ssh"],
+                "content": "# Rsync\n\nUse it over #ssh"
+            })
+        );
+    }
+
+    #[test]
+    pub fn non_utf8_paths() {
+        let path = PathBuf::from(OsStr::from_bytes(b"/repo/caf\xe9.md"));
+        let output = JsonFormat::new().note_changes(&[NoteChange::Created { path: path.clone() }]);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({ "changes": [{ "action": "created", "path": "/repo/caf\u{fffd}.md" }] })
+        );
+
+        let note = Note::from(3, path, "# Café".to_string()).unwrap();
+        assert!(JsonFormat::new().note_list_item(&note).contains("/repo/caf\u{fffd}.md"));
+    }
+
+    #[test]
+    pub fn note_changes() {
+        let changes = vec![
+            NoteChange::Deleted { path: "/repo/a.md".into() },
+            NoteChange::Restored {
+                path: "b.md".into(),
+                revision: "00c1325".to_string(),
+            },
+        ];
+        let output = JsonFormat::new().note_changes(&changes);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({
+                "changes": [
+                    { "action": "deleted", "path": "/repo/a.md" },
+                    { "action": "restored", "path": "b.md", "revision": "00c1325" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    pub fn config_values() {
+        let values = vec![("editor".to_string(), "vim".to_string()), ("auto_push".to_string(), "false".to_string())];
+        assert_eq!(JsonFormat::new().config_values(&values), "{\"auto_push\":\"false\",\"editor\":\"vim\"}\n");
+        assert_eq!(JsonFormat::new().config_value("editor", "vim"), "{\"key\":\"editor\",\"value\":\"vim\"}\n");
+    }
+
+    #[test]
+    pub fn command_output() {
+        let output = JsonFormat::new().command_output(ConsoleOutput::new("[master 00c1325] Update\n".to_string(), "warning\n".to_string()));
+        assert_eq!(output, ConsoleOutput::from_stderr("[master 00c1325] Update\nwarning\n"));
+    }
+
+    #[test]
+    pub fn note_history() {
+        let note = Note::from(3, "/repo/tools/rsync.md".into(), "# Rsync".to_string()).unwrap();
+        let commits = vec![LogEntry {
+            commit: "00c1325".to_string(),
+            date: "2020-10-18".to_string(),
+            author: "Jane Doe".to_string(),
+            message: "Create note".to_string(),
+            path: PathBuf::from("rsync.md"),
+            added: 3,
+            removed: 0,
+        }];
+        let output = JsonFormat::new().note_history(&note, Path::new("tools/rsync.md"), &commits);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({
+                "id": 3,
+                "title": "# Rsync",
+                "path": "tools/rsync.md",
+                "count": 1,
+                "commits": [{
+                    "commit": "00c1325",
+                    "date": "2020-10-18",
+                    "author": "Jane Doe",
+                    "message": "Create note",
+                    "path": "rsync.md",
+                    "added": 3,
+                    "removed": 0
+                }]
+            })
+        );
+    }
+}
@@ -1,14 +1,18 @@
 #![feature(backtrace)]
 
+use std::env::current_dir;
 use std::path::PathBuf;
 
-use crate::cli_format::CliFormatImpl;
-use crate::command_handler::CommandHandler;
+use crate::cli_format::{CliFormat, CliFormatImpl, OutputFormat};
+use crate::command_handler::{CommandHandler, Notebook};
 use crate::command_parser::CommandParser;
-use crate::config::Config;
+use crate::config::{ColorTheme, Config, GitBackend};
 use crate::console_output::ConsoleOutput;
 use crate::default_error::DefaultError;
-use crate::git::GitImpl;
+use crate::git::{Git, GitImpl};
+use crate::git_library::GitLibraryImpl;
+use crate::json_format::JsonFormat;
+use crate::prompt::PromptImpl;
 use crate::repository::{Repository, RepositoryImpl};
 use crate::shell::{command, ShellImpl};
 
@@ -17,17 +21,30 @@ pub mod config;
 pub mod console_output;
 pub mod default_error;
 pub mod logger;
+pub mod pager;
 #[doc(hidden)]
 pub mod test_env;
 
 mod cli_format;
 mod command_handler;
 mod command_parser;
+mod config_file;
 mod env;
+mod front_matter;
+mod fuzzy;
 mod git;
+mod git_library;
+mod id_map;
+mod json_format;
 mod note;
+mod note_selector;
+mod picker;
+mod prompt;
+mod ranking;
 mod repository;
+mod search_index;
 mod search_match;
+mod search_query;
 mod shell;
 mod usage;
 
@@ -36,24 +53,97 @@ pub const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const PKG_AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 pub const PKG_DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
 
-pub fn parse_and_apply_command(args: Vec<String>, config: &Config) -> Result<ConsoleOutput, DefaultError> {
-    check_prerequisites()?;
-    let command = CommandParser::new().parse_arguments(args)?;
+/// Options parsed from command line that change how outputs are displayed
+#[derive(Debug, PartialEq)]
+pub struct DisplayOptions {
+    /// False if long outputs must not be displayed with a pager
+    pub pager: bool,
+    /// Banner is not displayed with JSON output, it would prevent scripts from parsing it
+    pub banner: bool,
+}
+
+pub fn parse_and_apply_command(args: Vec<String>, config: &Config) -> Result<(ConsoleOutput, DisplayOptions), DefaultError> {
+    check_prerequisites(config)?;
+    let arguments = CommandParser::new().parse_arguments(args)?;
+    let command = arguments.command;
+    let display = DisplayOptions {
+        pager: arguments.pager,
+        banner: arguments.format == OutputFormat::Text,
+    };
+
+    let notebook_config;
+    let config = match &arguments.notebook {
+        Some(name) => {
+            notebook_config = config.for_notebook(name)?;
+            &notebook_config
+        }
+        None => config,
+    };
+
+    if config.color_theme == ColorTheme::None || arguments.format == OutputFormat::Json {
+        colored::control::set_override(false);
+    }
 
     let shell = ShellImpl::new(config);
-    let git = GitImpl::new(&shell);
-    let format = CliFormatImpl::new();
-    let repository = RepositoryImpl::new(config, &shell, &git);
-    let handler = CommandHandler::new(&repository, &format);
+    let git = new_git(config, &shell);
+    let text_format = CliFormatImpl::with_theme(&config.color_theme);
+    let json_format = JsonFormat::new();
+    let format: &dyn CliFormat = match arguments.format {
+        OutputFormat::Text => &text_format,
+        OutputFormat::Json => &json_format,
+    };
+    let prompt = PromptImpl::new();
+    let repository = RepositoryImpl::new(config, &shell, git.as_ref());
+
+    // Repositories of all notebooks are only loaded if needed
+    let notebook_configs = match command.uses_all_notebooks() {
+        true => config.notebook_configs()?,
+        false => vec![],
+    };
+    let notebook_shells: Vec<ShellImpl> = notebook_configs.iter().map(|(_, c)| ShellImpl::new(c)).collect();
+    let notebook_gits: Vec<Box<dyn Git>> = notebook_configs.iter().zip(notebook_shells.iter()).map(|((_, c), s)| new_git(c, s)).collect();
+    let notebook_repositories: Vec<RepositoryImpl> = notebook_configs
+        .iter()
+        .zip(notebook_shells.iter().zip(notebook_gits.iter()))
+        .map(|((_, c), (s, g))| RepositoryImpl::new(c, s, g.as_ref()))
+        .collect();
+    let notebooks: Vec<Notebook> = notebook_configs
+        .iter()
+        .zip(notebook_repositories.iter())
+        .map(|((name, _), repository)| Notebook {
+            name: name.clone(),
+            repository,
+        })
+        .collect();
+
+    let handler = CommandHandler::new(config, &repository, format, &prompt)
+        .with_prompt_format(&text_format)
+        .with_notebooks(notebooks)
+        .with_current_directory(current_dir().ok());
 
     repository.init()?;
-    handler.apply_command(command)
+    let output = handler.apply_command(command)?;
+    Ok((output, display))
 }
 
-fn check_prerequisites() -> Result<(), DefaultError> {
+fn new_git<'a>(config: &Config, shell: &'a ShellImpl) -> Box<dyn Git + 'a> {
+    match config.git_backend {
+        GitBackend::Shell => Box::new(GitImpl::new(shell)),
+        GitBackend::Library => Box::new(GitLibraryImpl::new(config, shell)),
+    }
+}
+
+fn check_prerequisites(config: &Config) -> Result<(), DefaultError> {
     assert_exists("sh", "sh must be installed and in path variable")?;
     assert_exists("git", "Git must be installed and in path variable")?;
-    assert_exists("$EDITOR", "EDITOR variable must contains a valid text editor, e.g.:\n\n\texport EDITOR=vim")
+    match &config.editor {
+        // Editor command can have arguments, e.g: code --wait
+        Some(editor) => assert_exists(
+            editor.split_whitespace().next().unwrap_or(""),
+            "Configured editor must be installed and in path variable",
+        ),
+        None => assert_exists("$EDITOR", "EDITOR variable must contains a valid text editor, e.g.:\n\n\texport EDITOR=vim"),
+    }
 }
 
 fn assert_exists(cmd: &str, message: &str) -> Result<(), DefaultError> {
@@ -5,20 +5,39 @@ use std::process;
 use notes::banners::Banners;
 use notes::config::Config;
 use notes::logger::{Logger, LoggerImpl};
+use notes::pager::{needs_pager, page, terminal_height, PagerError};
 use notes::parse_and_apply_command;
 
 mod logger;
 
 fn main() {
     let logger = LoggerImpl::default();
-    let config = Config::default();
     let args: Vec<String> = std::env::args().collect();
-
-    logger.log(&Banners::small());
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => {
+            logger.error(format!("{}", error).as_str());
+            process::exit(1);
+        }
+    };
 
     match parse_and_apply_command(args, &config) {
-        Ok(output) => {
-            if !output.stdout.is_empty() {
+        Ok((output, display)) => {
+            if display.banner {
+                logger.log(&Banners::small());
+            }
+            // If pager cannot be started, output is displayed as usual
+            let paged = display.pager
+                && needs_pager(&config.pager, &output.stdout, terminal_height())
+                && match page(&config.pager, &output.stdout) {
+                    Ok(()) => true,
+                    Err(PagerError::NotStarted(_)) => false,
+                    Err(PagerError::Failed(error)) => {
+                        logger.error(format!("{}", error).as_str());
+                        true
+                    }
+                };
+            if !output.stdout.is_empty() && !paged {
                 logger.stdout(&output.stdout)
             }
             if !output.stderr.is_empty() {
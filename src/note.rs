@@ -1,49 +1,67 @@
 extern crate regex;
 
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::fs;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use lazy_static::lazy_static;
 use regex::{Regex, RegexBuilder};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 
 use crate::default_error::DefaultError;
-use crate::search_match::{MatchedLine, SearchMatch};
+use crate::front_matter::FrontMatter;
+use crate::fuzzy;
+use crate::fuzzy::FuzzyMatch;
+use crate::ranking::Ranking;
+use crate::search_match::{Context, ContextLine, MatchedLine, SearchMatch};
+use crate::search_query::Query;
 
 lazy_static! {
     static ref HAS_CONTENT: Regex = RegexBuilder::new("\\w").case_insensitive(true).build().unwrap();
+    static ref INLINE_TAG: Regex = Regex::new("(?:^|\\s)#([\\w][\\w/-]*)").unwrap();
+    static ref VALID_TAG: Regex = Regex::new("^[\\w][\\w/-]*$").unwrap();
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Note {
     pub id: usize,
     pub path: PathBuf,
+    /// Title from front matter if any, otherwise first non empty line of note
     pub title: String,
-    /// Contains only non empty lines of note, without title
+    pub front_matter: Option<FrontMatter>,
+    /// Contains only non empty lines of note, without title and front matter
     pub body: Vec<String>,
-    /// Contains all note lines
+    /// Contains all note lines, front matter included
     pub raw: Vec<String>,
 }
 
 impl Note {
     pub fn from(id: usize, path: PathBuf, raw_content: String) -> Result<Note, DefaultError> {
         let all_lines: Vec<String> = raw_content.split('\n').map(String::from).collect();
-        let non_empty_lines: Vec<String> = all_lines.iter().filter(|l| !l.is_empty()).map(String::from).collect();
-
-        if non_empty_lines.is_empty() {
-            return Err(DefaultError {
-                message: "Not enough lines".to_string(),
-                backtrace: None,
-            });
-        }
-
-        let title = non_empty_lines.get(0).unwrap().to_string();
-        let body = non_empty_lines.into_iter().skip(1).collect();
+        let front_matter = FrontMatter::parse(&all_lines);
+        let content_start = front_matter.as_ref().map_or(0, |fm| fm.line_count);
+        let non_empty_lines: Vec<String> = all_lines.iter().skip(content_start).filter(|l| !l.is_empty()).map(String::from).collect();
+
+        let (title, body) = match front_matter.as_ref().and_then(|fm| fm.title.clone()) {
+            Some(title) => (title, non_empty_lines),
+            None if !non_empty_lines.is_empty() => {
+                let title = non_empty_lines.get(0).unwrap().to_string();
+                (title, non_empty_lines.into_iter().skip(1).collect())
+            }
+            None => {
+                return Err(DefaultError {
+                    message: "Not enough lines".to_string(),
+                    backtrace: None,
+                })
+            }
+        };
 
         Ok(Note {
             id,
             path,
             title,
+            front_matter,
             body,
             raw: all_lines,
         })
@@ -54,73 +72,238 @@ impl Note {
         Note::from(id, path, content)
     }
 
-    pub fn search_match(&self, needle_regex: &Regex) -> SearchMatch {
-        let score = self.match_score(needle_regex);
-        let title_position = self.raw.iter().position(|l| &self.title == l).unwrap();
+    /// Lines matching regex, with their neighbours. Title can match without match in content,
+    /// in this case we return the first lines of note.
+    pub fn matching_lines(&self, needle_regex: &Regex, context: &Context) -> Vec<MatchedLine> {
+        let body_start = self.body_start();
 
-        let mut matching_lines: Vec<MatchedLine> = self
+        let matches: Vec<(usize, Vec<Range<usize>>)> = self
             .raw
             .iter()
             .enumerate()
-            .skip(title_position + 1)
-            .filter_map(|(idx, line)| match needle_regex.captures(line) {
-                Some(captures) => {
-                    let matched = String::from(captures.get(1).map_or("", |m| m.as_str()));
-                    let previous: Option<String> = if idx > 1 {
-                        self.raw.get(idx - 1).filter(|s| HAS_CONTENT.is_match(s)).map(String::from)
-                    } else {
-                        None
-                    };
-                    let next: Option<String> = self.raw.get(idx + 1).filter(|s| HAS_CONTENT.is_match(s)).map(String::from);
-                    Some(MatchedLine {
-                        display_number: idx + 1,
-                        line_number: idx,
-                        content: String::from(line),
-                        matched,
-                        previous,
-                        next,
-                    })
-                }
-                None => None,
+            .skip(body_start)
+            .filter(|(_, line)| needle_regex.is_match(line))
+            .map(|(idx, line)| {
+                (
+                    idx,
+                    needle_regex.find_iter(line).filter(|m| !m.as_str().is_empty()).map(|m| m.range()).collect(),
+                )
             })
             .collect();
+        let mut matching_lines = self.matched_lines(matches, body_start, context);
 
-        // Title can match without match in content. In this case we return the first lines of note.
-        if score > 0 && matching_lines.is_empty() {
-            let show_lines = 6;
-            let first_lines = min(title_position + show_lines, self.raw.len());
+        if matching_lines.is_empty() && needle_regex.is_match(&self.title) {
+            matching_lines = self.first_lines(body_start);
+        }
+        matching_lines
+    }
+
+    /// Return None if note does not match query. Matching notes are scored with ranking, and highlighted
+    /// with searched texts. If query only searches paths or tags, first lines of note are returned.
+    pub fn query_match(&self, query: &Query, relative_path: &Path, ranking: &Ranking, context: &Context) -> Option<SearchMatch> {
+        if !query.matches(self, relative_path) {
+            return None;
+        }
+
+        let mut matched_lines = query.highlight_regex().map_or(vec![], |regex| self.matching_lines(&regex, context));
+        if matched_lines.is_empty() {
+            matched_lines = self.first_lines(self.body_start());
+        }
+        let explanation = ranking.explain(self, relative_path);
+        Some(SearchMatch {
+            id: self.id,
+            score: explanation.iter().map(|t| t.score).sum(),
+            path: self.path.clone(),
+            title: self.title.clone(),
+            matched_lines,
+            explanation,
+        })
+    }
 
-            matching_lines = self
+    /// Search words with a typo tolerant matcher, in title, file name and body. All words must be found.
+    /// Exact matches are worth twice as much as approximate ones.
+    pub fn fuzzy_match(&self, words: &[String], context: &Context) -> SearchMatch {
+        let body_start = self.body_start();
+        let file_name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let weight = |m: &FuzzyMatch| if m.distance == 0 { 2 } else { 1 };
+
+        let mut score = 0;
+        let mut all_found = !words.is_empty();
+        for word in words {
+            let in_title = fuzzy::find(word, &self.title).map_or(0, |m| 4 * weight(&m));
+            let in_file_name = fuzzy::find(word, file_name).map_or(0, |m| 2 * weight(&m));
+            let in_body: usize = self.body.iter().filter_map(|line| fuzzy::find(word, line)).map(|m| weight(&m)).sum();
+            all_found = all_found && in_title + in_file_name + in_body > 0;
+            score += in_title + in_file_name + in_body;
+        }
+        if !all_found {
+            score = 0;
+        }
+
+        let mut matching_lines: Vec<MatchedLine> = vec![];
+        if score > 0 {
+            let matches: Vec<(usize, Vec<Range<usize>>)> = self
                 .raw
                 .iter()
                 .enumerate()
-                .skip(title_position + 1)
-                .take(first_lines)
-                .filter(|(_, line)| HAS_CONTENT.is_match(line))
-                .map(|(idx, line)| MatchedLine {
-                    display_number: idx + 1,
-                    line_number: idx,
-                    content: String::from(line),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: None,
+                .skip(body_start)
+                .filter_map(|(idx, line)| {
+                    let matches: Vec<Range<usize>> = words.iter().filter_map(|word| fuzzy::find(word, line)).map(|m| m.start..m.end).collect();
+                    match matches.is_empty() {
+                        true => None,
+                        false => Some((idx, merge_ranges(matches))),
+                    }
                 })
                 .collect();
+            matching_lines = self.matched_lines(matches, body_start, context);
+        }
+        if score > 0 && matching_lines.is_empty() {
+            matching_lines = self.first_lines(body_start);
         }
 
         SearchMatch {
             id: self.id,
-            score,
+            score: score as f64,
             path: self.path.clone(),
             title: self.title.clone(),
             matched_lines: matching_lines,
+            explanation: vec![],
+        }
+    }
+
+    /// Matched raw lines, specified by index with their matches, and their context. Like grep, context
+    /// windows that overlap or touch are merged. Lines without content are not displayed at the edges of windows.
+    fn matched_lines(&self, matches: Vec<(usize, Vec<Range<usize>>)>, body_start: usize, context: &Context) -> Vec<MatchedLine> {
+        let indexes: Vec<usize> = matches.iter().map(|(idx, _)| *idx).collect();
+        // Index of the first line that is not displayed yet
+        let mut displayed_until = body_start;
+        let mut matched_lines: Vec<MatchedLine> = vec![];
+        for (position, (idx, ranges)) in matches.into_iter().enumerate() {
+            let next_match = indexes.get(position + 1).copied();
+            let before_start = max(displayed_until, idx.saturating_sub(context.before));
+            let after_end = min(next_match.unwrap_or(self.raw.len()), min(idx + 1 + context.after, self.raw.len()));
+            let touches_previous = position > 0 && idx.saturating_sub(context.before) <= displayed_until;
+            let touches_next = matches!(next_match, Some(next) if next.saturating_sub(context.before) <= after_end);
+
+            let mut before = self.context_lines(before_start..idx);
+            if !touches_previous {
+                let empty = before.iter().take_while(|l| !HAS_CONTENT.is_match(&l.content)).count();
+                before.drain(..empty);
+            }
+            let mut after = self.context_lines(idx + 1..after_end);
+            if !touches_next {
+                let empty = after.iter().rev().take_while(|l| !HAS_CONTENT.is_match(&l.content)).count();
+                after.truncate(after.len() - empty);
+            }
+
+            matched_lines.push(MatchedLine {
+                display_number: idx + 1,
+                line_number: idx,
+                content: self.raw[idx].clone(),
+                matches: ranges,
+                before,
+                after,
+            });
+            displayed_until = after_end;
+        }
+        matched_lines
+    }
+
+    fn context_lines(&self, indexes: Range<usize>) -> Vec<ContextLine> {
+        indexes
+            .map(|idx| ContextLine {
+                display_number: idx + 1,
+                content: self.raw[idx].clone(),
+            })
+            .collect()
+    }
+
+    /// First lines of body, displayed when only title matches
+    fn first_lines(&self, body_start: usize) -> Vec<MatchedLine> {
+        let show_lines = 6;
+        let first_lines = min(body_start + show_lines - 1, self.raw.len());
+
+        self.raw
+            .iter()
+            .enumerate()
+            .skip(body_start)
+            .take(first_lines)
+            .filter(|(_, line)| HAS_CONTENT.is_match(line))
+            .map(|(idx, line)| MatchedLine {
+                display_number: idx + 1,
+                line_number: idx,
+                content: String::from(line),
+                matches: vec![],
+                before: vec![],
+                after: vec![],
+            })
+            .collect()
+    }
+
+    /// Tags of note, from front matter and from inline #tags in body, sorted and without duplicates
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.front_matter_tags();
+        tags.extend(self.inline_tags());
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().iter().any(|t| t == tag)
+    }
+
+    fn front_matter_tags(&self) -> Vec<String> {
+        self.front_matter.as_ref().map_or(vec![], |fm| fm.tags.clone())
+    }
+
+    fn inline_tags(&self) -> Vec<String> {
+        let content_start = self.front_matter.as_ref().map_or(0, |fm| fm.line_count);
+        self.raw
+            .iter()
+            .skip(content_start)
+            .flat_map(|line| INLINE_TAG.captures_iter(line).map(|c| c[1].to_string()).collect::<Vec<String>>())
+            .collect()
+    }
+
+    /// Return a copy of note with specified tag added in front matter
+    pub fn with_tag(&self, tag: &str) -> Result<Note, DefaultError> {
+        if !VALID_TAG.is_match(tag) {
+            return Err(DefaultError::new(format!("Invalid tag '{}', use only letters, digits, '_', '-' and '/'", tag)));
+        }
+        let mut tags = self.front_matter_tags();
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+        self.with_front_matter_tags(&tags)
+    }
+
+    /// Return a copy of note with specified tag removed from front matter
+    pub fn without_tag(&self, tag: &str) -> Result<Note, DefaultError> {
+        if self.inline_tags().iter().any(|t| t == tag) {
+            return Err(DefaultError::new(format!("Tag '{}' is used inline in note, edit note to remove it", tag)));
         }
+        let tags: Vec<String> = self.front_matter_tags().into_iter().filter(|t| t != tag).collect();
+        self.with_front_matter_tags(&tags)
+    }
+
+    fn with_front_matter_tags(&self, tags: &[String]) -> Result<Note, DefaultError> {
+        let lines = FrontMatter::replace_tags(&self.raw, tags)?;
+        Note::from(self.id, self.path.clone(), lines.join("\n"))
     }
 
-    fn match_score(&self, needle_regex: &Regex) -> usize {
-        let match_in_title = if needle_regex.is_match(&self.title) { 4 } else { 0 };
-        let match_in_body: usize = self.body.iter().map(|line| if needle_regex.is_match(line) { 1 } else { 0 }).sum();
-        match_in_title + match_in_body
+    /// Index of the first raw line after front matter and title
+    fn body_start(&self) -> usize {
+        let content_start = self.front_matter.as_ref().map_or(0, |fm| fm.line_count);
+        let title_in_front_matter = self.front_matter.as_ref().and_then(|fm| fm.title.as_ref()).is_some();
+        if title_in_front_matter {
+            return content_start;
+        }
+        self.raw
+            .iter()
+            .skip(content_start)
+            .position(|l| l == &self.title)
+            .map_or(content_start, |position| content_start + position + 1)
     }
 
     pub fn content(&self) -> String {
@@ -128,6 +311,31 @@ impl Note {
     }
 }
 
+/// Sort ranges and merge the ones that overlap
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|r| (r.start, r.end));
+    let mut merged: Vec<Range<usize>> = vec![];
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = max(last.end, range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Notes are serialized without their content, for machine readable listings
+impl Serialize for Note {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Note", 4)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("path", &self.path.to_string_lossy())?;
+        state.serialize_field("title", &self.title)?;
+        state.serialize_field("tags", &self.tags())?;
+        state.end()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -135,6 +343,8 @@ mod tests {
     use regex::{Regex, RegexBuilder};
 
     use super::*;
+    use crate::search_index::SearchIndex;
+    use crate::search_query::SearchOptions;
 
     const SAMPLE_NOTE_1: &str = "\
 
@@ -161,6 +371,16 @@ With very interesting things inside
 A very interesting one
 With very interesting things inside
 
+";
+
+    const SAMPLE_NOTE_4: &str = "---
+title: Rsync tips
+tags: [rsync]
+---
+
+# Rsync
+
+A note about rsync
 ";
 
     pub fn needle_regexp(needle: &str) -> Regex {
@@ -187,70 +407,240 @@ With very interesting things inside
     }
 
     #[test]
-    pub fn match_score() {
-        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_1.to_string()).unwrap();
-        let needle_regex = needle_regexp("ssh");
-        assert_eq!(note.match_score(&needle_regex), 5);
+    pub fn from_front_matter() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_4.to_string()).unwrap();
+        assert_eq!(note.title, "Rsync tips");
+        assert_eq!(note.front_matter.as_ref().unwrap().tags, vec!["rsync".to_string()]);
+        assert_eq!(note.body, vec!["# Rsync".to_string(), "A note about rsync".to_string()]);
+        assert_eq!(note.content(), SAMPLE_NOTE_4);
     }
 
     #[test]
-    pub fn match_score_should_score_0() {
-        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_1.to_string()).unwrap();
-        let needle_regex = needle_regexp("something-else");
-        assert_eq!(note.match_score(&needle_regex), 0);
+    pub fn from_front_matter_without_title() {
+        let content = "---\ntags: [rsync]\n---\n# Rsync\nA note about rsync";
+        let note = Note::from(0, "/tmp/note-1.txt".into(), content.to_string()).unwrap();
+        assert_eq!(note.title, "# Rsync");
+        assert_eq!(note.body, vec!["A note about rsync".to_string()]);
+        assert_eq!(note.content(), content);
+    }
+
+    #[test]
+    pub fn from_front_matter_with_scalar_tags() {
+        let content = "---\ntitle: SSH tips\ntags: ssh\n---\n# SSH\nA note about SSH";
+        let note = Note::from(0, "/tmp/note-1.txt".into(), content.to_string()).unwrap();
+        assert_eq!(note.title, "SSH tips");
+        assert_eq!(note.tags(), vec!["ssh"]);
+        assert_eq!(note.body, vec!["# SSH".to_string(), "A note about SSH".to_string()]);
+    }
+
+    #[test]
+    pub fn tags() {
+        let content = "---\ntags: [rsync, network]\n---\n# Rsync #title-tag\n\nA note about #rsync and #ssh/keys\n## Heading";
+        let note = Note::from(0, "/tmp/note-1.txt".into(), content.to_string()).unwrap();
+        assert_eq!(note.tags(), vec!["network", "rsync", "ssh/keys", "title-tag"]);
+        assert!(note.has_tag("network"));
+        assert!(!note.has_tag("heading"));
+    }
+
+    #[test]
+    pub fn with_tag() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_4.to_string()).unwrap();
+        let note = note.with_tag("backup").unwrap();
+        assert_eq!(note.tags(), vec!["backup", "rsync"]);
+        assert!(note.content().starts_with("---\ntitle: Rsync tips\ntags: [rsync, backup]\n---\n"));
+    }
+
+    #[test]
+    pub fn with_tag_should_fail() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_4.to_string()).unwrap();
+        assert!(note.with_tag("two words").is_err());
+    }
+
+    #[test]
+    pub fn with_tag_should_fail_on_invalid_front_matter() {
+        let content = "---\ntitle: SSH tips\ntags: [ssh\n---\n# SSH";
+        let note = Note::from(0, "/tmp/note-1.txt".into(), content.to_string()).unwrap();
+        assert!(note.with_tag("network").is_err());
     }
 
     #[test]
-    pub fn search_match() {
+    pub fn without_tag() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_4.to_string()).unwrap();
+        let note = note.without_tag("rsync").unwrap();
+        assert!(note.tags().is_empty());
+        assert!(note.content().starts_with("---\ntitle: Rsync tips\n---\n"));
+    }
+
+    #[test]
+    pub fn without_tag_should_fail_if_tag_is_inline() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), "# Title\nSome #inline tag".to_string()).unwrap();
+        assert!(note.without_tag("inline").is_err());
+    }
+
+    #[test]
+    pub fn matching_lines() {
         let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_2.to_string()).unwrap();
         let needle_regex = needle_regexp("rsync");
-        let actual = note.search_match(&needle_regex);
+        let actual = note.matching_lines(&needle_regex, &Context::default());
+        let expected = vec![MatchedLine {
+            display_number: 4,
+            line_number: 3,
+            content: "About Rsync".into(),
+            matches: vec![Range { start: 6, end: 11 }],
+            before: vec![ContextLine {
+                display_number: 3,
+                content: "A very interesting note".into(),
+            }],
+            after: vec![ContextLine {
+                display_number: 5,
+                content: "With very interesting things inside".into(),
+            }],
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    pub fn matching_lines_should_find_all_matches() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_2.to_string()).unwrap();
+        let actual = note.matching_lines(&needle_regexp("about|rsync"), &Context::default());
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].matches, vec![0..5, 6..11]);
+
+        let actual = note.matching_lines(&needle_regexp("very|ing"), &Context::default());
+        assert_eq!(actual[1].content, "With very interesting things inside");
+        assert_eq!(actual[1].matches, vec![5..9, 18..21, 24..27]);
+    }
+
+    #[test]
+    pub fn matching_lines_should_merge_context() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), "# Title\na\nb ssh\nc\nd\ne\nf ssh\ng ssh\nh\ni".to_string()).unwrap();
+        let context = Context { before: 2, after: 1 };
+        let numbers = |lines: &[ContextLine]| lines.iter().map(|l| l.display_number).collect::<Vec<usize>>();
+
+        let actual = note.matching_lines(&needle_regexp("ssh"), &context);
+        let windows: Vec<(Vec<usize>, usize, Vec<usize>)> = actual.iter().map(|l| (numbers(&l.before), l.display_number, numbers(&l.after))).collect();
+        assert_eq!(windows, vec![(vec![2], 3, vec![4]), (vec![5, 6], 7, vec![]), (vec![], 8, vec![9])]);
+
+        let actual = note.matching_lines(&needle_regexp("ssh"), &Context { before: 0, after: 0 });
+        assert!(actual.iter().all(|l| l.before.is_empty() && l.after.is_empty()));
+    }
+
+    #[test]
+    pub fn matching_lines_should_keep_empty_lines_inside_context() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), "# Title\n\nssh\n\n\nssh\n\n".to_string()).unwrap();
+        let actual = note.matching_lines(&needle_regexp("ssh"), &Context { before: 2, after: 2 });
+        assert!(actual[0].before.is_empty());
+        assert_eq!(actual[0].after.len(), 2);
+        assert!(actual[1].before.is_empty());
+        assert!(actual[1].after.is_empty());
+    }
+
+    #[test]
+    pub fn merge_overlapping_ranges() {
+        assert_eq!(merge_ranges(vec![6..9, 0..3, 2..5, 9..10, 12..14]), vec![0..5, 6..10, 12..14]);
+    }
+
+    #[test]
+    pub fn matching_lines_should_ignore_front_matter() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_4.to_string()).unwrap();
+        let needle_regex = needle_regexp("tags");
+        assert!(note.matching_lines(&needle_regex, &Context::default()).is_empty());
+
+        let needle_regex = needle_regexp("about");
+        let actual = note.matching_lines(&needle_regex, &Context::default());
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].line_number, 7);
+        assert!(actual[0].before.is_empty());
+    }
+
+    #[test]
+    pub fn query_match() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_2.to_string()).unwrap();
+        let index = SearchIndex::new(Path::new("/tmp/index"));
+        let notes = vec![(note.clone(), PathBuf::from("note-1.txt"))];
+
+        let query = Query::parse("about OR python", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &index, &notes);
+        let actual = note.query_match(&query, Path::new("note-1.txt"), &ranking, &Context::default()).unwrap();
+        assert!(actual.score > 0.0);
+        assert_eq!(actual.explanation.len(), 2);
+        assert_eq!(actual.matched_lines[0].matches, vec![0..5]);
+
+        let query = Query::parse("path:note NOT python", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &index, &notes);
+        let actual = note.query_match(&query, Path::new("note-1.txt"), &ranking, &Context::default()).unwrap();
+        assert_eq!(actual.matched_lines.len(), 3);
+        assert_eq!(actual.matched_lines[0].content, "A very interesting note");
+
+        let query = Query::parse("rsync python", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &index, &notes);
+        assert!(note.query_match(&query, Path::new("note-1.txt"), &ranking, &Context::default()).is_none());
+    }
+
+    #[test]
+    pub fn fuzzy_match() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_2.to_string()).unwrap();
+        let actual = note.fuzzy_match(&["rsnyc".to_string()], &Context::default());
         let expected = SearchMatch {
             id: 0,
-            score: 5,
+            score: 5.0,
             path: "/tmp/note-1.txt".into(),
             title: "# Rsync".into(),
             matched_lines: vec![MatchedLine {
                 display_number: 4,
                 line_number: 3,
                 content: "About Rsync".into(),
-                matched: "Rsync".into(),
-                previous: Some("A very interesting note".into()),
-                next: Some("With very interesting things inside".into()),
+                matches: vec![Range { start: 6, end: 11 }],
+                before: vec![ContextLine {
+                    display_number: 3,
+                    content: "A very interesting note".into(),
+                }],
+                after: vec![ContextLine {
+                    display_number: 5,
+                    content: "With very interesting things inside".into(),
+                }],
             }],
+            explanation: vec![],
         };
         assert_eq!(actual, expected);
+
+        // Exact matches have a better score
+        assert_eq!(note.fuzzy_match(&["rsync".to_string()], &Context::default()).score, 10.0);
+        assert_eq!(note.fuzzy_match(&["note".to_string()], &Context::default()).score, 7.0);
+    }
+
+    #[test]
+    pub fn fuzzy_match_should_find_all_words() {
+        let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_2.to_string()).unwrap();
+        let actual = note.fuzzy_match(&["rsync".to_string(), "python".to_string()], &Context::default());
+        assert_eq!(actual.score, 0.0);
+        assert!(actual.matched_lines.is_empty());
+        assert_eq!(note.fuzzy_match(&[], &Context::default()).score, 0.0);
     }
 
     #[test]
-    pub fn search_match_only_title() {
+    pub fn matching_lines_only_title() {
         let note = Note::from(0, "/tmp/note-1.txt".into(), SAMPLE_NOTE_3.to_string()).unwrap();
         let needle_regex = needle_regexp("note");
-        let actual = note.search_match(&needle_regex);
-        let expected = SearchMatch {
-            id: 0,
-            score: 4,
-            path: "/tmp/note-1.txt".into(),
-            title: "# What a note !".into(),
-            matched_lines: vec![
-                MatchedLine {
-                    display_number: 3,
-                    line_number: 2,
-                    content: "A very interesting one".into(),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: None,
-                },
-                MatchedLine {
-                    display_number: 4,
-                    line_number: 3,
-                    content: "With very interesting things inside".into(),
-                    matched: "".to_string(),
-                    previous: None,
-                    next: None,
-                },
-            ],
-        };
+        let actual = note.matching_lines(&needle_regex, &Context::default());
+        let expected = vec![
+            MatchedLine {
+                display_number: 3,
+                line_number: 2,
+                content: "A very interesting one".into(),
+                matches: vec![],
+                before: vec![],
+                after: vec![],
+            },
+            MatchedLine {
+                display_number: 4,
+                line_number: 3,
+                content: "With very interesting things inside".into(),
+                matches: vec![],
+                before: vec![],
+                after: vec![],
+            },
+        ];
         assert_eq!(actual, expected);
     }
 }
@@ -0,0 +1,89 @@
+extern crate glob;
+
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+
+use crate::default_error::DefaultError;
+
+/// Designate one or several notes on command line, e.g: 3, 8-12 or 'archive/**/*.md'
+#[derive(Debug, PartialEq)]
+pub enum NoteSelector {
+    Id(usize),
+    Range(usize, usize),
+    /// Glob pattern, matched against note paths relative to repository
+    Glob(String),
+}
+
+impl NoteSelector {
+    pub fn parse(value: &str) -> Result<NoteSelector, DefaultError> {
+        if value.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(NoteSelector::Id(value.parse::<usize>()?));
+        }
+
+        let bounds: Vec<&str> = value.splitn(2, '-').collect();
+        let is_range = bounds.len() == 2 && bounds.iter().all(|b| !b.is_empty() && b.chars().all(|c| c.is_ascii_digit()));
+        if is_range {
+            let (start, end) = (bounds[0].parse::<usize>()?, bounds[1].parse::<usize>()?);
+            if start > end {
+                return Err(DefaultError::new(format!("Invalid range: {}", value)));
+            }
+            return Ok(NoteSelector::Range(start, end));
+        }
+
+        Pattern::new(value).map_err(|e| DefaultError::new(format!("Invalid pattern {}: {}", value, e.msg)))?;
+        Ok(NoteSelector::Glob(value.to_string()))
+    }
+
+    /// Path must be relative to repository
+    pub fn matches(&self, id: usize, path: &Path) -> bool {
+        match self {
+            NoteSelector::Id(selected) => id == *selected,
+            NoteSelector::Range(start, end) => id >= *start && id <= *end,
+            NoteSelector::Glob(pattern) => {
+                let options = MatchOptions {
+                    case_sensitive: true,
+                    require_literal_separator: true,
+                    require_literal_leading_dot: false,
+                };
+                Pattern::new(pattern).map(|p| p.matches_path_with(path, options)).unwrap_or(false)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    pub fn parse() {
+        assert_eq!(NoteSelector::parse("3").unwrap(), NoteSelector::Id(3));
+        assert_eq!(NoteSelector::parse("8-12").unwrap(), NoteSelector::Range(8, 12));
+        assert_eq!(
+            NoteSelector::parse("archive/**/*.md").unwrap(),
+            NoteSelector::Glob("archive/**/*.md".to_string())
+        );
+        assert_eq!(NoteSelector::parse("a-b.md").unwrap(), NoteSelector::Glob("a-b.md".to_string()));
+    }
+
+    #[test]
+    pub fn parse_should_fail() {
+        assert_eq!(NoteSelector::parse("12-8").unwrap_err().message, "Invalid range: 12-8");
+        assert!(NoteSelector::parse("archive/***.md").unwrap_err().message.starts_with("Invalid pattern"));
+    }
+
+    #[test]
+    pub fn matches() {
+        let path = PathBuf::from("archive/2020/a.md");
+        assert!(NoteSelector::Id(3).matches(3, &path));
+        assert!(!NoteSelector::Id(3).matches(4, &path));
+        assert!(NoteSelector::Range(3, 5).matches(5, &path));
+        assert!(!NoteSelector::Range(3, 5).matches(6, &path));
+        assert!(NoteSelector::Glob("archive/**/*.md".to_string()).matches(1, &path));
+        assert!(NoteSelector::Glob("archive/**/*.md".to_string()).matches(1, &PathBuf::from("archive/a.md")));
+        assert!(!NoteSelector::Glob("archive/*.md".to_string()).matches(1, &path));
+        assert!(!NoteSelector::Glob("*.md".to_string()).matches(1, &path));
+    }
+}
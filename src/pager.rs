@@ -0,0 +1,80 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use terminal_size::{terminal_size, Height};
+
+use crate::default_error::DefaultError;
+
+/// Height of terminal, if stdout is a terminal
+pub fn terminal_height() -> Option<usize> {
+    terminal_size().map(|(_, Height(height))| height as usize)
+}
+
+/// A pager is only used if output does not fit in terminal. An empty pager command disables it.
+pub fn needs_pager(pager: &str, text: &str, terminal_height: Option<usize>) -> bool {
+    match terminal_height {
+        Some(height) => !pager.trim().is_empty() && text.lines().count() >= height,
+        None => false,
+    }
+}
+
+/// Text must only be displayed again without pager if pager did not start, otherwise it was already shown
+#[derive(Debug)]
+pub enum PagerError {
+    NotStarted(DefaultError),
+    Failed(DefaultError),
+}
+
+/// Display text with specified pager command, e.g: less -R
+pub fn page(pager: &str, text: &str) -> Result<(), PagerError> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(pager)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| PagerError::NotStarted(e.into()))?;
+    // Pager can be closed before the end of text, e.g. if user quits less
+    let written = match child.stdin.as_mut() {
+        Some(stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+        None => false,
+    };
+    let status = child.wait().map_err(|e| PagerError::Failed(e.into()))?;
+    let error = DefaultError::new(format!("Pager '{}' failed", pager));
+    // Shell exits with 126 or 127 if pager command cannot be run
+    match (status.success(), status.code()) {
+        (true, _) => Ok(()),
+        (false, Some(126)) | (false, Some(127)) => Err(PagerError::NotStarted(error)),
+        (false, _) if !written => Err(PagerError::NotStarted(error)),
+        (false, _) => Err(PagerError::Failed(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::new_test_root;
+    use std::fs;
+
+    #[test]
+    pub fn needs_pager_only_for_long_output_in_terminal() {
+        let text = "line\n".repeat(30);
+        assert!(needs_pager("less -R", &text, Some(24)));
+        assert!(!needs_pager("less -R", &text, Some(50)));
+        assert!(!needs_pager("less -R", &text, None));
+        assert!(!needs_pager("", &text, Some(24)));
+    }
+
+    #[test]
+    pub fn page() {
+        let path = new_test_root().join("paged");
+        super::page(&format!("cat > {}", path.to_str().unwrap()), "\u{1b}[32mcolored\u{1b}[0m\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "\u{1b}[32mcolored\u{1b}[0m\n");
+    }
+
+    #[test]
+    pub fn page_should_fail() {
+        assert!(matches!(super::page("cat > /dev/null; exit 1", "text"), Err(PagerError::Failed(_))));
+        assert!(matches!(super::page("nonexistentpager", "text"), Err(PagerError::NotStarted(_))));
+        assert!(matches!(super::page("/dev/null", "text"), Err(PagerError::NotStarted(_))));
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use colored::*;
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::command_handler::PickAction;
+use crate::default_error::DefaultError;
+use crate::fuzzy;
+use crate::note::Note;
+
+/// Smallest terminal where list, preview and status line can be drawn
+const MIN_WIDTH: usize = 10;
+const MIN_HEIGHT: usize = 3;
+
+/// Result of a key press in picker
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Continue,
+    Cancel,
+    /// Index of picked note in candidates, and action to apply
+    Pick(usize, PickAction),
+}
+
+/// Interactive fuzzy finder over note titles and paths. Candidates are notes with their paths
+/// relative to repository. The selected note is previewed next to the list.
+pub struct Picker<'a> {
+    candidates: &'a [(Note, PathBuf)],
+    actions: &'a [PickAction],
+    query: String,
+    /// Indexes of candidates matching query, best matches first
+    matches: Vec<usize>,
+    selected: usize,
+    action: usize,
+}
+
+impl<'a> Picker<'a> {
+    pub fn new(candidates: &'a [(Note, PathBuf)], actions: &'a [PickAction]) -> Picker<'a> {
+        Picker {
+            candidates,
+            actions,
+            query: String::new(),
+            matches: (0..candidates.len()).collect(),
+            selected: 0,
+            action: 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Outcome {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Outcome::Cancel,
+            KeyCode::Char('c') if control => return Outcome::Cancel,
+            KeyCode::Enter => {
+                if let Some(index) = self.matches.get(self.selected) {
+                    return Outcome::Pick(*index, self.actions[self.action]);
+                }
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('p') | KeyCode::Char('k') if control => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.select_next(),
+            KeyCode::Char('n') | KeyCode::Char('j') if control => self.select_next(),
+            KeyCode::Tab => self.action = (self.action + 1) % self.actions.len(),
+            KeyCode::BackTab => self.action = (self.action + self.actions.len() - 1) % self.actions.len(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_matches();
+            }
+            KeyCode::Char(c) if !control => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        Outcome::Continue
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = filter(self.candidates, &self.query);
+        self.selected = 0;
+    }
+
+    /// Display picker on whole terminal, then wait for user choice
+    pub fn run(&mut self) -> Result<Outcome, DefaultError> {
+        let _terminal = RawTerminal::enter()?;
+        loop {
+            self.render()?;
+            if let Event::Key(key) = read()? {
+                match self.handle_key(key) {
+                    Outcome::Continue => continue,
+                    outcome => return Ok(outcome),
+                }
+            }
+        }
+    }
+
+    fn render(&self) -> Result<(), DefaultError> {
+        let (width, height) = crossterm::terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        check_size(width, height)?;
+        let list_width = width / 2;
+        let rows = height.saturating_sub(2);
+        // List is scrolled so that selected note stays visible
+        let offset = (self.selected + 1).saturating_sub(rows);
+        let preview: Vec<&String> = match self.matches.get(self.selected) {
+            Some(index) => self.candidates[*index].0.raw.iter().collect(),
+            None => vec![],
+        };
+
+        let mut out = stdout();
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        write!(out, "> {}", self.query)?;
+        for row in 0..rows {
+            let item = match self.matches.get(offset + row) {
+                Some(index) => {
+                    let (note, path) = &self.candidates[*index];
+                    let text = truncate(
+                        &format!("@{} {} ({})", note.id, note.title, path.to_str().unwrap_or("")),
+                        list_width.saturating_sub(1),
+                    );
+                    match offset + row == self.selected {
+                        true => format!("{:width$}", text, width = list_width.saturating_sub(1)).reversed().to_string(),
+                        false => text,
+                    }
+                }
+                None => String::new(),
+            };
+            let line = preview.get(row).map(|l| truncate(l, width.saturating_sub(list_width + 2))).unwrap_or_default();
+            queue!(out, MoveTo(0, (row + 1) as u16))?;
+            write!(out, "{}", item)?;
+            queue!(out, MoveTo(list_width as u16, (row + 1) as u16))?;
+            write!(out, "{} {}", "│".dimmed(), line)?;
+        }
+
+        let actions: Vec<String> = self
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(i, action)| match i == self.action {
+                true => format!("[{}]", action.name()).bold().to_string(),
+                false => action.name().to_string(),
+            })
+            .collect();
+        let help = "Enter: apply, Tab: change action, Esc: cancel".dimmed();
+        queue!(out, MoveTo(0, height.saturating_sub(1) as u16))?;
+        write!(out, "{}/{} {} {}", self.matches.len(), self.candidates.len(), actions.join(" "), help)?;
+        queue!(out, MoveTo((self.query.chars().count() + 2) as u16, 0))?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Terminal is restored when dropped, even if picker fails
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<RawTerminal, DefaultError> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen)?;
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), LeaveAlternateScreen, Show);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Return indexes of candidates whose title and path contain all words of query, tolerating typos.
+/// Candidates with less typos come first.
+pub fn filter(candidates: &[(Note, PathBuf)], query: &str) -> Vec<usize> {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let mut matches: Vec<(usize, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (note, path))| {
+            let text = format!("{} {}", note.title, path.to_str().unwrap_or(""));
+            let distances: Option<Vec<usize>> = words.iter().map(|word| fuzzy::find(word, &text).map(|m| m.distance)).collect();
+            distances.map(|d| (index, d.iter().sum()))
+        })
+        .collect();
+    matches.sort_by_key(|(_, distance)| *distance);
+    matches.into_iter().map(|(index, _)| index).collect()
+}
+
+fn check_size(width: usize, height: usize) -> Result<(), DefaultError> {
+    if width < MIN_WIDTH || height < MIN_HEIGHT {
+        return Err(DefaultError::new(format!(
+            "Terminal is too small to display picker, it must have at least {} columns and {} rows",
+            MIN_WIDTH, MIN_HEIGHT
+        )));
+    }
+    Ok(())
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<(Note, PathBuf)> {
+        vec![
+            (
+                Note::from(1, "/repo/tools/rsync.md".into(), "# Rsync tips".to_string()).unwrap(),
+                "tools/rsync.md".into(),
+            ),
+            (Note::from(2, "/repo/tools/ssh.md".into(), "# SSH".to_string()).unwrap(), "tools/ssh.md".into()),
+            (Note::from(3, "/repo/rust.md".into(), "# Rust".to_string()).unwrap(), "rust.md".into()),
+        ]
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    pub fn filter_candidates() {
+        let candidates = candidates();
+        assert_eq!(filter(&candidates, ""), vec![0, 1, 2]);
+        assert_eq!(filter(&candidates, "tools"), vec![0, 1]);
+        assert_eq!(filter(&candidates, "tools rsnyc"), vec![0]);
+        assert_eq!(filter(&candidates, "python"), Vec::<usize>::new());
+    }
+
+    #[test]
+    pub fn filter_should_rank_exact_matches_first() {
+        let candidates = vec![
+            (Note::from(1, "/repo/sync.md".into(), "# Rsnyc options".to_string()).unwrap(), "sync.md".into()),
+            (Note::from(2, "/repo/rsync.md".into(), "# Rsync".to_string()).unwrap(), "rsync.md".into()),
+        ];
+        assert_eq!(filter(&candidates, "rsync"), vec![1, 0]);
+    }
+
+    #[test]
+    pub fn check_terminal_size() {
+        assert!(check_size(80, 24).is_ok());
+        assert!(check_size(0, 0).is_err());
+        assert!(check_size(80, 2).is_err());
+        assert!(check_size(9, 24).is_err());
+    }
+
+    #[test]
+    pub fn pick_with_keys() {
+        let candidates = candidates();
+        let actions = vec![PickAction::Edit, PickAction::Show, PickAction::Delete];
+        let mut picker = Picker::new(&candidates, &actions);
+
+        assert_eq!(picker.handle_key(key(KeyCode::Char('t'))), Outcome::Continue);
+        assert_eq!(picker.handle_key(key(KeyCode::Char('o'))), Outcome::Continue);
+        picker.handle_key(key(KeyCode::Down));
+        picker.handle_key(key(KeyCode::Down));
+        picker.handle_key(key(KeyCode::BackTab));
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), Outcome::Pick(1, PickAction::Delete));
+
+        picker.handle_key(key(KeyCode::Backspace));
+        picker.handle_key(key(KeyCode::Backspace));
+        picker.handle_key(key(KeyCode::Tab));
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), Outcome::Pick(0, PickAction::Edit));
+    }
+
+    #[test]
+    pub fn cancel_picker() {
+        let candidates = candidates();
+        let actions = vec![PickAction::Edit];
+        let mut picker = Picker::new(&candidates, &actions);
+
+        for c in "python".chars() {
+            picker.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), Outcome::Continue);
+        assert_eq!(picker.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Outcome::Cancel);
+        assert_eq!(picker.handle_key(key(KeyCode::Esc)), Outcome::Cancel);
+    }
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[cfg(test)]
+use mockall::automock;
+
+use crate::command_handler::PickAction;
+use crate::default_error::DefaultError;
+use crate::note::Note;
+use crate::pager;
+use crate::picker::{Outcome, Picker};
+
+#[cfg_attr(test, automock)]
+pub trait Prompt {
+    /// Ask a yes/no question to user, return true if user answered yes
+    fn confirm(&self, message: &str) -> Result<bool, DefaultError>;
+    /// Ask user for a line of text, return it trimmed
+    fn input(&self, message: &str) -> Result<String, DefaultError>;
+    /// Let user choose a note and an action in a fuzzy finder. Candidates are notes with their
+    /// paths relative to repository. Return index of chosen note in candidates, or None if cancelled.
+    fn pick_note(&self, candidates: &[(Note, PathBuf)], actions: &[PickAction]) -> Result<Option<(usize, PickAction)>, DefaultError>;
+}
+
+/// Questions are written on standard error, so that standard output only contains results of
+/// commands, e.g. JSON documents
+pub struct PromptImpl;
+
+impl PromptImpl {
+    pub fn new() -> Self {
+        PromptImpl {}
+    }
+}
+
+impl Default for PromptImpl {
+    fn default() -> Self {
+        PromptImpl::new()
+    }
+}
+
+impl Prompt for PromptImpl {
+    fn confirm(&self, message: &str) -> Result<bool, DefaultError> {
+        eprint!("{} [y/N] ", message);
+        io::stderr().flush()?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        Ok(is_yes(&answer))
+    }
+
+    fn input(&self, message: &str) -> Result<String, DefaultError> {
+        eprint!("{}: ", message);
+        io::stderr().flush()?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        Ok(answer.trim().to_string())
+    }
+
+    fn pick_note(&self, candidates: &[(Note, PathBuf)], actions: &[PickAction]) -> Result<Option<(usize, PickAction)>, DefaultError> {
+        if pager::terminal_height().is_none() {
+            return Err(DefaultError::new("A terminal is required to pick a note".to_string()));
+        }
+        match Picker::new(candidates, actions).run()? {
+            Outcome::Pick(index, action) => Ok(Some((index, action))),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    answer == "y" || answer == "yes"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_yes_should_accept() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes("Yes"));
+    }
+
+    #[test]
+    fn is_yes_should_refuse() {
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("no"));
+        assert!(!is_yes("yeah"));
+    }
+}
@@ -0,0 +1,197 @@
+use std::cmp::max;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::note::Note;
+use crate::search_index::{word_count, SearchIndex};
+use crate::search_match::TermScore;
+use crate::search_query::{Field, Pattern, Query};
+
+lazy_static! {
+    static ref HEADING: Regex = Regex::new("^#{1,6}\\s").unwrap();
+}
+
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+const TITLE_BOOST: f64 = 3.0;
+const HEADING_BOOST: f64 = 2.0;
+const PATH_BOOST: f64 = 1.5;
+
+/// BM25 relevance model. Occurrences of terms are weighted by field: a term found in title is worth
+/// three times a term found in body, headings and path are also boosted. Document frequencies and
+/// lengths come from search index, so that rare terms and short notes are ranked first.
+pub struct Ranking<'a> {
+    terms: Vec<(Option<Field>, &'a Pattern, f64)>,
+    average_length: f64,
+}
+
+impl<'a> Ranking<'a> {
+    /// Candidates are the notes loaded for search, with their paths relative to repository. They are
+    /// used if index is empty, or for terms that cannot be found in index, like phrases or paths.
+    pub fn new(query: &'a Query, index: &SearchIndex, candidates: &[(Note, PathBuf)]) -> Ranking<'a> {
+        let document_count = max(index.document_count(), candidates.len()) as f64;
+        let average_length = match index.document_count() {
+            0 if !candidates.is_empty() => candidates.iter().map(|(n, _)| word_count(&n.content())).sum::<usize>() as f64 / candidates.len() as f64,
+            _ => index.average_length(),
+        };
+
+        let terms = query
+            .ranked_terms()
+            .into_iter()
+            .map(|(field, pattern)| {
+                let in_index = match field {
+                    Some(Field::Path) => 0,
+                    _ => index.document_frequency(pattern.regex()),
+                };
+                let in_candidates = candidates.iter().filter(|(n, p)| occurrences(n, p, field, pattern.regex()).total() > 0).count();
+                let frequency = max(in_index, in_candidates) as f64;
+                let idf = (1.0 + (document_count - frequency + 0.5) / (frequency + 0.5)).ln();
+                (field, pattern, idf)
+            })
+            .collect();
+
+        Ranking { terms, average_length }
+    }
+
+    /// Score of each term for specified note. Path must be relative to repository.
+    pub fn explain(&self, note: &Note, path: &Path) -> Vec<TermScore> {
+        let length = word_count(&note.content()) as f64;
+        let normalization = match self.average_length {
+            average if average > 0.0 => 1.0 - B + B * length / average,
+            _ => 1.0,
+        };
+
+        self.terms
+            .iter()
+            .map(|(field, pattern, idf)| {
+                let found = occurrences(note, path, *field, pattern.regex());
+                let frequency = TITLE_BOOST * found.title as f64 + HEADING_BOOST * found.headings as f64 + PATH_BOOST * found.path as f64 + found.body as f64;
+                let score = idf * frequency * (K1 + 1.0) / (frequency + K1 * normalization);
+                TermScore {
+                    term: pattern.text.clone(),
+                    title: found.title,
+                    headings: found.headings,
+                    path: found.path,
+                    body: found.body,
+                    idf: *idf,
+                    score,
+                }
+            })
+            .collect()
+    }
+}
+
+struct Occurrences {
+    title: usize,
+    headings: usize,
+    path: usize,
+    body: usize,
+}
+
+impl Occurrences {
+    fn total(&self) -> usize {
+        self.title + self.headings + self.path + self.body
+    }
+}
+
+/// Count occurrences of a term in fields of note
+fn occurrences(note: &Note, path: &Path, field: Option<Field>, regex: &Regex) -> Occurrences {
+    let count = |text: &str| regex.find_iter(text).count();
+    let in_field = |searched: Field| field.is_none() || field == Some(searched);
+
+    let title = if in_field(Field::Title) { count(&note.title) } else { 0 };
+    let path = if in_field(Field::Path) { count(path.to_str().unwrap_or("")) } else { 0 };
+    let (mut headings, mut body) = (0, 0);
+    if in_field(Field::Body) {
+        for line in note.body.iter() {
+            match HEADING.is_match(line) {
+                true => headings += count(line),
+                false => body += count(line),
+            }
+        }
+    }
+    Occurrences { title, headings, path, body }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_index::FileStamp;
+    use crate::search_query::SearchOptions;
+    use crate::test_env::new_test_root;
+
+    fn note(id: usize, content: &str) -> (Note, PathBuf) {
+        let path = PathBuf::from(format!("{}.md", id));
+        (Note::from(id, Path::new("/repo").join(&path), content.to_string()).unwrap(), path)
+    }
+
+    fn score(ranking: &Ranking, (note, path): &(Note, PathBuf)) -> f64 {
+        ranking.explain(note, path).iter().map(|t| t.score).sum()
+    }
+
+    #[test]
+    pub fn explain() {
+        let notes = vec![
+            note(1, "# SSH\n\n## Agent\nUse ssh agent"),
+            note(2, "# Rsync\nrsync over ssh"),
+            note(3, "# Rust\nCargo"),
+        ];
+        let query = Query::parse("ssh", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &SearchIndex::new(&new_test_root().join("index")), &notes);
+
+        let explanation = ranking.explain(&notes[0].0, Path::new("tools/ssh.md"));
+        assert_eq!(explanation.len(), 1);
+        assert_eq!(explanation[0].term, "ssh");
+        assert_eq!(
+            (explanation[0].title, explanation[0].headings, explanation[0].path, explanation[0].body),
+            (1, 0, 1, 1)
+        );
+        assert!((explanation[0].idf - (1.0 + 1.5 / 2.5_f64).ln()).abs() < 1e-9);
+        assert!(explanation[0].score > 0.0);
+    }
+
+    #[test]
+    pub fn title_and_headings_are_boosted() {
+        let notes = vec![
+            note(1, "# Tips\nssh"),
+            note(2, "# Tips\n## ssh"),
+            note(3, "# ssh\nTips"),
+            note(4, "# Rust\nCargo"),
+        ];
+        let query = Query::parse("ssh", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &SearchIndex::new(&new_test_root().join("index")), &notes);
+
+        assert!(score(&ranking, &notes[1]) > score(&ranking, &notes[0]));
+        assert!(score(&ranking, &notes[2]) > score(&ranking, &notes[1]));
+        assert_eq!(score(&ranking, &notes[3]), 0.0);
+    }
+
+    #[test]
+    pub fn short_notes_and_rare_terms_are_ranked_first() {
+        let long_note = format!("# Tips\nssh {}", "and many other things ".repeat(20));
+        let notes = vec![note(1, "# Tips\nssh rsync"), note(2, &long_note), note(3, "# Other\nssh")];
+        let query = Query::parse("ssh OR rsync", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &SearchIndex::new(&new_test_root().join("index")), &notes);
+
+        assert!(score(&ranking, &notes[2]) > score(&ranking, &notes[1]));
+        let explanation = ranking.explain(&notes[0].0, &notes[0].1);
+        assert!(explanation[1].idf > explanation[0].idf);
+    }
+
+    #[test]
+    pub fn use_index_statistics() {
+        let mut index = SearchIndex::new(&new_test_root().join("index"));
+        for i in 0..10 {
+            index.update(&PathBuf::from(format!("{}.md", i)), FileStamp { modified: 1, size: 1 }, "# Rust\nCargo");
+        }
+        index.update(&PathBuf::from("ssh.md"), FileStamp { modified: 1, size: 1 }, "# SSH\nssh");
+
+        let notes = vec![note(1, "# SSH\nssh")];
+        let query = Query::parse("ssh", &SearchOptions::default()).unwrap();
+        let ranking = Ranking::new(&query, &index, &notes);
+        let explanation = ranking.explain(&notes[0].0, Path::new("ssh.md"));
+        assert!((explanation[0].idf - (1.0 + 10.5 / 1.5_f64).ln()).abs() < 1e-9);
+    }
+}
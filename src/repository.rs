@@ -2,40 +2,96 @@ extern crate walkdir;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 #[cfg(test)]
 use mockall::automock;
 
 use self::walkdir::{DirEntry, WalkDir};
 use crate::config::Config;
+use crate::config_file::ConfigFile;
 use crate::console_output::ConsoleOutput;
 use crate::default_error::DefaultError;
-use crate::git::Git;
+use crate::git::{Git, LogEntry, RebaseOutcome};
+use crate::id_map::IdMap;
 use crate::note::Note;
+use crate::note_selector::NoteSelector;
+use crate::search_index::{FileStamp, SearchIndex};
+use crate::search_match::HistoryMatch;
 use crate::shell::Shell;
+use serde::Serialize;
 
 #[cfg_attr(test, automock)]
 pub trait Repository {
     fn init(&self) -> Result<ConsoleOutput, DefaultError>;
-    fn new_note(&self, id: usize, path: &str) -> Result<Note, DefaultError>;
+    fn new_note(&self, path: &str) -> Result<Note, DefaultError>;
     fn edit_note(&self, note: &Note) -> Result<ConsoleOutput, DefaultError>;
-    fn find_note_by_id(&self, id: usize) -> Option<Note>;
-    fn load_repository_tree(&self) -> Vec<RepositoryDir>;
-    fn load_notes(&self) -> Vec<Note>;
-    fn delete_note(&self, note: &Note) -> Result<ConsoleOutput, DefaultError>;
+    /// Write notes and commit them all with specified message
+    fn update_notes(&self, notes: &[Note], message: &str) -> Result<ConsoleOutput, DefaultError>;
+    fn find_note_by_id(&self, id: usize) -> Result<Option<Note>, DefaultError>;
+    /// Return notes designated by selectors, in id order. Fail if a selector does not match any note.
+    fn find_notes(&self, selectors: &[NoteSelector]) -> Result<Vec<Note>, DefaultError>;
+    fn load_repository_tree(&self) -> Result<Vec<RepositoryDir>, DefaultError>;
+    /// Same as load_repository_tree(), restricted to a directory relative to repository root.
+    /// An empty path designates the whole repository.
+    fn load_directory_tree(&self, directory: &Path) -> Result<Vec<RepositoryDir>, DefaultError>;
+    fn load_notes(&self) -> Result<Vec<Note>, DefaultError>;
+    /// Load only notes of directory that may contain all specified words, according to search index.
+    /// Index is updated for notes of directory before use. All notes of directory are loaded if no
+    /// word is specified. An empty path designates the whole repository.
+    fn load_notes_containing(&self, words: &[String], directory: &Path, index: &mut SearchIndex) -> Result<Vec<Note>, DefaultError>;
+    /// Path of note relative to repository root
+    fn note_path(&self, note: &Note) -> PathBuf;
+    /// Search index as saved on disk. It is empty if it cannot be read.
+    fn search_index(&self) -> SearchIndex;
+    /// Delete notes in a single commit
+    fn delete_notes(&self, notes: &[Note]) -> Result<ConsoleOutput, DefaultError>;
+    /// Files of directory and its sub directories that are not notes, e.g. images, attachments or
+    /// ignored files. Paths are relative to repository root.
+    fn other_files(&self, directory: &RepositoryDir) -> Vec<PathBuf>;
+    /// Delete directory, its notes and all its sub directories
+    fn delete_directory(&self, directory: &RepositoryDir) -> Result<ConsoleOutput, DefaultError>;
+    /// Move notes in a single commit. If several notes are moved, destination must be a directory.
+    fn move_notes(&self, notes: &[Note], destination: &str) -> Result<ConsoleOutput, DefaultError>;
     fn push_repo(&self) -> Result<ConsoleOutput, DefaultError>;
     fn pull_repo(&self) -> Result<ConsoleOutput, DefaultError>;
+    /// Commit pending changes, pull with rebase, then push if rebase succeeded
+    fn sync_repo(&self) -> Result<SyncResult, DefaultError>;
+    /// Commits that add or remove lines matching pattern, newest first. See Git::log_matching()
+    fn search_history(&self, pattern: &str) -> Result<Vec<HistoryMatch>, DefaultError>;
+    /// Commits that changed note, newest first, including those made before it was moved
+    fn note_history(&self, note: &Note) -> Result<Vec<LogEntry>, DefaultError>;
+    /// Write file as it was at specified revision and commit it. Path is relative to repository root.
+    fn restore_file(&self, path: &Path, revision: &str) -> Result<ConsoleOutput, DefaultError>;
+    /// Changes of paths relative to repository root, or of whole repository if paths are empty. See Git::diff()
+    fn diff(&self, range: &str, paths: &[PathBuf], word: bool) -> Result<String, DefaultError>;
+    /// Diff between note and its content at revision, when its path was the specified one
+    fn restore_preview(&self, note: &Note, revision: &str, path: &Path) -> Result<String, DefaultError>;
+    /// Write content of note at revision, when its path was the specified one, and commit it
+    fn restore_note(&self, note: &Note, revision: &str, path: &Path) -> Result<ConsoleOutput, DefaultError>;
+    /// Write repository configuration file and commit it
+    fn save_config(&self, file: &ConfigFile) -> Result<ConsoleOutput, DefaultError>;
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+pub struct SyncResult {
+    pub output: ConsoleOutput,
+    /// Paths of conflicted files relative to repository root. Nothing was pushed if not empty.
+    pub conflicts: Vec<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct RepositoryDir {
     pub name: String,
+    #[serde(serialize_with = "crate::json_format::lossy_path")]
     pub path: PathBuf,
     pub notes: Vec<Note>,
     pub level: usize,
 }
 
+/// Files of data directory that are generated locally, and must not be versioned
+const IGNORED_DATA_FILES: [&str; 2] = ["ids", "index"];
+
 pub struct RepositoryImpl<'a> {
     config: &'a Config,
     shell: &'a dyn Shell,
@@ -45,7 +101,8 @@ pub struct RepositoryImpl<'a> {
 
 impl<'a> RepositoryImpl<'a> {
     pub fn new(config: &'a Config, shell: &'a dyn Shell, git: &'a dyn Git) -> RepositoryImpl<'a> {
-        let ignored_dirs: Vec<&str> = vec![".git", ".idea"];
+        let mut ignored_dirs: Vec<&str> = vec![".git", ".notes"];
+        ignored_dirs.extend(config.ignored_directories.iter().map(String::as_str));
         RepositoryImpl {
             config,
             shell,
@@ -53,6 +110,164 @@ impl<'a> RepositoryImpl<'a> {
             ignored_dirs,
         }
     }
+
+    fn id_map_path(&self) -> PathBuf {
+        self.config.data_directory.join("ids")
+    }
+
+    /// A missing id map is created, but an invalid one is never replaced: ids would change and could be reused
+    fn load_id_map(&self) -> Result<IdMap, DefaultError> {
+        let path = self.id_map_path();
+        IdMap::load(&path).map_err(|e| {
+            DefaultError::new(format!(
+                "Cannot read note ids from {}: {}. Fix this file, or delete it to assign new ids to all notes",
+                path.to_str().unwrap(),
+                e
+            ))
+        })
+    }
+
+    fn search_index_path(&self) -> PathBuf {
+        self.config.data_directory.join("index")
+    }
+
+    /// Return absolute path of a directory relative to repository root. Directory must exist and must
+    /// not be ignored.
+    fn scope_directory(&self, directory: &Path) -> Result<PathBuf, DefaultError> {
+        let path = self.config.storage_directory.join(directory);
+        let outside = directory.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if outside || !path.is_dir() || self.is_ignored(&path) {
+            return Err(DefaultError::new(format!("Directory {} not found.", directory.to_str().unwrap())));
+        }
+        Ok(path)
+    }
+
+    /// Return paths of all notes of specified directory, in the same order as repository tree
+    fn note_paths(&self, root: &Path) -> Vec<PathBuf> {
+        WalkDir::new(root)
+            .sort_by(|a, b| a.path().cmp(b.path()))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir() && !self.is_ignored(e.path()))
+            .flat_map(|dir| {
+                WalkDir::new(dir.path())
+                    .max_depth(1)
+                    .sort_by(|a, b| a.path().cmp(b.path()))
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| !self.is_ignored(e.path()) && e.path().is_file() && self.is_note(e.path()))
+                    .map(|e| e.path().to_path_buf())
+                    .collect::<Vec<PathBuf>>()
+            })
+            .collect()
+    }
+
+    fn relative_path<'b>(&self, path: &'b Path) -> &'b Path {
+        path.strip_prefix(&self.config.storage_directory).unwrap_or(path)
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.relative_path(path)
+            .components()
+            .any(|c| self.ignored_dirs.iter().any(|dir| c.as_os_str().to_str() == Some(*dir)))
+    }
+
+    fn is_note(&self, path: &Path) -> bool {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) => self.config.extensions.iter().any(|e| e == extension),
+            None => false,
+        }
+    }
+
+    /// Commit changes of notes, according to auto commit and auto push settings
+    fn commit_note(&self, note: &Note, message: &str) -> Result<ConsoleOutput, DefaultError> {
+        if !self.config.auto_commit {
+            return Ok(ConsoleOutput::empty());
+        }
+        let mut out = self.git.commit(note, message)?;
+        if self.config.auto_push {
+            out.append(self.git.push()?);
+        }
+        Ok(out)
+    }
+
+    /// Commit changes of paths, according to auto commit and auto push settings
+    fn commit_paths(&self, paths: &[PathBuf], message: &str) -> Result<ConsoleOutput, DefaultError> {
+        if !self.config.auto_commit {
+            return Ok(ConsoleOutput::empty());
+        }
+        let mut out = self.git.commit_paths(paths, message)?;
+        if self.config.auto_push {
+            out.append(self.git.push()?);
+        }
+        Ok(out)
+    }
+
+    /// Destination can be a directory, ending with a slash or existing, or a note path.
+    /// Destination is relative to repository root.
+    fn move_destination(&self, note: &Note, destination: &str, is_directory: bool) -> Result<PathBuf, DefaultError> {
+        // Absolute paths and parent directories could designate a path outside of repository
+        let outside = Path::new(destination)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if outside {
+            return Err(DefaultError::new(format!("Destination must be inside repository: {}", destination)));
+        }
+
+        let mut path: PathBuf = [self.config.storage_directory.to_str().unwrap(), destination].iter().collect();
+        if is_directory || destination.ends_with('/') || path.is_dir() {
+            path.push(note.path.file_name().unwrap());
+        } else if !self.is_note(&path) {
+            path.set_file_name(format!("{}.{}", path.file_name().unwrap().to_str().unwrap(), self.config.extensions[0]));
+        }
+
+        if path.exists() {
+            return Err(DefaultError::new(format!("Already exists: {}", path.to_str().unwrap())));
+        }
+        Ok(path)
+    }
+
+    /// Index notes changed by a command, so that next search does not need to read them. Paths are
+    /// absolute, paths of files that do not exist anymore are removed from index.
+    fn update_search_index(&self, paths: &[PathBuf]) {
+        let mut index = self.search_index();
+        for path in paths {
+            let relative_path = self.relative_path(path).to_path_buf();
+            match (FileStamp::of(path), fs::read_to_string(path)) {
+                (Ok(stamp), Ok(content)) => index.update(&relative_path, stamp, &content),
+                _ => index.remove(&relative_path),
+            }
+        }
+        // Index is only an optimization, it will be updated on next search if it cannot be saved
+        let _ = index.save();
+    }
+
+    /// Data directory contains files that must not be versioned, like note ids or search index
+    fn init_data_directory(&self) -> Result<ConsoleOutput, DefaultError> {
+        let gitignore = self.config.data_directory.join(".gitignore");
+        if !gitignore.exists() {
+            fs::create_dir_all(&self.config.data_directory)?;
+            fs::write(&gitignore, format!("{}\n", IGNORED_DATA_FILES.join("\n")))?;
+            return self.git.commit_paths(&[gitignore], "Create notes data directory");
+        }
+
+        // Data directories created by previous versions may miss some entries
+        let mut content = fs::read_to_string(&gitignore)?;
+        let missing: Vec<&str> = IGNORED_DATA_FILES
+            .iter()
+            .filter(|f| !content.lines().any(|l| l.trim() == **f))
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return Ok(ConsoleOutput::empty());
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!("{}\n", missing.join("\n")));
+        fs::write(&gitignore, content)?;
+        self.git.commit_paths(&[gitignore], "Update notes data directory")
+    }
 }
 
 impl<'a> Repository for RepositoryImpl<'a> {
@@ -67,10 +282,11 @@ impl<'a> Repository for RepositoryImpl<'a> {
             file.write_all(note.content().as_bytes())?;
             output.append(self.git.commit(&note, "Create note template")?);
         }
+        output.append(self.init_data_directory()?);
         Ok(output)
     }
 
-    fn new_note(&self, id: usize, partial_path: &str) -> Result<Note, DefaultError> {
+    fn new_note(&self, partial_path: &str) -> Result<Note, DefaultError> {
         let path: PathBuf = [self.config.storage_directory.to_str().unwrap(), partial_path].iter().collect();
 
         if path.exists() {
@@ -80,6 +296,11 @@ impl<'a> Repository for RepositoryImpl<'a> {
         fs::create_dir_all(path.parent().unwrap())?;
         fs::copy(&self.config.template_path, &path)?;
 
+        let mut id_map = self.load_id_map()?;
+        let id = id_map.id_for(self.relative_path(&path));
+        id_map.save()?;
+
+        self.update_search_index(std::slice::from_ref(&path));
         let content = fs::read_to_string(&path)?;
         let note = Note::from(id, path, content)?;
         Ok(note)
@@ -88,34 +309,70 @@ impl<'a> Repository for RepositoryImpl<'a> {
     fn edit_note(&self, note: &Note) -> Result<ConsoleOutput, DefaultError> {
         let mut out = ConsoleOutput::empty();
         let path = note.path.to_str().unwrap();
-        self.shell.execute_interactive_in_repo(format!("$EDITOR {}", path).as_str())?;
+        let editor = self.config.editor.as_deref().unwrap_or("$EDITOR");
+        self.shell.execute_interactive_in_repo(format!("{} {}", editor, path).as_str())?;
+        self.update_search_index(std::slice::from_ref(&note.path));
         let file_has_changed = self.git.has_changed(note);
         if file_has_changed {
             let message = format!("Update note {}", note.path.file_name().unwrap().to_str().unwrap());
-            out.append(self.git.commit(&note, message.as_str())?);
+            out.append(self.commit_note(&note, message.as_str())?);
         }
         Ok(out)
     }
 
-    fn find_note_by_id(&self, id: usize) -> Option<Note> {
-        let notes = self.load_notes();
-        notes.get(id - 1).map(|note| (*note).clone())
+    fn update_notes(&self, notes: &[Note], message: &str) -> Result<ConsoleOutput, DefaultError> {
+        for note in notes {
+            fs::write(&note.path, note.content())?;
+        }
+        let paths: Vec<PathBuf> = notes.iter().map(|n| n.path.clone()).collect();
+        self.update_search_index(&paths);
+        self.commit_paths(&paths, message)
+    }
+
+    fn find_note_by_id(&self, id: usize) -> Result<Option<Note>, DefaultError> {
+        Ok(self.load_notes()?.into_iter().find(|note| note.id == id))
+    }
+
+    fn find_notes(&self, selectors: &[NoteSelector]) -> Result<Vec<Note>, DefaultError> {
+        let notes = self.load_notes()?;
+        for selector in selectors {
+            if !notes.iter().any(|n| selector.matches(n.id, self.relative_path(&n.path))) {
+                let message = match selector {
+                    NoteSelector::Id(id) => format!("Note with id {} not found.", id),
+                    NoteSelector::Range(start, end) => format!("No note found with id between {} and {}.", start, end),
+                    NoteSelector::Glob(pattern) => format!("No note matches {}.", pattern),
+                };
+                return Err(DefaultError::new(message));
+            }
+        }
+
+        let mut selected: Vec<Note> = notes
+            .into_iter()
+            .filter(|n| selectors.iter().any(|s| s.matches(n.id, self.relative_path(&n.path))))
+            .collect();
+        selected.sort_by_key(|n| n.id);
+        Ok(selected)
+    }
+
+    /// This method loads all notes sorted by path. Note ids are persisted in data directory,
+    /// so that they do not change when notes are added, moved or deleted.
+    fn load_repository_tree(&self) -> Result<Vec<RepositoryDir>, DefaultError> {
+        self.load_directory_tree(Path::new(""))
     }
 
-    /// This method loads all notes sorted so that the ids are correctly displayed in tree view
-    fn load_repository_tree(&self) -> Vec<RepositoryDir> {
-        let directories = WalkDir::new(&self.config.storage_directory)
+    /// Ids of notes outside of directory are kept, so that they do not change
+    fn load_directory_tree(&self, directory: &Path) -> Result<Vec<RepositoryDir>, DefaultError> {
+        let root = self.scope_directory(directory)?;
+        let directories = WalkDir::new(&root)
             .sort_by(|a, b| a.path().cmp(&b.path()))
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| {
-                let is_directory = e.path().is_dir();
-                let not_ignored = self.ignored_dirs.iter().find(|dir| e.path().to_str().unwrap().contains(*dir)).is_none();
-                is_directory && not_ignored
-            })
+            .filter(|e| e.path().is_dir() && !self.is_ignored(e.path()))
             .collect::<Vec<DirEntry>>();
 
-        let mut current_note_id = 0;
+        let mut id_map = self.load_id_map()?;
+        let mut existing_notes: Vec<PathBuf> = vec![];
+
         let notes: Vec<(PathBuf, Vec<Note>)> = directories
             .iter()
             .map(|dir| {
@@ -124,26 +381,30 @@ impl<'a> Repository for RepositoryImpl<'a> {
                     .sort_by(|a, b| a.path().cmp(&b.path()))
                     .into_iter()
                     .filter_map(|e| e.ok())
-                    .filter(|e| {
-                        let not_ignored = self.ignored_dirs.iter().find(|dir| e.path().to_str().unwrap().contains(*dir)).is_none();
-                        let is_markdown = e.path().to_str().unwrap().ends_with(".md");
-                        not_ignored && is_markdown
+                    .filter(|e| !self.is_ignored(e.path()) && e.path().is_file() && self.is_note(e.path()))
+                    .map(|entry| {
+                        let relative_path = self.relative_path(entry.path()).to_path_buf();
+                        let id = id_map.id_for(&relative_path);
+                        existing_notes.push(relative_path);
+                        (id, entry)
                     })
-                    .map(|file| {
-                        current_note_id += 1;
-                        (current_note_id, file)
-                    })
-                    .filter_map(|(index, entry)| Note::from_file(index, entry.path().to_path_buf()).ok())
+                    .filter_map(|(id, entry)| Note::from_file(id, entry.path().to_path_buf()).ok())
                     .collect();
                 (dir.path().to_path_buf(), notes)
             })
             .collect();
 
-        let repo_level = self.config.storage_directory.iter().count();
-        notes
+        if root == self.config.storage_directory {
+            id_map.retain(&existing_notes);
+        }
+        // Ids would not be persistent anymore if they could not be saved
+        id_map.save()?;
+
+        let root_level = root.iter().count();
+        Ok(notes
             .iter()
             .map(|(dir, notes)| {
-                let level = dir.iter().count() - repo_level;
+                let level = dir.iter().count() - root_level;
                 let mut dir_name = String::from(dir.clone().strip_prefix(&self.config.storage_directory).unwrap().to_str().unwrap());
 
                 // If directory does not have a name, it is the top level directory, so we assign full repository path
@@ -158,17 +419,154 @@ impl<'a> Repository for RepositoryImpl<'a> {
                     level,
                 }
             })
+            .collect())
+    }
+
+    fn load_notes(&self) -> Result<Vec<Note>, DefaultError> {
+        Ok(self.load_repository_tree()?.iter().flat_map(|dir| dir.notes.to_vec()).collect())
+    }
+
+    /// Search index is updated for notes modified since last run, then only candidate notes are read
+    fn load_notes_containing(&self, words: &[String], directory: &Path, index: &mut SearchIndex) -> Result<Vec<Note>, DefaultError> {
+        let root = self.scope_directory(directory)?;
+        let whole_repository = root == self.config.storage_directory;
+        let paths = self.note_paths(&root);
+        let relative_paths: Vec<PathBuf> = paths.iter().map(|p| self.relative_path(p).to_path_buf()).collect();
+
+        let mut id_map = self.load_id_map()?;
+        let ids: Vec<usize> = relative_paths.iter().map(|p| id_map.id_for(p)).collect();
+        if whole_repository {
+            id_map.retain(&relative_paths);
+        }
+        id_map.save()?;
+
+        for (path, relative_path) in paths.iter().zip(relative_paths.iter()) {
+            let stamp = match FileStamp::of(path) {
+                Ok(stamp) => stamp,
+                Err(_) => continue,
+            };
+            if !index.is_up_to_date(relative_path, &stamp) {
+                if let Ok(content) = fs::read_to_string(path) {
+                    index.update(relative_path, stamp, &content);
+                }
+            }
+        }
+        if whole_repository {
+            index.retain(&relative_paths);
+        }
+        // Index is only an optimization, it will be updated on next run if it cannot be saved
+        let _ = index.save();
+
+        let candidates = index.candidates(words);
+        Ok(paths
+            .into_iter()
+            .zip(relative_paths.iter())
+            .zip(ids)
+            .filter(|((_, relative_path), _)| words.is_empty() || candidates.contains(*relative_path))
+            .filter_map(|((path, _), id)| Note::from_file(id, path).ok())
+            .collect())
+    }
+
+    fn note_path(&self, note: &Note) -> PathBuf {
+        self.relative_path(&note.path).to_path_buf()
+    }
+
+    fn search_index(&self) -> SearchIndex {
+        SearchIndex::load(&self.search_index_path()).unwrap_or_else(|_| SearchIndex::new(&self.search_index_path()))
+    }
+
+    fn delete_notes(&self, notes: &[Note]) -> Result<ConsoleOutput, DefaultError> {
+        let mut id_map = self.load_id_map()?;
+        for note in notes {
+            fs::remove_file(&note.path)?;
+            id_map.remove(self.relative_path(&note.path));
+        }
+        id_map.save()?;
+
+        let message = match notes {
+            [note] => format!("Delete note {}", note.path.to_str().unwrap()),
+            _ => format!("Delete {} notes", notes.len()),
+        };
+        let paths: Vec<PathBuf> = notes.iter().map(|n| n.path.clone()).collect();
+        self.update_search_index(&paths);
+        self.commit_paths(&paths, &message)
+    }
+
+    fn other_files(&self, directory: &RepositoryDir) -> Vec<PathBuf> {
+        WalkDir::new(&directory.path)
+            .sort_by(|a, b| a.path().cmp(b.path()))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file() && (self.is_ignored(e.path()) || !self.is_note(e.path())))
+            .map(|e| self.relative_path(e.path()).to_path_buf())
             .collect()
     }
 
-    fn load_notes(&self) -> Vec<Note> {
-        self.load_repository_tree().iter().flat_map(|dir| dir.notes.to_vec()).collect()
+    fn delete_directory(&self, directory: &RepositoryDir) -> Result<ConsoleOutput, DefaultError> {
+        if directory.path == self.config.storage_directory || !directory.path.starts_with(&self.config.storage_directory) {
+            return Err(DefaultError::new(format!("Cannot delete directory: {}", directory.path.to_str().unwrap())));
+        }
+
+        fs::remove_dir_all(&directory.path)?;
+
+        let mut id_map = self.load_id_map()?;
+        id_map.remove_directory(self.relative_path(&directory.path));
+        id_map.save()?;
+
+        let mut index = self.search_index();
+        index.remove_directory(self.relative_path(&directory.path));
+        // Index is only an optimization, it will be updated on next search if it cannot be saved
+        let _ = index.save();
+
+        let message = format!("Delete directory {}", self.relative_path(&directory.path).to_str().unwrap());
+        self.commit_paths(std::slice::from_ref(&directory.path), &message)
     }
 
-    fn delete_note(&self, note: &Note) -> Result<ConsoleOutput, DefaultError> {
-        fs::remove_file(&note.path)?;
-        let message = format!("Delete note {}", note.path.to_str().unwrap());
-        self.git.commit(&note, message.as_str())
+    fn move_notes(&self, notes: &[Note], destination: &str) -> Result<ConsoleOutput, DefaultError> {
+        let mut out = ConsoleOutput::empty();
+
+        // All destinations are checked before moving anything
+        let mut targets: Vec<PathBuf> = vec![];
+        for note in notes {
+            let target = self.move_destination(note, destination, notes.len() > 1)?;
+            if targets.contains(&target) {
+                return Err(DefaultError::new(format!("Several notes would be moved to: {}", target.to_str().unwrap())));
+            }
+            targets.push(target);
+        }
+
+        let mut id_map = self.load_id_map()?;
+        for (position, (note, target)) in notes.iter().zip(targets.iter()).enumerate() {
+            let moved = fs::create_dir_all(target.parent().unwrap())
+                .map_err(DefaultError::from)
+                .and_then(|_| self.git.move_path(&note.path, target));
+            match moved {
+                Ok(o) => out.append(o),
+                Err(e) => {
+                    // Notes already moved are moved back, so that nothing changes if one move fails
+                    for (note, target) in notes.iter().zip(targets.iter()).take(position).rev() {
+                        self.git.move_path(target, &note.path)?;
+                    }
+                    return Err(e);
+                }
+            }
+            id_map.rename(self.relative_path(&note.path), self.relative_path(target));
+        }
+        id_map.save()?;
+
+        let message = match (notes, targets.as_slice()) {
+            ([note], [target]) => format!(
+                "Move note {} to {}",
+                self.relative_path(&note.path).to_str().unwrap(),
+                self.relative_path(target).to_str().unwrap()
+            ),
+            _ => format!("Move {} notes to {}", notes.len(), destination),
+        };
+        let mut paths: Vec<PathBuf> = notes.iter().map(|n| n.path.clone()).collect();
+        paths.extend(targets);
+        self.update_search_index(&paths);
+        out.append(self.commit_paths(&paths, &message)?);
+        Ok(out)
     }
 
     fn push_repo(&self) -> Result<ConsoleOutput, DefaultError> {
@@ -178,6 +576,75 @@ impl<'a> Repository for RepositoryImpl<'a> {
     fn pull_repo(&self) -> Result<ConsoleOutput, DefaultError> {
         self.git.pull()
     }
+
+    fn sync_repo(&self) -> Result<SyncResult, DefaultError> {
+        let mut output = ConsoleOutput::empty();
+        if self.git.has_pending_changes()? {
+            output.append(self.git.commit_all("Synchronize notes")?);
+        }
+        match self.git.pull_rebase()? {
+            RebaseOutcome::Done(out) => output.append(out),
+            RebaseOutcome::Conflicts(conflicts) => return Ok(SyncResult { output, conflicts }),
+        }
+        output.append(self.git.push()?);
+        Ok(SyncResult { output, conflicts: vec![] })
+    }
+
+    fn search_history(&self, pattern: &str) -> Result<Vec<HistoryMatch>, DefaultError> {
+        self.git.log_matching(pattern)
+    }
+
+    fn note_history(&self, note: &Note) -> Result<Vec<LogEntry>, DefaultError> {
+        self.git.log_for_path(&self.note_path(note))
+    }
+
+    fn restore_file(&self, path: &Path, revision: &str) -> Result<ConsoleOutput, DefaultError> {
+        let content = self.git.show_file(revision, path)?;
+        let target = self.config.storage_directory.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, content)?;
+        self.update_search_index(std::slice::from_ref(&target));
+        let message = format!("Restore {} from {}", path.to_str().unwrap(), revision);
+        self.commit_paths(&[target], &message)
+    }
+
+    fn diff(&self, range: &str, paths: &[PathBuf], word: bool) -> Result<String, DefaultError> {
+        let mut diff = self.git.diff(range, paths, word)?;
+        // Notes created outside of this tool are not known by Git yet, they differ from all revisions
+        if !range.contains("..") {
+            for path in self.git.untracked_files(paths)? {
+                if self.is_note(&path) && !self.is_ignored(&path) {
+                    diff.push_str(&self.git.diff_untracked(&path, word)?);
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    fn restore_preview(&self, note: &Note, revision: &str, path: &Path) -> Result<String, DefaultError> {
+        let current_path = self.note_path(note);
+        let mut paths = vec![current_path];
+        if !paths.contains(&path.to_path_buf()) {
+            paths.push(path.to_path_buf());
+        }
+        self.git.diff_to_revision(revision, &paths)
+    }
+
+    fn restore_note(&self, note: &Note, revision: &str, path: &Path) -> Result<ConsoleOutput, DefaultError> {
+        let content = self.git.show_file(revision, path)?;
+        fs::write(&note.path, content)?;
+        self.update_search_index(std::slice::from_ref(&note.path));
+        let message = format!("Restore note {} to {}", note.path.file_name().unwrap().to_str().unwrap(), revision);
+        self.commit_note(note, &message)
+    }
+
+    fn save_config(&self, file: &ConfigFile) -> Result<ConsoleOutput, DefaultError> {
+        let path = self.config.repository_config_path();
+        file.save(&path)?;
+        self.commit_paths(&[path], "Update notes configuration")
+    }
 }
 
 #[cfg(test)]
@@ -194,10 +661,7 @@ mod tests {
     pub fn init() {
         let test_root = new_test_root();
         let repo_path = PathBuf::from(format!("{}/test-a/test-b", test_root.to_str().unwrap()));
-        let config = Config {
-            storage_directory: repo_path.clone(),
-            template_path: PathBuf::from(format!("{}/.template.md", &repo_path.to_str().unwrap())),
-        };
+        let config = Config::from_path(&repo_path);
         let shell = ShellImpl::new(&config);
 
         let mut git_mock = MockGit::new();
@@ -207,11 +671,17 @@ mod tests {
             .times(1)
             .withf(|n, msg| n.title.contains("Note template") && msg.contains("Create note template"))
             .returning(|_, _| Ok(ConsoleOutput::empty()));
+        git_mock
+            .expect_commit_paths()
+            .times(1)
+            .withf(|paths, msg| paths.len() == 1 && paths[0].ends_with(".notes/.gitignore") && msg == "Create notes data directory")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
 
         let repository = RepositoryImpl::new(&config, &shell, &git_mock);
 
         let result = repository.init();
         assert!(result.is_ok());
+        assert!(config.data_directory.join(".gitignore").exists());
 
         let template_content = fs::read_to_string(config.template_path).unwrap();
         assert!(template_content.contains("Note template"));
@@ -225,7 +695,7 @@ mod tests {
         let repository = RepositoryImpl::new(&config, &shell, &git);
 
         let partial_path = "test-a/test-b/-test-c/test.md";
-        let result = repository.new_note(99, partial_path);
+        let result = repository.new_note(partial_path);
         assert!(result.is_ok());
         assert_eq!(result.unwrap().title, "# Note template".to_string());
     }
@@ -238,28 +708,27 @@ mod tests {
         let repository = RepositoryImpl::new(&config, &shell, &git);
 
         let partial_path = "test-a/test-b/-test-c/test.md";
-        let result = repository.new_note(99, partial_path);
+        let result = repository.new_note(partial_path);
         assert!(result.is_ok());
 
-        let result = repository.new_note(99, partial_path);
+        let result = repository.new_note(partial_path);
         assert!(result.is_err());
         assert!(result.err().unwrap().message.contains("Already exists"));
     }
 
     #[test]
     pub fn edit_note() {
+        let test_root = new_test_root();
         let fake_note = Note {
             id: 0,
             title: "Fake note".into(),
-            path: "/tmp/fake-note.md".into(),
+            path: test_root.join("fake-note.md"),
+            front_matter: None,
             raw: vec![],
             body: vec![],
         };
 
-        let config = Config {
-            storage_directory: "/tmp".into(),
-            template_path: "/tmp".into(),
-        };
+        let config = Config::from_path(&test_root);
 
         let mut shell_mock = MockShell::new();
 
@@ -289,18 +758,17 @@ mod tests {
 
     #[test]
     pub fn edit_note_should_not_commit() {
+        let test_root = new_test_root();
         let fake_note = Note {
             id: 0,
             title: "Fake note".into(),
-            path: "/tmp/fake-note.md".into(),
+            path: test_root.join("fake-note.md"),
+            front_matter: None,
             raw: vec![],
             body: vec![],
         };
 
-        let config = Config {
-            storage_directory: "/tmp".into(),
-            template_path: "/tmp".into(),
-        };
+        let config = Config::from_path(&test_root);
 
         let mut shell_mock = MockShell::new();
 
@@ -320,6 +788,27 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    pub fn update_notes() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+
+        let note_a = Note::from(2, config.storage_directory.join("a.md"), "# Updated note\n".to_string()).unwrap();
+        let note_b = Note::from(3, config.storage_directory.join("b.md"), "# Other note\n".to_string()).unwrap();
+        let mut git_mock = MockGit::new();
+        let exp_paths = vec![note_a.path.clone(), note_b.path.clone()];
+        git_mock
+            .expect_commit_paths()
+            .times(1)
+            .withf(move |paths, m| paths == exp_paths.as_slice() && m == "Add tag")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let repository = RepositoryImpl::new(&config, &shell, &git_mock);
+        repository.update_notes(&[note_a.clone(), note_b.clone()], "Add tag").unwrap();
+        assert_eq!(fs::read_to_string(&note_a.path).unwrap(), "# Updated note\n");
+        assert_eq!(fs::read_to_string(&note_b.path).unwrap(), "# Other note\n");
+    }
+
     #[test]
     pub fn find_note_by_id() {
         let config = new_sample_repo();
@@ -327,11 +816,530 @@ mod tests {
         let git = GitImpl::new(&shell);
         let repository = RepositoryImpl::new(&config, &shell, &git);
 
-        let result = repository.find_note_by_id(2);
+        let result = repository.find_note_by_id(2).unwrap();
         assert!(result.is_some());
         assert_eq!(result.unwrap().title, "# test/assets/sample-repo/a.md".to_string());
     }
 
+    #[test]
+    pub fn find_note_by_id_should_return_nothing() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        assert!(repository.find_note_by_id(0).unwrap().is_none());
+        assert!(repository.find_note_by_id(999).unwrap().is_none());
+    }
+
+    #[test]
+    pub fn load_notes_should_fail_if_ids_are_invalid() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.load_notes().unwrap();
+        let path = config.data_directory.join("ids");
+        let content = format!("{}garbage\n", fs::read_to_string(&path).unwrap());
+        fs::write(&path, &content).unwrap();
+
+        let message = repository.load_notes().unwrap_err().message;
+        assert!(message.starts_with(&format!("Cannot read note ids from {}", path.to_str().unwrap())));
+        assert!(repository.load_notes_containing(&[], Path::new(""), &mut repository.search_index()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    }
+
+    #[test]
+    pub fn load_notes_should_fail_if_ids_cannot_be_saved() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        // A directory cannot be read nor written as a file
+        fs::create_dir_all(config.data_directory.join("ids")).unwrap();
+
+        assert!(repository.load_notes().is_err());
+        assert!(repository.load_notes_containing(&[], Path::new(""), &mut repository.search_index()).is_err());
+    }
+
+    #[test]
+    pub fn find_notes() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let selectors = vec![NoteSelector::Glob("a/**/*.md".to_string()), NoteSelector::Id(2), NoteSelector::Range(2, 3)];
+        let ids: Vec<usize> = repository.find_notes(&selectors).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    pub fn find_notes_should_fail_if_selector_matches_nothing() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let result = repository.find_notes(&[NoteSelector::Id(2), NoteSelector::Id(999)]);
+        assert_eq!(result.unwrap_err().message, "Note with id 999 not found.");
+        let result = repository.find_notes(&[NoteSelector::Glob("z/*.md".to_string())]);
+        assert_eq!(result.unwrap_err().message, "No note matches z/*.md.");
+    }
+
+    #[test]
+    pub fn delete_notes() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        let notes = repository.find_notes(&[NoteSelector::Range(2, 3)]).unwrap();
+        repository.delete_notes(&notes).unwrap();
+
+        assert!(notes.iter().all(|n| !n.path.exists()));
+        let status = shell.execute_in_repo("git status --porcelain").unwrap();
+        assert_eq!(status.stdout, "");
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, "Delete 2 notes\n");
+    }
+
+    #[test]
+    pub fn search_index_should_follow_note_changes() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+        let words = vec!["zanzibar".to_string()];
+
+        let mut notes = repository.find_notes(&[NoteSelector::parse("a.md").unwrap()]).unwrap();
+        notes[0].raw = vec!["# A".to_string(), "zanzibar".to_string()];
+        repository.update_notes(&notes, "Update a").unwrap();
+        let candidates: Vec<PathBuf> = repository.search_index().candidates(&words).into_iter().collect();
+        assert_eq!(candidates, vec![PathBuf::from("a.md")]);
+
+        let notes = repository.find_notes(&[NoteSelector::parse("a.md").unwrap()]).unwrap();
+        repository.move_notes(&notes, "b/").unwrap();
+        let candidates: Vec<PathBuf> = repository.search_index().candidates(&words).into_iter().collect();
+        assert_eq!(candidates, vec![PathBuf::from("b/a.md")]);
+
+        let notes = repository.find_notes(&[NoteSelector::parse("b/a.md").unwrap()]).unwrap();
+        repository.delete_notes(&notes).unwrap();
+        assert!(repository.search_index().candidates(&words).is_empty());
+    }
+
+    #[test]
+    pub fn search_history_and_restore_file() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        let path = config.storage_directory.join("b.md");
+        let content = fs::read_to_string(&path).unwrap();
+        let notes = repository.find_notes(&[NoteSelector::parse("b.md").unwrap()]).unwrap();
+        repository.delete_notes(&notes).unwrap();
+
+        let matches = repository.search_history("sample-repo/B\\.md").unwrap();
+        let deletion = &matches[0];
+        assert_eq!(deletion.path, PathBuf::from("b.md"));
+        assert!(deletion.lines.iter().all(|l| !l.added));
+
+        repository.restore_file(&deletion.path, &format!("{}^", deletion.commit)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, format!("Restore b.md from {}^\n", deletion.commit));
+    }
+
+    #[test]
+    pub fn sync_repo() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let mut git_mock = MockGit::new();
+        let mut sequence = mockall::Sequence::new();
+        git_mock.expect_has_pending_changes().times(1).in_sequence(&mut sequence).returning(|| Ok(true));
+        git_mock
+            .expect_commit_all()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .withf(|m| m == "Synchronize notes")
+            .returning(|_| Ok(ConsoleOutput::empty()));
+        git_mock
+            .expect_pull_rebase()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|| Ok(RebaseOutcome::Done(ConsoleOutput::empty())));
+        git_mock
+            .expect_push()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|| Ok(ConsoleOutput::empty()));
+        let repository = RepositoryImpl::new(&config, &shell, &git_mock);
+
+        assert_eq!(repository.sync_repo().unwrap().conflicts, Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    pub fn sync_repo_should_not_push_conflicts() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let mut git_mock = MockGit::new();
+        git_mock.expect_has_pending_changes().times(1).returning(|| Ok(false));
+        git_mock.expect_commit_all().times(0);
+        git_mock
+            .expect_pull_rebase()
+            .times(1)
+            .returning(|| Ok(RebaseOutcome::Conflicts(vec![PathBuf::from("b.md")])));
+        git_mock.expect_push().times(0);
+        let repository = RepositoryImpl::new(&config, &shell, &git_mock);
+
+        assert_eq!(repository.sync_repo().unwrap().conflicts, vec![PathBuf::from("b.md")]);
+    }
+
+    #[test]
+    pub fn sync_repo_with_remote() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        let root = config.storage_directory.parent().unwrap().to_path_buf();
+        let remote = root.join("remote.git");
+        let other = root.join("other");
+        shell.execute(&format!("git init --bare {}", remote.display()), &root).unwrap();
+        shell
+            .execute_in_repo(&format!("git remote add origin {} && git push -u origin HEAD", remote.display()))
+            .unwrap();
+        shell.execute(&format!("git clone {} {}", remote.display(), other.display()), &root).unwrap();
+        let commit_in_other = "git -c user.name=other -c user.email=other@localhost commit -am 'Update b' && git push";
+
+        // Remote change without conflicts, and local pending change
+        fs::write(other.join("a.md"), "# Remote").unwrap();
+        shell.execute(commit_in_other, &other).unwrap();
+        fs::write(config.storage_directory.join("b.md"), "# Local").unwrap();
+        let result = repository.sync_repo().unwrap();
+        assert!(result.conflicts.is_empty());
+        assert_eq!(fs::read_to_string(config.storage_directory.join("a.md")).unwrap(), "# Remote");
+        shell.execute("git pull", &other).unwrap();
+        assert_eq!(fs::read_to_string(other.join("b.md")).unwrap(), "# Local");
+
+        // Both sides changed the same note
+        fs::write(other.join("b.md"), "# Remote").unwrap();
+        shell.execute(commit_in_other, &other).unwrap();
+        fs::write(config.storage_directory.join("b.md"), "# Local again").unwrap();
+        let result = repository.sync_repo().unwrap();
+        assert_eq!(result.conflicts, vec![PathBuf::from("b.md")]);
+        let pushed = shell.execute("git fetch && git rev-list --count HEAD..@{u}", &other).unwrap();
+        assert_eq!(pushed.stdout, "0\n");
+    }
+
+    #[test]
+    pub fn note_history_should_follow_moves() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let notes = repository.find_notes(&[NoteSelector::parse("b.md").unwrap()]).unwrap();
+        let mut note = notes[0].clone();
+        note.raw.push("Updated".to_string());
+        repository.update_notes(&[note], "Update b.md").unwrap();
+        repository.move_notes(&notes, "moved/").unwrap();
+        let moved = repository.find_notes(&[NoteSelector::parse("moved/b.md").unwrap()]).unwrap();
+
+        let history = repository.note_history(&moved[0]).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].path, PathBuf::from("moved/b.md"));
+        assert_eq!(history[1].message, "Update b.md");
+        assert_eq!(history[1].path, PathBuf::from("b.md"));
+        assert_eq!(history[2].path, PathBuf::from("b.md"));
+    }
+
+    #[test]
+    pub fn diff_should_show_changes_made_outside() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        assert_eq!(repository.diff("HEAD", &[], false).unwrap(), "");
+        fs::write(config.storage_directory.join("a.md"), "# Changed outside\n").unwrap();
+        let diff = repository.diff("HEAD", &[], false).unwrap();
+        assert!(diff.contains("+# Changed outside\n"));
+        assert_eq!(repository.diff("HEAD", &[PathBuf::from("b.md")], false).unwrap(), "");
+        let diff = repository.diff("HEAD", &[PathBuf::from("a.md")], true).unwrap();
+        assert!(diff.contains("{+Changed outside+}"));
+    }
+
+    #[test]
+    pub fn diff_should_show_notes_created_outside() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        fs::create_dir_all(config.storage_directory.join("new")).unwrap();
+        fs::write(config.storage_directory.join("new/created.md"), "# Created outside\n").unwrap();
+        fs::write(config.storage_directory.join("new/image.png"), "").unwrap();
+        let diff = repository.diff("HEAD", &[], false).unwrap();
+        assert!(diff.contains("+++ b/new/created.md\n"));
+        assert!(diff.contains("+# Created outside\n"));
+        assert!(!diff.contains("image.png"));
+
+        let diff = repository.diff("HEAD", &[PathBuf::from("new/created.md")], true).unwrap();
+        assert!(diff.contains("{+# Created outside+}"));
+        assert_eq!(repository.diff("HEAD", &[PathBuf::from("b.md")], false).unwrap(), "");
+        assert_eq!(repository.diff("HEAD~1..HEAD", &[PathBuf::from("new/created.md")], false).unwrap(), "");
+    }
+
+    #[test]
+    pub fn restore_note() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let path = config.storage_directory.join("b.md");
+        let content = fs::read_to_string(&path).unwrap();
+        let notes = repository.find_notes(&[NoteSelector::parse("b.md").unwrap()]).unwrap();
+        repository.move_notes(&notes, "moved/").unwrap();
+        let mut note = repository.find_notes(&[NoteSelector::parse("moved/b.md").unwrap()]).unwrap()[0].clone();
+        note.raw = vec!["Bad edit".to_string()];
+        repository.update_notes(std::slice::from_ref(&note), "Update b.md").unwrap();
+
+        let preview = repository.restore_preview(&note, "HEAD~2", Path::new("b.md")).unwrap();
+        assert!(preview.contains("-Bad edit\n"));
+        assert!(preview.contains(&format!("+{}", content)));
+
+        repository.restore_note(&note, "HEAD~2", Path::new("b.md")).unwrap();
+        assert_eq!(fs::read_to_string(&note.path).unwrap(), content);
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, "Restore note b.md to HEAD~2\n");
+        let preview = repository.restore_preview(&note, "HEAD~3", Path::new("b.md")).unwrap();
+        assert!(!preview.contains("@@"));
+    }
+
+    #[test]
+    pub fn note_ids_should_be_stable() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let before: Vec<(usize, PathBuf)> = repository.load_notes().unwrap().iter().map(|n| (n.id, n.path.clone())).collect();
+        let new_note = repository.new_note("a/a0.md").unwrap();
+        fs::remove_file(config.storage_directory.join("b.md")).unwrap();
+        let after = repository.load_notes().unwrap();
+
+        assert_eq!(new_note.id, before.len() + 1);
+        before.iter().filter(|(_, path)| path.exists()).for_each(|(id, path)| {
+            let note = after.iter().find(|n| &n.path == path).unwrap();
+            assert_eq!(&note.id, id);
+        });
+        assert_eq!(after.iter().find(|n| n.path == new_note.path).unwrap().id, new_note.id);
+    }
+
+    #[test]
+    pub fn delete_notes_should_not_commit() {
+        let mut config = new_sample_repo();
+        config.auto_commit = false;
+        let shell = ShellImpl::new(&config);
+        let mut git_mock = MockGit::new();
+        git_mock.expect_commit_paths().times(0);
+        let repository = RepositoryImpl::new(&config, &shell, &git_mock);
+
+        let notes = repository.find_notes(&[NoteSelector::Id(2)]).unwrap();
+        repository.delete_notes(&notes).unwrap();
+        assert!(!notes[0].path.exists());
+    }
+
+    #[test]
+    pub fn load_notes_with_configured_extensions() {
+        let mut config = new_sample_repo();
+        config.extensions = vec!["txt".to_string()];
+        config.ignored_directories = vec!["b".to_string()];
+        fs::write(config.storage_directory.join("c.txt"), "# Text note").unwrap();
+        fs::write(config.storage_directory.join("b/d.txt"), "# Ignored note").unwrap();
+
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let paths: Vec<PathBuf> = repository.load_notes().unwrap().iter().map(|n| n.path.clone()).collect();
+        assert_eq!(paths, vec![config.storage_directory.join("c.txt")]);
+    }
+
+    #[test]
+    pub fn save_config() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        let mut file = ConfigFile::default();
+        file.set("auto_push", "true").unwrap();
+        repository.save_config(&file).unwrap();
+
+        assert_eq!(ConfigFile::load(&config.repository_config_path()).unwrap(), file);
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, "Update notes configuration\n");
+    }
+
+    #[test]
+    pub fn delete_directory() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        let tree = repository.load_repository_tree().unwrap();
+        let directory = tree.iter().find(|d| d.name == "a").unwrap();
+        repository.delete_directory(directory).unwrap();
+
+        assert!(!config.storage_directory.join("a").exists());
+        let paths: Vec<PathBuf> = repository.load_notes().unwrap().iter().map(|n| n.path.clone()).collect();
+        assert!(paths.iter().all(|p| !p.starts_with(config.storage_directory.join("a"))));
+
+        let status = shell.execute_in_repo("git status --porcelain").unwrap();
+        assert_eq!(status.stdout, "");
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, "Delete directory a\n");
+    }
+
+    #[test]
+    pub fn other_files() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        fs::write(config.storage_directory.join("a/image.png"), "").unwrap();
+        fs::write(config.storage_directory.join("a/a/data.json"), "").unwrap();
+
+        let tree = repository.load_repository_tree().unwrap();
+        let directory = tree.iter().find(|d| d.name == "a").unwrap();
+        assert_eq!(
+            repository.other_files(directory),
+            vec![PathBuf::from("a/a/data.json"), PathBuf::from("a/image.png")]
+        );
+    }
+
+    #[test]
+    pub fn delete_directory_should_not_delete_root() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let tree = repository.load_repository_tree().unwrap();
+        let result = repository.delete_directory(&tree[0]);
+        assert!(result.unwrap_err().message.contains("Cannot delete directory"));
+        assert!(config.storage_directory.exists());
+    }
+
+    #[test]
+    pub fn move_note() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        repository.init().unwrap();
+
+        let note = repository.find_note_by_id(2).unwrap().unwrap();
+        repository.move_notes(std::slice::from_ref(&note), "c/d/moved").unwrap();
+
+        let moved = repository.find_note_by_id(2).unwrap().unwrap();
+        assert_eq!(moved.path, config.storage_directory.join("c/d/moved.md"));
+        assert!(!note.path.exists());
+
+        let status = shell.execute_in_repo("git status --porcelain").unwrap();
+        assert_eq!(status.stdout, "");
+        let log = shell.execute_in_repo("git log -1 --format=%s").unwrap();
+        assert_eq!(log.stdout, "Move note a.md to c/d/moved.md\n");
+    }
+
+    #[test]
+    pub fn move_note_in_directory() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let note = repository.find_note_by_id(2).unwrap().unwrap();
+        repository.move_notes(std::slice::from_ref(&note), "b").unwrap();
+        assert_eq!(repository.find_note_by_id(2).unwrap().unwrap().path, config.storage_directory.join("b/a.md"));
+    }
+
+    #[test]
+    pub fn move_note_should_not_overwrite() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        let note = repository.find_note_by_id(2).unwrap().unwrap();
+        let result = repository.move_notes(std::slice::from_ref(&note), "b.md");
+        assert!(result.unwrap_err().message.contains("Already exists"));
+
+        let result = repository.move_notes(std::slice::from_ref(&note), "../outside.md");
+        assert!(result.unwrap_err().message.contains("Destination must be inside repository"));
+        assert!(note.path.exists());
+
+        let outside = new_test_root().join("outside");
+        let result = repository.move_notes(std::slice::from_ref(&note), &format!("{}/", outside.to_str().unwrap()));
+        assert!(result.unwrap_err().message.contains("Destination must be inside repository"));
+        assert!(!outside.exists());
+        assert!(note.path.exists());
+    }
+
+    #[test]
+    pub fn move_notes_should_revert_moves_on_failure() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let (a, b) = (config.storage_directory.join("a.md"), config.storage_directory.join("b.md"));
+        let (moved_a, moved_b) = (config.storage_directory.join("c/a.md"), config.storage_directory.join("c/b.md"));
+
+        let mut git_mock = MockGit::new();
+        let (from, to) = (a.clone(), moved_a.clone());
+        git_mock
+            .expect_move_path()
+            .times(1)
+            .withf(move |f, t| f == from && t == to)
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+        let (from, to) = (b.clone(), moved_b.clone());
+        git_mock
+            .expect_move_path()
+            .times(1)
+            .withf(move |f, t| f == from && t == to)
+            .returning(|_, _| Err(DefaultError::new("fatal: not under version control".to_string())));
+        let (from, to) = (moved_a.clone(), a.clone());
+        git_mock
+            .expect_move_path()
+            .times(1)
+            .withf(move |f, t| f == from && t == to)
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+        git_mock.expect_commit_paths().times(0);
+        let repository = RepositoryImpl::new(&config, &shell, &git_mock);
+
+        let notes: Vec<Note> = repository.load_notes().unwrap().into_iter().filter(|n| n.path == a || n.path == b).collect();
+        let ids: Vec<usize> = notes.iter().map(|n| n.id).collect();
+        let result = repository.move_notes(&notes, "c/");
+        assert!(result.unwrap_err().message.contains("not under version control"));
+
+        let id_map = IdMap::load(&config.data_directory.join("ids")).unwrap();
+        assert_eq!(
+            vec![id_map.get(Path::new("a.md")), id_map.get(Path::new("b.md"))],
+            vec![Some(ids[0]), Some(ids[1])]
+        );
+        assert_eq!(id_map.get(Path::new("c/a.md")), None);
+    }
+
     #[test]
     pub fn load_repository_tree() {
         let config = new_sample_repo();
@@ -340,7 +1348,7 @@ mod tests {
         let git = GitImpl::new(&shell);
         let repository = RepositoryImpl::new(&config, &shell, &git);
 
-        let result = repository.load_repository_tree();
+        let result = repository.load_repository_tree().unwrap();
         let tree: Vec<&str> = result
             .iter()
             .flat_map(|dir| {
@@ -371,6 +1379,37 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn load_directory_tree() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        let all_notes = repository.load_notes().unwrap();
+
+        let result = repository.load_directory_tree(Path::new("a")).unwrap();
+        let tree: Vec<(&str, usize, usize)> = result.iter().map(|dir| (dir.name.as_str(), dir.level, dir.notes.len())).collect();
+        assert_eq!(tree, vec![("a", 0, 2), ("a/a", 1, 2)]);
+
+        // Ids of notes outside of directory must not change
+        repository.load_directory_tree(Path::new("a/a")).unwrap();
+        let ids: Vec<usize> = repository.load_notes().unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, all_notes.iter().map(|n| n.id).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    pub fn load_directory_tree_should_fail() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+
+        for directory in &["c", "a.md", "../", ".notes", ".git"] {
+            let result = repository.load_directory_tree(Path::new(directory));
+            assert_eq!(result.unwrap_err().message, format!("Directory {} not found.", directory));
+        }
+    }
+
     #[test]
     pub fn load_notes() {
         let config = new_sample_repo();
@@ -378,7 +1417,7 @@ mod tests {
         let git = GitImpl::new(&shell);
         let repository = RepositoryImpl::new(&config, &shell, &git);
 
-        let result = repository.load_notes();
+        let result = repository.load_notes().unwrap();
         let paths: Vec<&str> = result
             .iter()
             .map(|note| &note.path)
@@ -390,4 +1429,65 @@ mod tests {
             vec![".template.md", "a.md", "b.md", "a/aa.md", "a/ab.md", "a/a/aaa.md", "a/a/aab.md", "b/bb.md"]
         );
     }
+
+    #[test]
+    pub fn load_notes_containing() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        let git = GitImpl::new(&shell);
+        let repository = RepositoryImpl::new(&config, &shell, &git);
+        let all_notes = repository.load_notes().unwrap();
+
+        let note = &all_notes[3];
+        fs::write(&note.path, "# Aa\n\nAn unusual word: zanzibar\n").unwrap();
+        let result = repository
+            .load_notes_containing(&["ZANZI".to_string()], Path::new(""), &mut repository.search_index())
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].id, note.id);
+        assert!(config.data_directory.join("index").exists());
+
+        // Index must be updated when notes change outside of this tool
+        fs::write(&note.path, "# Aa\n\nNothing to see here, this note is longer than before\n").unwrap();
+        assert!(repository
+            .load_notes_containing(&["zanzibar".to_string()], Path::new(""), &mut repository.search_index())
+            .unwrap()
+            .is_empty());
+
+        let ids: Vec<usize> = repository
+            .load_notes_containing(&[], Path::new(""), &mut repository.search_index())
+            .unwrap()
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, all_notes.iter().map(|n| n.id).collect::<Vec<usize>>());
+
+        let ids: Vec<usize> = repository
+            .load_notes_containing(&[], Path::new("a/a"), &mut repository.search_index())
+            .unwrap()
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec![all_notes[5].id, all_notes[6].id]);
+        assert!(repository.load_notes_containing(&[], Path::new("c"), &mut repository.search_index()).is_err());
+    }
+
+    #[test]
+    pub fn init_should_update_data_directory() {
+        let config = new_sample_repo();
+        let shell = ShellImpl::new(&config);
+        fs::create_dir_all(&config.data_directory).unwrap();
+        fs::write(config.data_directory.join(".gitignore"), "ids\n").unwrap();
+
+        let mut git_mock = MockGit::new();
+        git_mock
+            .expect_commit_paths()
+            .times(1)
+            .withf(|paths, msg| paths[0].ends_with(".notes/.gitignore") && msg == "Update notes data directory")
+            .returning(|_, _| Ok(ConsoleOutput::empty()));
+
+        let repository = RepositoryImpl::new(&config, &shell, &git_mock);
+        repository.init().unwrap();
+        assert_eq!(fs::read_to_string(config.data_directory.join(".gitignore")).unwrap(), "ids\nindex\n");
+    }
 }
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use regex::Regex;
+
+use crate::default_error::DefaultError;
+
+const HEADER: &str = "# Search index, generated by notes. Do not edit.";
+
+/// Modification time and size of a file, used to detect changes since last indexation
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FileStamp {
+    pub modified: u128,
+    pub size: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Result<FileStamp, DefaultError> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        Ok(FileStamp {
+            modified,
+            size: metadata.len(),
+        })
+    }
+}
+
+/// Persistent inverted index of words contained in notes, used to avoid reading all notes on search.
+/// Note paths are relative to repository.
+///
+/// On disk, each file is written on a line 'f <modified> <size> <word count> <path>', then each word
+/// on a line 't <word> <file number> <file number> ...'.
+#[derive(Debug)]
+pub struct SearchIndex {
+    path: PathBuf,
+    files: BTreeMap<PathBuf, FileStamp>,
+    /// Number of words of each file, used to rank search results
+    lengths: BTreeMap<PathBuf, usize>,
+    postings: BTreeMap<String, BTreeSet<PathBuf>>,
+    changed: bool,
+}
+
+impl SearchIndex {
+    pub fn new(path: &Path) -> SearchIndex {
+        SearchIndex {
+            path: path.to_path_buf(),
+            files: BTreeMap::new(),
+            lengths: BTreeMap::new(),
+            postings: BTreeMap::new(),
+            changed: false,
+        }
+    }
+
+    /// Load index from specified file. If file does not exist, an empty index is returned.
+    pub fn load(path: &Path) -> Result<SearchIndex, DefaultError> {
+        let mut index = SearchIndex::new(path);
+        if !path.exists() {
+            return Ok(index);
+        }
+
+        let invalid = |line: &str| DefaultError::new(format!("Invalid line in {}: {}", path.to_str().unwrap(), line));
+        let content = fs::read_to_string(path)?;
+        let mut file_paths: Vec<PathBuf> = vec![];
+        for line in content.lines().filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.splitn(5, ' ');
+            match (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some("f"), Some(modified), Some(size), Some(length), Some(file_path)) => {
+                    let stamp = FileStamp {
+                        modified: modified.parse::<u128>().map_err(|_| invalid(line))?,
+                        size: size.parse::<u64>()?,
+                    };
+                    file_paths.push(PathBuf::from(file_path));
+                    index.files.insert(PathBuf::from(file_path), stamp);
+                    index.lengths.insert(PathBuf::from(file_path), length.parse::<usize>()?);
+                }
+                (Some("t"), Some(word), _, _, _) => {
+                    let mut paths = BTreeSet::new();
+                    for number in line.split(' ').skip(2) {
+                        let file_path = file_paths.get(number.parse::<usize>()?).ok_or_else(|| invalid(line))?;
+                        paths.insert(file_path.clone());
+                    }
+                    index.postings.insert(word.to_string(), paths);
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn is_up_to_date(&self, file_path: &Path, stamp: &FileStamp) -> bool {
+        self.files.get(file_path) == Some(stamp)
+    }
+
+    /// Replace indexed words of specified file
+    pub fn update(&mut self, file_path: &Path, stamp: FileStamp, content: &str) {
+        self.remove(file_path);
+        for word in tokenize(content) {
+            self.postings.entry(word).or_default().insert(file_path.to_path_buf());
+        }
+        self.files.insert(file_path.to_path_buf(), stamp);
+        self.lengths.insert(file_path.to_path_buf(), word_count(content));
+        self.changed = true;
+    }
+
+    pub fn remove(&mut self, file_path: &Path) {
+        if self.files.remove(file_path).is_none() {
+            return;
+        }
+        self.lengths.remove(file_path);
+        for paths in self.postings.values_mut() {
+            paths.remove(file_path);
+        }
+        self.postings.retain(|_, paths| !paths.is_empty());
+        self.changed = true;
+    }
+
+    /// Forget all files of directory and its sub directories
+    pub fn remove_directory(&mut self, directory: &Path) {
+        let removed: Vec<PathBuf> = self.files.keys().filter(|p| p.starts_with(directory)).cloned().collect();
+        removed.iter().for_each(|p| self.remove(p));
+    }
+
+    /// Forget all files that are not in specified list
+    pub fn retain(&mut self, existing: &[PathBuf]) {
+        let removed: Vec<PathBuf> = self.files.keys().filter(|p| !existing.contains(p)).cloned().collect();
+        removed.iter().for_each(|p| self.remove(p));
+    }
+
+    /// Return files that contain all specified words. Words are matched as parts of indexed words,
+    /// e.g. 'ssh' matches a file containing 'openssh'.
+    pub fn candidates(&self, words: &[String]) -> BTreeSet<PathBuf> {
+        let mut result: Option<BTreeSet<PathBuf>> = None;
+        for word in words.iter().map(|w| w.to_lowercase()) {
+            let matching: BTreeSet<PathBuf> = self
+                .postings
+                .iter()
+                .filter(|(indexed, _)| indexed.contains(&word))
+                .flat_map(|(_, paths)| paths.iter().cloned())
+                .collect();
+            result = Some(match result {
+                Some(previous) => previous.intersection(&matching).cloned().collect(),
+                None => matching,
+            });
+        }
+        result.unwrap_or_else(|| self.files.keys().cloned().collect())
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Average number of words of indexed files
+    pub fn average_length(&self) -> f64 {
+        match self.lengths.len() {
+            0 => 0.0,
+            count => self.lengths.values().sum::<usize>() as f64 / count as f64,
+        }
+    }
+
+    /// Number of files containing a word matching specified regular expression
+    pub fn document_frequency(&self, regex: &Regex) -> usize {
+        let files: BTreeSet<&PathBuf> = self
+            .postings
+            .iter()
+            .filter(|(word, _)| regex.is_match(word))
+            .flat_map(|(_, paths)| paths.iter())
+            .collect();
+        files.len()
+    }
+
+    /// Write index on disk, only if something changed since loading
+    pub fn save(&self) -> Result<(), DefaultError> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let mut lines = vec![HEADER.to_string()];
+        let mut numbers: BTreeMap<&PathBuf, usize> = BTreeMap::new();
+        for (number, (file_path, stamp)) in self.files.iter().enumerate() {
+            let length = self.lengths.get(file_path).cloned().unwrap_or(0);
+            lines.push(format!("f {} {} {} {}", stamp.modified, stamp.size, length, file_path.to_str().unwrap()));
+            numbers.insert(file_path, number);
+        }
+        for (word, paths) in self.postings.iter() {
+            let file_numbers: Vec<String> = paths.iter().map(|p| numbers[p].to_string()).collect();
+            lines.push(format!("t {} {}", word, file_numbers.join(" ")));
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, format!("{}\n", lines.join("\n")))?;
+        Ok(())
+    }
+}
+
+/// Split text in lower case words, made of letters and digits
+pub fn tokenize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Number of words in text, with the same definition of words as tokenize()
+pub fn word_count(text: &str) -> usize {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_env::new_test_root;
+
+    fn stamp(modified: u128) -> FileStamp {
+        FileStamp { modified, size: 10 }
+    }
+
+    fn paths(values: &[&str]) -> BTreeSet<PathBuf> {
+        values.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    pub fn tokenize_text() {
+        let words: Vec<String> = tokenize("# SSH tips\nUse ssh-agent, or état").into_iter().collect();
+        assert_eq!(words, vec!["agent", "or", "ssh", "tips", "use", "état"]);
+    }
+
+    #[test]
+    pub fn candidates() {
+        let mut index = SearchIndex::new(&new_test_root().join("index"));
+        index.update(&PathBuf::from("a.md"), stamp(1), "# OpenSSH\nssh tunnels");
+        index.update(&PathBuf::from("b.md"), stamp(1), "# Rsync\nrsync over ssh");
+        index.update(&PathBuf::from("c.md"), stamp(1), "# Rust");
+
+        assert_eq!(index.candidates(&["SSH".to_string()]), paths(&["a.md", "b.md"]));
+        assert_eq!(index.candidates(&["ssh".to_string(), "rsync".to_string()]), paths(&["b.md"]));
+        assert_eq!(index.candidates(&["tunnel".to_string()]), paths(&["a.md"]));
+        assert_eq!(index.candidates(&["python".to_string()]), paths(&[]));
+        assert_eq!(index.candidates(&[]), paths(&["a.md", "b.md", "c.md"]));
+    }
+
+    #[test]
+    pub fn update_should_replace_words() {
+        let mut index = SearchIndex::new(&new_test_root().join("index"));
+        index.update(&PathBuf::from("a.md"), stamp(1), "# OpenSSH");
+        index.update(&PathBuf::from("a.md"), stamp(2), "# Rsync");
+
+        assert!(index.candidates(&["ssh".to_string()]).is_empty());
+        assert!(index.is_up_to_date(&PathBuf::from("a.md"), &stamp(2)));
+        assert!(!index.is_up_to_date(&PathBuf::from("a.md"), &stamp(1)));
+    }
+
+    #[test]
+    pub fn retain() {
+        let mut index = SearchIndex::new(&new_test_root().join("index"));
+        index.update(&PathBuf::from("a.md"), stamp(1), "# Rust");
+        index.update(&PathBuf::from("b.md"), stamp(1), "# Rust");
+        index.retain(&[PathBuf::from("b.md")]);
+
+        assert_eq!(index.candidates(&["rust".to_string()]), paths(&["b.md"]));
+        assert!(!index.is_up_to_date(&PathBuf::from("a.md"), &stamp(1)));
+    }
+
+    #[test]
+    pub fn save_and_load() {
+        let path = new_test_root().join(".notes/index");
+        let mut index = SearchIndex::load(&path).unwrap();
+        index.update(&PathBuf::from("a.md"), stamp(1), "# OpenSSH");
+        index.update(&PathBuf::from("dir/b c.md"), stamp(2), "# Rsync over ssh");
+        index.save().unwrap();
+
+        let index = SearchIndex::load(&path).unwrap();
+        assert_eq!(index.document_count(), 2);
+        assert_eq!(index.average_length(), 2.0);
+        assert!(index.is_up_to_date(&PathBuf::from("dir/b c.md"), &stamp(2)));
+        assert_eq!(index.candidates(&["ssh".to_string()]), paths(&["a.md", "dir/b c.md"]));
+        assert_eq!(index.candidates(&["rsync".to_string()]), paths(&["dir/b c.md"]));
+    }
+
+    #[test]
+    pub fn document_frequency() {
+        let mut index = SearchIndex::new(&new_test_root().join("index"));
+        index.update(&PathBuf::from("a.md"), stamp(1), "# OpenSSH\nssh tunnels");
+        index.update(&PathBuf::from("b.md"), stamp(1), "# Rsync\nrsync over ssh");
+        index.update(&PathBuf::from("c.md"), stamp(1), "# Rust");
+
+        assert_eq!(index.document_frequency(&Regex::new("ssh").unwrap()), 2);
+        assert_eq!(index.document_frequency(&Regex::new("^r").unwrap()), 2);
+        assert_eq!(index.document_frequency(&Regex::new("python").unwrap()), 0);
+        assert_eq!(word_count("# Rsync\nrsync over ssh"), 4);
+    }
+
+    #[test]
+    pub fn load_should_fail() {
+        let path = new_test_root().join("index");
+        fs::write(&path, "t word 3\n").unwrap();
+        assert!(SearchIndex::load(&path).is_err());
+    }
+}
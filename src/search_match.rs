@@ -1,20 +1,84 @@
+use std::ops::Range;
 use std::path::PathBuf;
 
-#[derive(Debug, Eq, PartialEq)]
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct SearchMatch {
     pub id: usize,
-    pub score: usize,
+    pub score: f64,
+    #[serde(serialize_with = "crate::json_format::lossy_path")]
     pub path: PathBuf,
     pub title: String,
     pub matched_lines: Vec<MatchedLine>,
+    /// Score of each searched term, displayed with --explain
+    pub explanation: Vec<TermScore>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize)]
 pub struct MatchedLine {
     pub display_number: usize,
     pub line_number: usize,
     pub content: String,
-    pub matched: String,
-    pub previous: Option<String>,
-    pub next: Option<String>,
+    /// Byte ranges of matches in content, sorted and not overlapping
+    pub matches: Vec<Range<usize>>,
+    /// Lines displayed around this one. Lines already displayed with the previous matched line
+    /// are not repeated, so that context of close matches is merged.
+    pub before: Vec<ContextLine>,
+    pub after: Vec<ContextLine>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct ContextLine {
+    pub display_number: usize,
+    pub content: String,
+}
+
+/// Number of lines displayed before and after matched lines
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Context {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context { before: 1, after: 1 }
+    }
+}
+
+/// Occurrences of a term in each field of a note, and resulting score
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TermScore {
+    pub term: String,
+    pub title: usize,
+    pub headings: usize,
+    pub path: usize,
+    pub body: usize,
+    /// Inverse document frequency, rare terms are worth more
+    pub idf: f64,
+    pub score: f64,
+}
+
+/// Changes of a note in a commit, where a searched pattern was added or removed
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct HistoryMatch {
+    /// Abbreviated hash of commit
+    pub commit: String,
+    pub date: String,
+    /// Path of note relative to repository root, at this commit
+    #[serde(serialize_with = "crate::json_format::lossy_path")]
+    pub path: PathBuf,
+    /// Revision containing the matched version of note: the parent commit if pattern was removed
+    pub revision: String,
+    pub lines: Vec<HistoryLine>,
+}
+
+/// Line added or removed in a commit
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct HistoryLine {
+    pub added: bool,
+    pub content: String,
+    /// Byte ranges of matches in content, sorted and not overlapping
+    pub matches: Vec<Range<usize>>,
 }
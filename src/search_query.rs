@@ -0,0 +1,577 @@
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::default_error::DefaultError;
+use crate::note::Note;
+use crate::search_index::tokenize;
+use crate::search_match::Context;
+
+/// Part of note a term is searched in. Terms without field are searched in title and body.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Field {
+    Title,
+    Path,
+    Tag,
+    Body,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "title" => Some(Field::Title),
+            "path" => Some(Field::Path),
+            "tag" => Some(Field::Tag),
+            "body" => Some(Field::Body),
+            _ => None,
+        }
+    }
+}
+
+/// How terms of a search are matched, and how many lines are displayed around matches
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct SearchOptions {
+    /// Needle is a list of words, searched in titles, file names and contents with typos
+    pub fuzzy: bool,
+    /// Needle is searched as is, without query syntax nor regular expressions
+    pub literal: bool,
+    pub case_sensitive: bool,
+    /// Terms only match whole words
+    pub word: bool,
+    pub context: Context,
+}
+
+/// Pattern of a term, compiled once when query is parsed
+#[derive(Debug)]
+pub struct Pattern {
+    pub text: String,
+    /// Text is not a regular expression
+    literal: bool,
+    case_sensitive: bool,
+    regex: Regex,
+}
+
+impl Pattern {
+    fn new(text: &str, literal: bool, options: &SearchOptions) -> Result<Pattern, regex::Error> {
+        let mut source = match literal {
+            true => regex::escape(text),
+            false => text.to_string(),
+        };
+        // Boundaries can only be found next to word characters, e.g. 'C++' matches at the end of 'Use C++'
+        if options.word {
+            let is_word = |c: Option<char>| matches!(c, Some(c) if c.is_alphanumeric() || c == '_');
+            let start = if is_word(text.chars().next()) { "\\b" } else { "" };
+            let end = if is_word(text.chars().last()) { "\\b" } else { "" };
+            source = format!("{}(?:{}){}", start, source, end);
+        }
+        let regex = RegexBuilder::new(&source).case_insensitive(!options.case_sensitive).build()?;
+        Ok(Pattern {
+            text: text.to_string(),
+            literal,
+            case_sensitive: options.case_sensitive,
+            regex,
+        })
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+/// Search query, e.g: title:ssh AND body:"rsync over ssh" AND NOT path:archive/
+///
+/// Terms are case insensitive regular expressions, quoted terms can contain spaces and parentheses.
+/// Terms separated by spaces must all match. Operators must be written in upper case.
+/// With literal option, the whole query is a single term searched as is.
+#[derive(Debug, PartialEq)]
+pub enum Query {
+    Term { field: Option<Field>, pattern: Pattern },
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Term { field: Option<Field>, text: String },
+}
+
+impl Token {
+    fn name(&self) -> String {
+        match self {
+            Token::LeftParen => "(".to_string(),
+            Token::RightParen => ")".to_string(),
+            Token::And => "AND".to_string(),
+            Token::Or => "OR".to_string(),
+            Token::Not => "NOT".to_string(),
+            Token::Term { text, .. } => text.clone(),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str, options: &SearchOptions) -> Result<Query, DefaultError> {
+        if options.literal {
+            if query.trim().is_empty() {
+                return Err(invalid("query is empty".to_string()));
+            }
+            // An escaped text is always a valid regular expression
+            let pattern = Pattern::new(query, true, options).unwrap();
+            return Ok(Query::Term { field: None, pattern });
+        }
+
+        let tokens = tokenize_query(query)?;
+        if tokens.is_empty() {
+            return Err(invalid("query is empty".to_string()));
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            options: *options,
+        };
+        let result = parser.parse_or()?;
+        match parser.tokens.get(parser.position) {
+            Some((token, position)) => Err(invalid(format!("unexpected '{}' at position {}", token.name(), position))),
+            None => Ok(result),
+        }
+    }
+
+    /// Path must be relative to repository
+    pub fn matches(&self, note: &Note, path: &Path) -> bool {
+        match self {
+            Query::Term { field, pattern } => {
+                let regex = &pattern.regex;
+                match field {
+                    Some(Field::Title) => regex.is_match(&note.title),
+                    Some(Field::Path) => regex.is_match(path.to_str().unwrap_or("")),
+                    Some(Field::Tag) => note.tags().iter().any(|t| t.eq_ignore_ascii_case(&pattern.text)),
+                    Some(Field::Body) => note.body.iter().any(|l| regex.is_match(l)),
+                    None => regex.is_match(&note.title) || note.body.iter().any(|l| regex.is_match(l)),
+                }
+            }
+            Query::And(left, right) => left.matches(note, path) && right.matches(note, path),
+            Query::Or(left, right) => left.matches(note, path) || right.matches(note, path),
+            Query::Not(query) => !query.matches(note, path),
+        }
+    }
+
+    /// Regular expression matching all searched texts, used to score notes and to highlight matches.
+    /// Negated terms, paths and tags are not highlighted.
+    pub fn highlight_regex(&self) -> Option<Regex> {
+        let patterns = self.highlighted_patterns();
+        if patterns.is_empty() {
+            return None;
+        }
+        let alternatives: Vec<String> = patterns.iter().map(|p| format!("(?:{})", p.regex.as_str())).collect();
+        let case_sensitive = patterns.iter().all(|p| p.case_sensitive);
+        RegexBuilder::new(&format!("({})", alternatives.join("|")))
+            .case_insensitive(!case_sensitive)
+            .build()
+            .ok()
+    }
+
+    fn highlighted_patterns(&self) -> Vec<&Pattern> {
+        match self {
+            Query::Term { field, pattern } => match field {
+                None | Some(Field::Title) | Some(Field::Body) => vec![pattern],
+                _ => vec![],
+            },
+            Query::And(left, right) | Query::Or(left, right) => {
+                let mut patterns = left.highlighted_patterns();
+                patterns.extend(right.highlighted_patterns());
+                patterns
+            }
+            Query::Not(_) => vec![],
+        }
+    }
+
+    /// Terms that are searched, and not excluded, used to rank results. Tags are not ranked.
+    pub fn ranked_terms(&self) -> Vec<(Option<Field>, &Pattern)> {
+        match self {
+            Query::Term { field, pattern } => match field {
+                Some(Field::Tag) => vec![],
+                _ => vec![(*field, pattern)],
+            },
+            Query::And(left, right) | Query::Or(left, right) => {
+                let mut terms = left.ranked_terms();
+                terms.extend(right.ranked_terms());
+                terms
+            }
+            Query::Not(_) => vec![],
+        }
+    }
+
+    /// Words that every matching note contains, used to pre-filter notes with search index.
+    /// Regular expressions cannot be used with index, so they are ignored.
+    pub fn required_words(&self) -> Vec<String> {
+        match self {
+            Query::Term { field, pattern } => match field {
+                None | Some(Field::Title) | Some(Field::Body) if pattern.literal || regex::escape(&pattern.text) == pattern.text => {
+                    tokenize(&pattern.text).into_iter().collect()
+                }
+                _ => vec![],
+            },
+            Query::And(left, right) => {
+                let mut words = left.required_words();
+                words.extend(right.required_words());
+                words
+            }
+            Query::Or(_, _) | Query::Not(_) => vec![],
+        }
+    }
+}
+
+fn invalid(message: String) -> DefaultError {
+    DefaultError::new(format!("Invalid query: {}", message))
+}
+
+/// Split query in tokens, with their position (starting at 1)
+fn tokenize_query(query: &str) -> Result<Vec<(Token, usize)>, DefaultError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = vec![];
+    let mut idx = 0;
+    while idx < chars.len() {
+        let start = idx + 1;
+        match chars[idx] {
+            c if c.is_whitespace() => idx += 1,
+            '(' => {
+                tokens.push((Token::LeftParen, start));
+                idx += 1;
+            }
+            ')' => {
+                tokens.push((Token::RightParen, start));
+                idx += 1;
+            }
+            _ => {
+                let mut word = String::new();
+                while idx < chars.len() && !chars[idx].is_whitespace() && !"()\"".contains(chars[idx]) {
+                    word.push(chars[idx]);
+                    idx += 1;
+                }
+
+                let (field, prefix) = match word.find(':') {
+                    Some(colon) => match Field::from_name(&word[..colon]) {
+                        Some(field) => (Some(field), word[..=colon].to_string()),
+                        None => (None, "".to_string()),
+                    },
+                    None => (None, "".to_string()),
+                };
+                let mut text = word[prefix.len()..].to_string();
+
+                // Phrase, possibly after a field prefix
+                if text.is_empty() && idx < chars.len() && chars[idx] == '"' {
+                    let phrase_start = idx + 1;
+                    idx += 1;
+                    while idx < chars.len() && chars[idx] != '"' {
+                        text.push(chars[idx]);
+                        idx += 1;
+                    }
+                    if idx == chars.len() {
+                        return Err(invalid(format!("missing closing quote for phrase at position {}", phrase_start)));
+                    }
+                    idx += 1;
+                }
+
+                if text.is_empty() {
+                    return Err(invalid(format!("missing value after '{}' at position {}", prefix, start)));
+                }
+                let token = match (field, text.as_str(), prefix.is_empty()) {
+                    (None, "AND", true) => Token::And,
+                    (None, "OR", true) => Token::Or,
+                    (None, "NOT", true) => Token::Not,
+                    _ => Token::Term { field, text },
+                };
+                tokens.push((token, start));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    options: SearchOptions,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, DefaultError> {
+        let mut query = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, DefaultError> {
+        let mut query = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.position += 1,
+                Some(Token::Not) | Some(Token::LeftParen) | Some(Token::Term { .. }) => (),
+                _ => break,
+            }
+            query = Query::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, DefaultError> {
+        let (token, position) = match self.tokens.get(self.position) {
+            Some((token, position)) => (token, *position),
+            None => return Err(invalid("missing term at end of query".to_string())),
+        };
+        self.position += 1;
+
+        match token {
+            Token::Not => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Token::LeftParen => {
+                let query = self.parse_or()?;
+                if self.peek() != Some(&Token::RightParen) {
+                    return Err(invalid(format!("missing ')' for '(' at position {}", position)));
+                }
+                self.position += 1;
+                Ok(query)
+            }
+            Token::Term { field, text } => {
+                // Tags are compared as is
+                let pattern = match field {
+                    Some(Field::Tag) => Pattern::new(text, true, &SearchOptions::default()),
+                    _ => Pattern::new(text, false, &self.options),
+                }
+                .map_err(|e| invalid(format!("invalid pattern '{}' at position {}: {}", text, position, e)))?;
+                Ok(Query::Term { field: *field, pattern })
+            }
+            Token::RightParen | Token::And | Token::Or => Err(invalid(format!("unexpected '{}' at position {}", token.name(), position))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(field: Option<Field>, text: &str) -> Query {
+        Query::Term {
+            field,
+            pattern: Pattern {
+                text: text.to_string(),
+                literal: false,
+                case_sensitive: false,
+                regex: Regex::new(text).unwrap(),
+            },
+        }
+    }
+
+    fn and(left: Query, right: Query) -> Query {
+        Query::And(Box::new(left), Box::new(right))
+    }
+
+    fn or(left: Query, right: Query) -> Query {
+        Query::Or(Box::new(left), Box::new(right))
+    }
+
+    fn not(query: Query) -> Query {
+        Query::Not(Box::new(query))
+    }
+
+    fn parse_error(query: &str) -> String {
+        Query::parse(query, &SearchOptions::default()).unwrap_err().message
+    }
+
+    const NOTE: &str = "\
+---
+tags: [network]
+---
+# SSH tips
+Use rsync over ssh
+Ssh agent
+";
+
+    fn sample_note() -> Note {
+        Note::from(1, "/repo/tools/ssh.md".into(), NOTE.to_string()).unwrap()
+    }
+
+    #[test]
+    pub fn parse_terms() {
+        assert_eq!(Query::parse("ssh", &SearchOptions::default()).unwrap(), term(None, "ssh"));
+        assert_eq!(Query::parse("title:ssh", &SearchOptions::default()).unwrap(), term(Some(Field::Title), "ssh"));
+        assert_eq!(
+            Query::parse("body:\"rsync over ssh\"", &SearchOptions::default()).unwrap(),
+            term(Some(Field::Body), "rsync over ssh")
+        );
+        assert_eq!(Query::parse("\"a (b)\"", &SearchOptions::default()).unwrap(), term(None, "a (b)"));
+        assert_eq!(Query::parse("http://host", &SearchOptions::default()).unwrap(), term(None, "http://host"));
+        assert_eq!(Query::parse("ss.*h", &SearchOptions::default()).unwrap(), term(None, "ss.*h"));
+    }
+
+    #[test]
+    pub fn parse_operators() {
+        assert_eq!(
+            Query::parse("ssh rsync", &SearchOptions::default()).unwrap(),
+            and(term(None, "ssh"), term(None, "rsync"))
+        );
+        assert_eq!(
+            Query::parse("ssh AND rsync", &SearchOptions::default()).unwrap(),
+            and(term(None, "ssh"), term(None, "rsync"))
+        );
+        assert_eq!(
+            Query::parse("ssh OR rsync tips", &SearchOptions::default()).unwrap(),
+            or(term(None, "ssh"), and(term(None, "rsync"), term(None, "tips")))
+        );
+        assert_eq!(
+            Query::parse("(ssh OR rsync) tips", &SearchOptions::default()).unwrap(),
+            and(or(term(None, "ssh"), term(None, "rsync")), term(None, "tips"))
+        );
+        assert_eq!(
+            Query::parse("title:ssh AND body:rsync AND NOT path:archive/", &SearchOptions::default()).unwrap(),
+            and(
+                and(term(Some(Field::Title), "ssh"), term(Some(Field::Body), "rsync")),
+                not(term(Some(Field::Path), "archive/"))
+            )
+        );
+        assert_eq!(
+            Query::parse("ssh and rsync", &SearchOptions::default()).unwrap(),
+            and(and(term(None, "ssh"), term(None, "and")), term(None, "rsync"))
+        );
+    }
+
+    #[test]
+    pub fn parse_should_fail() {
+        assert_eq!(parse_error(""), "Invalid query: query is empty");
+        assert_eq!(parse_error("ssh AND"), "Invalid query: missing term at end of query");
+        assert_eq!(parse_error("OR ssh"), "Invalid query: unexpected 'OR' at position 1");
+        assert_eq!(parse_error("ssh)"), "Invalid query: unexpected ')' at position 4");
+        assert_eq!(parse_error("(ssh OR rsync"), "Invalid query: missing ')' for '(' at position 1");
+        assert_eq!(parse_error("ssh \"rsync"), "Invalid query: missing closing quote for phrase at position 5");
+        assert_eq!(parse_error("title: ssh"), "Invalid query: missing value after 'title:' at position 1");
+        assert!(parse_error("ss[h").starts_with("Invalid query: invalid pattern 'ss[h' at position 1"));
+    }
+
+    #[test]
+    pub fn matches() {
+        let note = sample_note();
+        let path = Path::new("tools/ssh.md");
+        let matches = |query: &str| Query::parse(query, &SearchOptions::default()).unwrap().matches(&note, path);
+
+        assert!(matches("ssh"));
+        assert!(matches("RSYNC"));
+        assert!(matches("title:ssh AND body:rsync AND NOT path:archive/"));
+        assert!(!matches("title:rsync"));
+        assert!(matches("path:^tools/"));
+        assert!(!matches("path:repo"));
+        assert!(matches("tag:network"));
+        assert!(!matches("tag:net"));
+        assert!(matches("python OR agent"));
+        assert!(!matches("ssh python"));
+        assert!(matches("\"rsync over ssh\""));
+        assert!(!matches("body:tips"));
+    }
+
+    #[test]
+    pub fn parse_with_options() {
+        let literal = SearchOptions {
+            literal: true,
+            ..SearchOptions::default()
+        };
+        match Query::parse("foo( OR C++", &literal).unwrap() {
+            Query::Term { field, pattern } => assert!(field.is_none() && pattern.text == "foo( OR C++" && pattern.literal),
+            query => panic!("Unexpected query {:?}", query),
+        }
+        assert!(Query::parse(" ", &literal).is_err());
+
+        let note = Note::from(1, "/repo/cpp.md".into(), "# C++ tips\nUse foo(bar) with std::vector".to_string()).unwrap();
+        let path = Path::new("cpp.md");
+        let matches = |query: &str, options: SearchOptions| Query::parse(query, &options).unwrap().matches(&note, path);
+
+        assert!(matches("c++", literal));
+        assert!(matches("foo(", literal));
+        assert!(!matches("foo.bar", literal));
+        assert!(matches("c\\+\\+", SearchOptions::default()));
+        assert!(!matches(
+            "c++",
+            SearchOptions {
+                case_sensitive: true,
+                ..literal
+            }
+        ));
+        assert!(matches(
+            "C++ tips",
+            SearchOptions {
+                case_sensitive: true,
+                ..literal
+            }
+        ));
+        assert!(!matches(
+            "vec",
+            SearchOptions {
+                word: true,
+                ..SearchOptions::default()
+            }
+        ));
+        assert!(matches(
+            "vector",
+            SearchOptions {
+                word: true,
+                ..SearchOptions::default()
+            }
+        ));
+        assert!(matches("C++", SearchOptions { word: true, ..literal }));
+        assert!(!matches("tip", SearchOptions { word: true, ..literal }));
+    }
+
+    #[test]
+    pub fn parse_should_point_at_invalid_pattern() {
+        assert!(parse_error("ssh \"foo(\"").starts_with("Invalid query: invalid pattern 'foo(' at position 5"));
+        assert!(parse_error("ssh [a-").starts_with("Invalid query: invalid pattern '[a-' at position 5"));
+    }
+
+    #[test]
+    pub fn highlight_regex() {
+        let query = Query::parse("title:ssh OR body:\"rsync\" AND NOT python path:tools tag:network", &SearchOptions::default()).unwrap();
+        assert_eq!(query.highlight_regex().unwrap().as_str(), "((?:ssh)|(?:rsync))");
+        assert!(Query::parse("path:tools", &SearchOptions::default()).unwrap().highlight_regex().is_none());
+
+        let options = SearchOptions {
+            literal: true,
+            case_sensitive: true,
+            word: true,
+            ..SearchOptions::default()
+        };
+        let regex = Query::parse("C++", &options).unwrap().highlight_regex().unwrap();
+        assert_eq!(regex.as_str(), "((?:\\b(?:C\\+\\+)))");
+        assert!(!regex.is_match("c++"));
+    }
+
+    #[test]
+    pub fn ranked_terms() {
+        let query = Query::parse("title:ssh OR rsync NOT python tag:network", &SearchOptions::default()).unwrap();
+        let terms: Vec<(Option<Field>, &str)> = query.ranked_terms().iter().map(|(f, p)| (*f, p.text.as_str())).collect();
+        assert_eq!(terms, vec![(Some(Field::Title), "ssh"), (None, "rsync")]);
+    }
+
+    #[test]
+    pub fn required_words() {
+        let words = |query: &str| Query::parse(query, &SearchOptions::default()).unwrap().required_words();
+        assert_eq!(words("ssh \"rsync tips\""), vec!["ssh", "rsync", "tips"]);
+        assert_eq!(words("title:ssh NOT python path:tools"), vec!["ssh"]);
+        assert!(words("ssh OR rsync").is_empty());
+        assert!(words("ss.*h").is_empty());
+    }
+}
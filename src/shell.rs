@@ -190,10 +190,7 @@ mod tests {
 
     #[test]
     pub fn shell_impl_execute_correct_command() {
-        let config = Config {
-            storage_directory: PathBuf::from("/storage"),
-            template_path: PathBuf::from("/template.md"),
-        };
+        let config = Config::from_path(&PathBuf::from("/storage"));
         fn executor(c: &str, p: &PathBuf) -> Result<CommandOutput, DefaultError> {
             assert_eq!(p, &PathBuf::from("/storage"));
             assert_eq!(c, "test-command");
@@ -217,10 +214,7 @@ mod tests {
 
     #[test]
     pub fn shell_impl_execute_bad_command() {
-        let config = Config {
-            storage_directory: PathBuf::from("/storage"),
-            template_path: PathBuf::from("/template.md"),
-        };
+        let config = Config::from_path(&PathBuf::from("/storage"));
         fn executor(c: &str, p: &PathBuf) -> Result<CommandOutput, DefaultError> {
             assert_eq!(p, &PathBuf::from("/storage"));
             assert_eq!(c, "test-command");
@@ -243,10 +237,7 @@ mod tests {
 
     #[test]
     pub fn shell_impl_execute_error() {
-        let config = Config {
-            storage_directory: PathBuf::from("/storage"),
-            template_path: PathBuf::from("/template.md"),
-        };
+        let config = Config::from_path(&PathBuf::from("/storage"));
         fn executor(c: &str, p: &PathBuf) -> Result<CommandOutput, DefaultError> {
             assert_eq!(p, &PathBuf::from("/storage"));
             assert_eq!(c, "test-command");
@@ -264,10 +255,7 @@ mod tests {
 
     #[test]
     pub fn shell_impl_execute_interactive_correct_command() {
-        let config = Config {
-            storage_directory: PathBuf::from("/storage"),
-            template_path: PathBuf::from("/template.md"),
-        };
+        let config = Config::from_path(&PathBuf::from("/storage"));
         fn executor(c: &str, p: &PathBuf) -> Result<CommandOutput, DefaultError> {
             assert_eq!(p, &PathBuf::from("/storage"));
             assert_eq!(c, "test-command");
@@ -291,10 +279,7 @@ mod tests {
 
     #[test]
     pub fn shell_impl_execute_interactive_bad_command() {
-        let config = Config {
-            storage_directory: PathBuf::from("/storage"),
-            template_path: PathBuf::from("/template.md"),
-        };
+        let config = Config::from_path(&PathBuf::from("/storage"));
         fn executor(c: &str, p: &PathBuf) -> Result<CommandOutput, DefaultError> {
             assert_eq!(p, &PathBuf::from("/storage"));
             assert_eq!(c, "test-command");
@@ -316,10 +301,7 @@ mod tests {
 
     #[test]
     pub fn shell_impl_execute_interactive_error() {
-        let config = Config {
-            storage_directory: PathBuf::from("/storage"),
-            template_path: PathBuf::from("/template.md"),
-        };
+        let config = Config::from_path(&PathBuf::from("/storage"));
         fn executor(c: &str, p: &PathBuf) -> Result<CommandOutput, DefaultError> {
             assert_eq!(p, &PathBuf::from("/storage"));
             assert_eq!(c, "test-command");
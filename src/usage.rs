@@ -7,17 +7,82 @@ Version: {pkg_version}
 Usage:
 
   notes new <path>          Create a new note.
-  notes search <needle>     Search for a note. You can use regex !
+  notes search <query>      Search for a note. You can use regex and queries !
   notes edit <id>           Edit specified note
-  notes delete <id>         Delete specified note
+  notes history <id>        List commits that changed specified note, even before it was moved
+  notes diff [<id>] [--rev <commit>|--rev <A..B>] [--word]
+                            Show uncommitted changes of specified note, or of whole repository.
+                            With --rev, show changes since a commit or between two commits
+  notes restore <id> [--rev <commit>|--steps <n>]
+                            Restore specified note to an earlier version, after a preview of
+                            changes. Previous version is used by default. Use -y to skip confirmation
+  notes pick [<action>]     Choose a note in a fuzzy finder with preview, then edit, show,
+                            delete or move it. Edit without id does the same
+  notes delete <id>...      Delete specified notes. Deletion of several notes must be confirmed,
+                            use -y to skip confirmation
+  notes delete --dir <path> Delete a directory with all its notes and files. Use -y to skip confirmation
+  notes move <id>... <path> Move or rename specified notes. Path can be a directory
   notes list                List all notes
+  notes list --tag <tag>    List notes with specified tag
+  notes list --in <dir>
+  notes search <query> --in <dir>
+                            List or search only in a directory. From inside repository, current
+                            directory is used by default, use --in / for the whole repository
+  notes search <needle> --tag <tag>
+                            Search only in notes with specified tag
+  notes search --fuzzy <words>
+                            Search words in titles, file names and contents, tolerating typos
+  notes search <query> --explain
+                            Show how the score of each result is computed
+  notes search <needle> --literal
+                            Search needle as is, e.g. 'C++' or 'foo(', without query syntax
+  notes search <query> --case-sensitive --word
+                            Do not ignore case, only match whole words
+  notes search <query> -C <lines> [-B <lines>] [-A <lines>]
+                            Display lines around matches, or only before or after them
+  notes search --history <regex> [--restore <commit>]
+                            Search in git history for versions of notes where text was added or
+                            removed. Restore versions of a commit with --restore. Regex must be a
+                            POSIX extended regular expression, e.g. [0-9] instead of \\d
+  notes tags                List all tags
+  notes tag add <id>... <tag>
+                            Add a tag to specified notes
+  notes tag remove <id>... <tag>
+                            Remove a tag from specified notes
+  notes config list         Show configuration
+  notes config get <key>    Show a configuration value
+  notes config set <key> <value> [--global]
+                            Change a configuration value, in repository or user configuration
+  notes list --all-notebooks
+  notes search <needle> --all-notebooks
+                            List or search in all notebooks
   notes push                Push notes repository
   notes pull                Pull notes repository
+  notes sync                Commit changes, pull with rebase then push notes repository.
+                            Conflicted notes are listed and nothing is pushed.
   notes help                Show this help
 
 Options:
-  -h --help     Show this screen.
-  --version     Show version.
+  -h --help               Show this screen.
+  --version               Show version.
+  --notebook <name>       Use specified notebook instead of default one
+  --no-pager              Do not display long outputs with a pager
+  --format <format>       Output format of all commands except push, pull and help: text (default)
+                          or json
+
+Configuration is read from $XDG_CONFIG_HOME/notes/config.toml, then from .notes/config.toml
+in repository. Each value can be overridden with an environment variable, e.g. NOTES_EDITOR.
+Keys: editor, pager (default $PAGER or less -R), ignored_directories, extensions, color_theme (default, light or none),
+auto_commit, auto_push, git_backend (shell or library), default_notebook, notebooks. Notebooks are named repositories, declared
+in user configuration, e.g: notes config set --global notebooks.work ~/notes/work
+Editor, pager, git_backend and notebooks can only be set in user configuration.
+
+Search queries combine terms with AND (or spaces), OR, NOT and parentheses. Terms are regular
+expressions, quoted terms can contain spaces. Use title:, path:, tag: or body: to search a term
+in a specific field, e.g: notes search 'title:ssh AND body:\"rsync over\" AND NOT path:archive/'
+
+Notes can be designated by ids (3 5), id ranges (8-12) or glob paths ('archive/**/*.md').
+Commands applied to several notes create a single commit.
 
 Examples:
 
@@ -25,12 +90,16 @@ Examples:
     $ notes list
     $ notes edit 123
     $ notes delete 123
+    $ notes move 123 archive/
+    $ notes delete 3 5 8-12
+    $ notes tag add 'archive/**/*.md' old
 
 With shortcuts:
 
     $ notes n my-awesome-idea
     $ notes e 123
     $ notes d 123
+    $ notes m 123 archive/
 
 See https://gitlab.com/remipassmoilesel/notes
 
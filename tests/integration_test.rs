@@ -3,10 +3,11 @@ mod integration_tests {
 
     use uuid::Uuid;
 
+    use notes::config::Config;
     use notes::console_output::ConsoleOutput;
     use notes::default_error::DefaultError;
-    use notes::parse_and_apply_command;
     use notes::test_env::new_sample_repo;
+    use notes::{parse_and_apply_command, DisplayOptions};
 
     // TODO: improve tests
 
@@ -15,7 +16,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec![]);
-        let res: Result<ConsoleOutput, DefaultError> = parse_and_apply_command(args, &config);
+        let res: Result<ConsoleOutput, DefaultError> = apply_command(args, &config);
         assert_eq!(res.unwrap_err().message, "Bad command, try: $ notes help");
     }
 
@@ -24,7 +25,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["help"]);
-        let res = parse_and_apply_command(args, &config).unwrap();
+        let res = apply_command(args, &config).unwrap();
         assert_eq!(res.stderr, "");
         assert!(res.stdout.contains("Clean all the brains !"));
         assert!(res.stdout.contains("Usage:"));
@@ -35,7 +36,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["h"]);
-        let res = parse_and_apply_command(args, &config).unwrap();
+        let res = apply_command(args, &config).unwrap();
         assert_eq!(res.stderr, "");
         assert!(res.stdout.contains("Clean all the brains !"));
         assert!(res.stdout.contains("Usage:"));
@@ -46,18 +47,52 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["search", "ab"]);
-        let res = parse_and_apply_command(args, &config).unwrap();
+        let res = apply_command(args, &config).unwrap();
         assert!(res.stdout.contains("2 results found"));
         assert!(res.stderr.is_empty());
     }
 
+    #[test]
+    fn search_and_list_in_directory() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["search", "ab", "--in", "a/a"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("1 results found for 'ab' in a/a"));
+
+        let args = fake_args(vec!["list", "--in", "a/a"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("aaa.md"));
+        assert!(!res.stdout.contains("a/ab.md"));
+
+        let args = fake_args(vec!["list", "--in", "unknown"]);
+        let res = apply_command(args, &config);
+        assert_eq!(res.unwrap_err().message, "Directory unknown not found.");
+    }
+
+    #[test]
+    fn search_and_list_as_json() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["search", "ab", "--in", "a/a", "--format", "json"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.starts_with("{\"count\":1,\"directory\":\"a/a\",\"query\":\"ab\",\"results\":[{"));
+        assert!(res.stdout.contains("\"title\":\"# test/assets/sample-repo/a/a/aab.md\""));
+
+        let args = fake_args(vec!["--format", "json", "list", "--in", "a/a"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.starts_with("[{\"name\":\"a/a\","));
+        assert!(res.stdout.contains("\"level\":0"));
+        assert!(res.stdout.contains("\"title\":\"# test/assets/sample-repo/a/a/aab.md\",\"tags\":[]"));
+    }
+
     #[test]
     fn new_note() {
         let config = new_sample_repo();
 
         let note_path = format!("test/note-{}.md", Uuid::new_v4().to_string());
         let args = fake_args(vec!["new", note_path.as_str()]);
-        let res = parse_and_apply_command(args, &config).unwrap();
+        let res = apply_command(args, &config).unwrap();
         assert!(res.stdout.contains(&format!("{}' created", note_path)));
         assert!(res.stderr.is_empty());
     }
@@ -67,7 +102,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["new", "a.md"]);
-        let res = parse_and_apply_command(args, &config).unwrap_err();
+        let res = apply_command(args, &config).unwrap_err();
         assert!(res.message.contains("Already exists:"));
     }
 
@@ -76,7 +111,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["list"]);
-        let res = parse_and_apply_command(args, &config);
+        let res = apply_command(args, &config);
         assert!(res.is_ok());
     }
 
@@ -85,7 +120,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["edit", "2"]);
-        let res = parse_and_apply_command(args, &config);
+        let res = apply_command(args, &config);
         assert!(res.is_ok());
     }
 
@@ -94,7 +129,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["edit", "999"]);
-        let res = parse_and_apply_command(args, &config);
+        let res = apply_command(args, &config);
         assert_eq!(res.unwrap_err().message, "Note with id 999 not found.");
     }
 
@@ -103,7 +138,7 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["delete", "2"]);
-        let res = parse_and_apply_command(args, &config).unwrap();
+        let res = apply_command(args, &config).unwrap();
         assert!(res.stdout.contains("sample-repo/a.md' deleted"));
         assert!(res.stderr.is_empty());
     }
@@ -113,10 +148,139 @@ mod integration_tests {
         let config = new_sample_repo();
 
         let args = fake_args(vec!["delete", "999"]);
-        let res = parse_and_apply_command(args, &config);
+        let res = apply_command(args, &config);
         assert_eq!(res.unwrap_err().message, "Note with id 999 not found.");
     }
 
+    #[test]
+    fn delete_several_notes() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["delete", "2", "4-5", "a/a/*.md", "--yes"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("sample-repo/a.md' deleted"));
+        assert!(res.stdout.contains("sample-repo/a/a/aab.md' deleted"));
+
+        let args = fake_args(vec!["list"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("@3"));
+        assert!(res.stdout.contains("@8"));
+        assert!(!res.stdout.contains("@2"));
+        assert!(!res.stdout.contains("@4"));
+        assert!(!res.stdout.contains("@7"));
+    }
+
+    #[test]
+    fn delete_several_notes_should_delete_nothing_if_one_is_missing() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["delete", "2", "999"]);
+        let res = apply_command(args, &config);
+        assert_eq!(res.unwrap_err().message, "Note with id 999 not found.");
+        assert!(config.storage_directory.join("a.md").exists());
+    }
+
+    #[test]
+    fn delete_directory() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["delete", "--dir", "a", "--yes"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("Directory 'a' deleted, 4 notes removed"));
+        assert!(!config.storage_directory.join("a").exists());
+    }
+
+    #[test]
+    fn config_set_and_get() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["config", "set", "auto_push", "false"]);
+        apply_command(args, &config).unwrap();
+        assert!(config.storage_directory.join(".notes/config.toml").exists());
+
+        let args = fake_args(vec!["config", "set", "editor", "touch pwned; true"]);
+        let res = apply_command(args, &config);
+        assert_eq!(res.unwrap_err().message, "editor can only be set in user configuration, use --global");
+
+        let args = fake_args(vec!["config", "set", "unknown", "value"]);
+        let res = apply_command(args, &config);
+        assert!(res.unwrap_err().message.starts_with("Unknown configuration key 'unknown'"));
+
+        let args = fake_args(vec!["config", "get", "extensions"]);
+        let res = apply_command(args, &config).unwrap();
+        assert_eq!(res.stdout, "md\n");
+    }
+
+    #[test]
+    fn notebooks() {
+        let mut config = new_sample_repo();
+        let work = new_sample_repo();
+        config.notebooks.insert("personal".to_string(), config.storage_directory.clone());
+        config.notebooks.insert("work".to_string(), work.storage_directory.clone());
+
+        let args = fake_args(vec!["--notebook", "work", "move", "2", "archive/"]);
+        apply_command(args, &config).unwrap();
+        assert!(work.storage_directory.join("archive/a.md").exists());
+        assert!(config.storage_directory.join("a.md").exists());
+
+        let args = fake_args(vec!["search", "a.md", "--all-notebooks"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("[personal]"));
+        assert!(res.stdout.contains("[work]"));
+
+        let args = fake_args(vec!["list", "--notebook", "unknown"]);
+        let res = apply_command(args, &config);
+        assert!(res.unwrap_err().message.starts_with("Unknown notebook 'unknown'"));
+    }
+
+    #[test]
+    fn move_note() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["move", "2", "archive/"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("sample-repo/a.md' moved to 'archive/'"));
+
+        let args = fake_args(vec!["edit", "2"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("sample-repo/archive/a.md' edited"));
+    }
+
+    #[test]
+    fn tag_and_list() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["tag", "add", "2", "sample"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("sample-repo/a.md' tags: sample"));
+
+        let args = fake_args(vec!["list", "--tag", "sample"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("@2"));
+        assert!(!res.stdout.contains("@3"));
+
+        let args = fake_args(vec!["tags"]);
+        let res = apply_command(args, &config).unwrap();
+        assert!(res.stdout.contains("1 tags found"));
+    }
+
+    #[test]
+    fn display_options() {
+        let config = new_sample_repo();
+
+        let args = fake_args(vec!["list"]);
+        let (_, display) = parse_and_apply_command(args, &config).unwrap();
+        assert_eq!(display, DisplayOptions { pager: true, banner: true });
+
+        let args = fake_args(vec!["list", "--no-pager", "--format", "json"]);
+        let (_, display) = parse_and_apply_command(args, &config).unwrap();
+        assert_eq!(display, DisplayOptions { pager: false, banner: false });
+    }
+
+    fn apply_command(args: Vec<String>, config: &Config) -> Result<ConsoleOutput, DefaultError> {
+        parse_and_apply_command(args, config).map(|(output, _)| output)
+    }
+
     fn fake_args(args: Vec<&str>) -> Vec<String> {
         let mut res = vec!["/intergation-test/note".to_string()];
         args.iter().for_each(|a| res.push(String::from(*a)));